@@ -0,0 +1,47 @@
+//! Times parsing a large generated Jonla file, run with `cargo bench --bench parse`
+use jonla_compiler::autogen::parse::{parse_program, parse_program_cst};
+use std::time::{Duration, Instant};
+
+/// A program with `n` numbered copies of some declarations that use most of the grammar
+fn generated_program(n: usize) -> String {
+    let mut src = String::new();
+    for i in 0..n {
+        src.push_str(&format!(
+            "data Nat{i} : Type {{\n    zero : Nat{i}\n    succ : Nat{i} -> Nat{i}\n}}\n\
+             def plus{i} : Nat{i} -> Nat{i} -> Nat{i} = / m : Nat{i}, n : Nat{i}. \
+             Nat{i}.elim (/ _ : Nat{i}. Nat{i}) n (/ _ : Nat{i}, ih : Nat{i}. Nat{i}.succ ih) m\n\
+             def id{i} : {{a : Type}} -> (x : a) -> a = / {{a : Type}}, x : a. \
+             let y : a = x ; let z : a = y ; z\n\
+             def four{i} : Nat{i} = plus{i} (succ (succ zero)) (id{i} {{Nat{i}}} (succ (succ zero)))\n\n",
+            i = i
+        ));
+    }
+    src
+}
+
+/// The fastest of `runs` runs of `f`
+fn time(runs: usize, mut f: impl FnMut()) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    for n in [100, 1000] {
+        let src = generated_program(n);
+        let ast = time(5, || assert!(parse_program(&src).is_ok()));
+        let cst = time(5, || assert!(parse_program_cst(&src).is_ok()));
+        println!(
+            "{} declarations, {} bytes: ast {:?}, cst {:?}",
+            4 * n,
+            src.len(),
+            ast,
+            cst
+        );
+    }
+}
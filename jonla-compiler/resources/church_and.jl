@@ -1,4 +1,6 @@
-let and : Type -> Type -> Type = / p:Type, q:Type. (c : Type) -> (p -> q -> c) -> c
-let conj : (p : Type) -> (q : Type) -> p -> q -> and p q = / p : Type, q : Type, x : p, y : q, c : Type, f : p -> q -> c. f x y
-let proj1 : (p : Type) -> (q : Type) -> and p q -> p = / p : Type, q : Type, a : and p q. a p (/ x : p, _ : q. x)
-let proj2 : (p : Type) -> (q : Type) -> and p q -> q = / p : Type, q : Type, a : and p q. a q (/ _ : p, y : q. y)
+universe u
+def and : Type u -> Type u -> Type (u + 1) = / p : Type u, q : Type u. (c : Type u) -> (p -> q -> c) -> c
+def conj : {p : Type u} -> {q : Type u} -> p -> q -> and p q = / x : p, y : q, c : Type u, f : p -> q -> c. f x y
+def proj1 : {p : Type u} -> {q : Type u} -> and p q -> p = / a : and p q. a p (/ x : p, _ : q. x)
+def proj2 : {p : Type u} -> {q : Type u} -> and p q -> q = / a : and p q. a q (/ _ : p, y : q. y)
+def swap : {p : Type u} -> {q : Type u} -> and p q -> and q p = / a : and p q. conj (proj2 a) (proj1 a)
@@ -1,3 +1,3 @@
-let wrapper : Type -> Type = / p:Type. (c : Type) -> (p -> c) -> c
-let proj : (p : Type) -> wrapper p -> p = / p : Type, a : wrapper p. a p (/ x : p. x)
-proj
\ No newline at end of file
+universe u
+def wrapper : Type u -> Type (u + 1) = / p : Type u. (c : Type u) -> (p -> c) -> c
+def proj : (p : Type u) -> wrapper p -> p = / p : Type u, a : wrapper p. a p (/ x : p. x)
@@ -0,0 +1,21 @@
+data Nat : Type {
+    zero : Nat
+    succ : Nat -> Nat
+}
+
+def plus : Nat -> Nat -> Nat = / m : Nat, n : Nat. Nat.elim (/ _ : Nat. Nat) n (/ _ : Nat, ih : Nat. succ ih) m
+
+data Eq (a : Type) (x : a) : a -> Type {
+    refl : Eq a x x
+}
+
+def two : Nat = succ (succ zero)
+def four : Nat = succ (succ two)
+def two_plus_two : Eq Nat (plus two two) four = refl Nat four
+
+data Vec (a : Type) : Nat -> Type {
+    nil : Vec a zero
+    cons : (n : Nat) -> a -> Vec a n -> Vec a (succ n)
+}
+
+def length : (a : Type) -> (n : Nat) -> Vec a n -> Nat = / a : Type. Vec.elim a (/ n : Nat, _ : Vec a n. Nat) zero (/ n : Nat, _ : a, _ : Vec a n, ih : Nat. succ ih)
@@ -1 +1,5 @@
-This -> That
\ No newline at end of file
+import church_and.jl
+import church_wrapper.jl
+
+def swap : (p : Type) -> (q : Type) -> church_and.and p q -> church_and.and q p = / p : Type, q : Type, a : church_and.and p q. church_and.conj (church_and.proj2 a) (church_and.proj1 a)
+def unwrap : (p : Type) -> church_wrapper.wrapper p -> p = church_wrapper.proj
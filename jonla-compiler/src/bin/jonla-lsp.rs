@@ -0,0 +1,10 @@
+use std::io;
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(err) = jonla_compiler::lsp::run(stdin.lock(), stdout.lock()) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
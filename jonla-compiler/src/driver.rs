@@ -0,0 +1,391 @@
+use crate::autogen::parse::{parse_program, parse_program_cst};
+use crate::lang::bytecode::compile;
+use crate::lang::codegen::emit_rust;
+use crate::lang::eval::normalize;
+use crate::lang::export::{export, import};
+use crate::lang::kernel::check_decls;
+use crate::lang::module::{LoadError, Loader};
+use crate::lang::pretty::{pretty_core, pretty_decl};
+use crate::lang::vm::Vm;
+use crate::lang::{parse_error_span, render_span};
+use crate::repl;
+use serde_json::json;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+/// Everything went fine
+pub const EXIT_SUCCESS: i32 = 0;
+/// One of the files contains an error
+pub const EXIT_FAILURE: i32 = 1;
+/// The arguments were invalid, or a file could not be read
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: jonla <command> [options] <files...>
+
+Commands:
+  check        Parse and type check the files, and the files they import, showing the goals of their holes
+  eval         Type check the files, and show the type and normal form of their definitions
+  export       Type check the files, and show their checked definitions in the export format, which `verify` reads
+  fmt          Show the files formatted
+  parse-tree   Show the concrete syntax tree of the files
+  repl         Start an interactive session
+  run          Type check the files, and show the values of their definitions, computed by compiling them to bytecode
+  rust         Type check the files, and show them compiled to Rust programs that show the values of their definitions
+  verify       Check exported definitions again, with the kernel instead of the type checker
+
+Options:
+  --error-format=human|json   How to show errors, defaults to human
+  -h, --help                  Show this message
+";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Command {
+    Check,
+    Eval,
+    Export,
+    Fmt,
+    ParseTree,
+    Repl,
+    Run,
+    Rust,
+    Verify,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+/// The line and column of the start of the span of `err`, both counting from 1
+fn line_column(err: &LoadError) -> Option<(usize, usize)> {
+    let (start, _) = err.span?;
+    let before = &err.src[..start];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Some((
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    ))
+}
+
+/// Shows an error in one of the files
+pub fn emit(err: &LoadError, format: ErrorFormat, out: &mut impl Write) -> io::Result<()> {
+    emit_diagnostic("error", err, format, out)
+}
+
+/// Shows the goal of a hole in one of the files, see `Loader::goals`
+pub fn emit_goal(goal: &LoadError, format: ErrorFormat, out: &mut impl Write) -> io::Result<()> {
+    emit_diagnostic("goal", goal, format, out)
+}
+
+/// Shows a message about a part of a file, the lines of the message after the first are shown below that part
+fn emit_diagnostic(
+    severity: &str,
+    err: &LoadError,
+    format: ErrorFormat,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        ErrorFormat::Human => {
+            let (header, notes) = match err.message.split_once('\n') {
+                Some((header, notes)) => (header, Some(notes)),
+                None => (err.message.as_str(), None),
+            };
+            writeln!(out, "{}: {}", severity, header)?;
+            match (err.span, line_column(err)) {
+                (Some(span), Some((line, column))) => {
+                    writeln!(out, " --> {}:{}:{}", err.path.display(), line, column)?;
+                    write!(out, "{}", render_span(&err.src, span))?;
+                }
+                _ => writeln!(out, " --> {}", err.path.display())?,
+            }
+            match notes {
+                Some(notes) => writeln!(out, "{}", notes),
+                None => Ok(()),
+            }
+        }
+        ErrorFormat::Json => {
+            let (line, column) = line_column(err).unzip();
+            let message = json!({
+                "file": err.path.display().to_string(),
+                "severity": severity,
+                "message": err.message,
+                "start": err.span.map(|(start, _)| start),
+                "end": err.span.map(|(_, end)| end),
+                "line": line,
+                "column": column,
+            });
+            writeln!(out, "{}", message)
+        }
+    }
+}
+
+/// Runs the command line in `args`, which doesn't include the program name.
+/// Results are written to `stdout` and errors to `stderr`, returns the exit code.
+pub fn run(
+    args: impl IntoIterator<Item = String>,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> io::Result<i32> {
+    let mut command = None;
+    let mut format = ErrorFormat::Human;
+    let mut files = vec![];
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let format_arg = match arg.strip_prefix("--error-format") {
+            Some("") => args.next(),
+            Some(value) => value.strip_prefix('=').map(str::to_string),
+            None => None,
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                write!(stdout, "{}", USAGE)?;
+                return Ok(EXIT_SUCCESS);
+            }
+            _ if arg.starts_with("--error-format") => match format_arg.as_deref() {
+                Some("human") => format = ErrorFormat::Human,
+                Some("json") => format = ErrorFormat::Json,
+                _ => return usage_error(stderr, "`--error-format` should be `human` or `json`"),
+            },
+            _ if arg.starts_with('-') => {
+                return usage_error(stderr, &format!("Unknown option `{}`", arg))
+            }
+            _ if command.is_none() => {
+                command = Some(match arg.as_str() {
+                    "check" => Command::Check,
+                    "eval" => Command::Eval,
+                    "export" => Command::Export,
+                    "fmt" => Command::Fmt,
+                    "parse-tree" => Command::ParseTree,
+                    "repl" => Command::Repl,
+                    "run" => Command::Run,
+                    "rust" => Command::Rust,
+                    "verify" => Command::Verify,
+                    _ => return usage_error(stderr, &format!("Unknown command `{}`", arg)),
+                })
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let command = match command {
+        None => return usage_error(stderr, "No command given"),
+        Some(Command::Repl) if !files.is_empty() => {
+            return usage_error(stderr, "`repl` doesn't take files, use `:load` instead")
+        }
+        Some(Command::Repl) => {
+            repl::run(io::stdin().lock(), stdout)?;
+            return Ok(EXIT_SUCCESS);
+        }
+        Some(_) if files.is_empty() => return usage_error(stderr, "No files given"),
+        Some(command) => command,
+    };
+
+    let mut exit_code = EXIT_SUCCESS;
+    //Files that import the same file share it, so it is only checked once
+    let mut loader = Loader::default();
+    for file in &files {
+        let path = Path::new(file);
+        let known_goals = loader.goals.len();
+        let result = match command {
+            Command::Check | Command::Eval => {
+                check_file(&mut loader, path, command == Command::Eval, stdout)?
+            }
+            Command::Run => run_file(&mut loader, path, stdout)?,
+            Command::Rust => rust_file(&mut loader, path, stdout)?,
+            Command::Export => export_file(&mut loader, path, stdout)?,
+            Command::Verify => verify_file(path)?,
+            _ => format_file(path, command == Command::ParseTree, stdout)?,
+        };
+        //A file with holes is not finished, so it fails like a file with errors
+        for goal in &loader.goals[known_goals..] {
+            emit_goal(goal, format, stderr)?;
+            exit_code = exit_code.max(EXIT_FAILURE);
+        }
+        for err in result.err().unwrap_or_default() {
+            emit(&err, format, stderr)?;
+            //Not being able to read a file given on the command line is a usage error
+            let unreadable = err.path == path && err.src.is_empty();
+            exit_code = exit_code.max(if unreadable { EXIT_USAGE } else { EXIT_FAILURE });
+        }
+    }
+    Ok(exit_code)
+}
+
+fn usage_error(stderr: &mut impl Write, message: &str) -> io::Result<i32> {
+    writeln!(stderr, "error: {}\n\n{}", message, USAGE)?;
+    Ok(EXIT_USAGE)
+}
+
+/// Checks a file and its imports.
+/// If `show` is set, shows the type and normal form of every definition in the file.
+fn check_file(
+    loader: &mut Loader,
+    path: &Path,
+    show: bool,
+    stdout: &mut impl Write,
+) -> io::Result<Result<(), Vec<LoadError>>> {
+    let id = match loader.load(path) {
+        Ok(id) => id,
+        Err(err) => return Ok(Err(err)),
+    };
+    if show {
+        let globals = &loader.globals;
+        for (name, id) in &loader.modules[id].defs {
+            let def = &globals.defs[*id];
+            writeln!(
+                stdout,
+                "{} : {} = {}",
+                name,
+                pretty_core(globals, &[], &def.typ),
+                pretty_core(globals, &[], &normalize(globals, &vec![], &def.value))
+            )?;
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Checks a file and its imports, and shows the value of every definition in the file, computed by the VM
+fn run_file(
+    loader: &mut Loader,
+    path: &Path,
+    stdout: &mut impl Write,
+) -> io::Result<Result<(), Vec<LoadError>>> {
+    let id = match loader.load(path) {
+        Ok(id) => id,
+        Err(err) => return Ok(Err(err)),
+    };
+    let globals = &loader.globals;
+    let program = compile(globals);
+    let mut vm = Vm::new(&program);
+    let module = &loader.modules[id];
+    for (name, id) in &module.defs {
+        match vm.global(*id) {
+            Ok(value) => writeln!(stdout, "{} = {}", name, value.render(globals))?,
+            Err(err) => {
+                return Ok(Err(vec![LoadError {
+                    path: path.to_path_buf(),
+                    src: module.src.clone(),
+                    span: None,
+                    message: err.render(globals),
+                }]))
+            }
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Checks a file and its imports, and shows them compiled to a Rust program
+fn rust_file(
+    loader: &mut Loader,
+    path: &Path,
+    stdout: &mut impl Write,
+) -> io::Result<Result<(), Vec<LoadError>>> {
+    let id = match loader.load(path) {
+        Ok(id) => id,
+        Err(err) => return Ok(Err(err)),
+    };
+    write!(
+        stdout,
+        "{}",
+        emit_rust(&loader.globals, &loader.modules[id].defs)
+    )?;
+    Ok(Ok(()))
+}
+
+/// Checks a file and its imports, and shows all checked definitions in the export format
+fn export_file(
+    loader: &mut Loader,
+    path: &Path,
+    stdout: &mut impl Write,
+) -> io::Result<Result<(), Vec<LoadError>>> {
+    let id = match loader.load(path) {
+        Ok(id) => id,
+        Err(err) => return Ok(Err(err)),
+    };
+    match export(&loader.globals) {
+        Ok(exported) => write!(stdout, "{}", exported)?,
+        Err(message) => {
+            return Ok(Err(vec![LoadError {
+                path: path.to_path_buf(),
+                src: loader.modules[id].src.clone(),
+                span: None,
+                message,
+            }]))
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Checks the definitions in an export with the kernel, see `export_file`
+fn verify_file(path: &Path) -> io::Result<Result<(), Vec<LoadError>>> {
+    let mut err = LoadError {
+        path: path.to_path_buf(),
+        src: String::new(),
+        span: None,
+        message: String::new(),
+    };
+    err.src = match std::fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(io_err) => {
+            err.message = format!("Could not read file: {}", io_err);
+            return Ok(Err(vec![err]));
+        }
+    };
+    err.message = match import(&err.src) {
+        Ok(decls) => match check_decls(&decls) {
+            Ok(_) => return Ok(Ok(())),
+            Err(kernel_err) => format!("`{}`: {}", kernel_err.name, kernel_err.message),
+        },
+        Err(message) => message,
+    };
+    Ok(Err(vec![err]))
+}
+
+/// Shows a file formatted, or its concrete syntax tree if `tree` is set
+fn format_file(
+    path: &Path,
+    tree: bool,
+    stdout: &mut impl Write,
+) -> io::Result<Result<(), Vec<LoadError>>> {
+    let mut err = LoadError {
+        path: path.to_path_buf(),
+        src: String::new(),
+        span: None,
+        message: String::new(),
+    };
+    let src = match std::fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(io_err) => {
+            err.message = format!("Could not read file: {}", io_err);
+            return Ok(Err(vec![err]));
+        }
+    };
+
+    let result = if tree {
+        parse_program_cst(&src)
+            .inner
+            .map(|ok| write!(stdout, "{}", ok.result.debug_dump(&src)))
+    } else {
+        parse_program(&src).inner.map(|ok| {
+            ok.result
+                .iter()
+                .try_for_each(|decl| writeln!(stdout, "{}", pretty_decl(decl)))
+        })
+    };
+    match result {
+        Ok(written) => written.map(Ok),
+        Err(parse_err) => {
+            err.span = Some(parse_error_span(&src, &parse_err));
+            err.message = parse_err
+                .message()
+                .unwrap_or_else(|| "Failed to parse".to_string());
+            err.src = src;
+            Ok(Err(vec![err]))
+        }
+    }
+}
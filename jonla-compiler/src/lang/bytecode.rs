@@ -0,0 +1,165 @@
+use crate::lang::core::Globals;
+use crate::lang::erase::{erase_def, Expr};
+
+/// An instruction of the virtual machine, see `vm::Vm`.
+/// Instructions push their result on the value stack, and the local variables of a call are kept apart from it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Instr {
+    /// Pushes the local variable in this slot
+    Local(usize),
+    /// Pushes the value of a global definition
+    Global(usize),
+    /// Pushes a function, with the local variables in these slots as the first locals of its calls.
+    /// A recursive function gets itself as the next local, and then its argument.
+    Closure {
+        chunk: usize,
+        captures: Vec<usize>,
+        rec: bool,
+    },
+    /// Pops an argument and a function, and calls the function
+    Apply,
+    /// Pops a value into a new local variable
+    Bind,
+    /// Removes this many local variables, the ones that were added last
+    Unbind(usize),
+    /// Pops the fields of a constructor, by the index of its data type and its own index
+    Con {
+        data: usize,
+        ctor: usize,
+        fields: usize,
+    },
+    /// Pops a constructed value, adds its fields as local variables, and jumps to the arm for its constructor
+    Match(Vec<usize>),
+    Jump(usize),
+    /// Fails, as the program reached a hole
+    Hole(usize),
+    /// Pushes a value that stands for an erased type or proof
+    Erased,
+    /// Ends the call, its result is on top of the stack
+    Return,
+}
+
+/// The code of a function, or of the value of a global definition
+#[derive(Clone, Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Instr>,
+}
+
+/// The compiled global definitions
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    pub chunks: Vec<Chunk>,
+    /// The chunk that computes every global definition, which takes no local variables
+    pub globals: Vec<usize>,
+}
+
+/// Erases the types in all global definitions, and compiles them
+pub fn compile(globals: &Globals) -> Program {
+    let mut program = Program::default();
+    for id in 0..globals.defs.len() {
+        let chunk = program.chunk(&erase_def(globals, id), vec![], 0);
+        program.globals.push(chunk);
+    }
+    program
+}
+
+impl Program {
+    /// Compiles `expr` into a new chunk, where the variables of the expression, by de Bruijn level,
+    /// are in the local slots `vars`, and the chunk starts with `locals` local variables
+    fn chunk(&mut self, expr: &Expr, mut vars: Vec<Option<usize>>, locals: usize) -> usize {
+        let id = self.chunks.len();
+        self.chunks.push(Chunk::default());
+        let mut code = vec![];
+        self.expr(expr, &mut vars, locals, &mut code);
+        code.push(Instr::Return);
+        self.chunks[id].code = code;
+        id
+    }
+
+    fn expr(
+        &mut self,
+        expr: &Expr,
+        vars: &mut Vec<Option<usize>>,
+        locals: usize,
+        code: &mut Vec<Instr>,
+    ) {
+        match expr {
+            Expr::Var(ix) => {
+                let slot = vars[vars.len() - 1 - ix].expect("Variable was not captured");
+                code.push(Instr::Local(slot));
+            }
+            Expr::Global(id) => code.push(Instr::Global(*id)),
+            Expr::Lam(_, body) | Expr::Rec(_, body) => {
+                let rec = matches!(expr, Expr::Rec(..));
+                let bound = if rec { 2 } else { 1 };
+                //The function only captures the variables it uses, which become its first locals
+                let mut free = vec![];
+                body.free_vars(bound, &mut free);
+                free.sort_unstable();
+                free.dedup();
+                let mut inner = vec![None; vars.len()];
+                let mut captures = vec![];
+                for (slot, ix) in free.into_iter().enumerate() {
+                    let lvl = vars.len() - 1 - ix;
+                    inner[lvl] = Some(slot);
+                    captures.push(vars[lvl].expect("Variable was not captured"));
+                }
+                let locals = captures.len() + bound;
+                inner.extend((captures.len()..locals).map(Some));
+                let chunk = self.chunk(body, inner, locals);
+                code.push(Instr::Closure {
+                    chunk,
+                    captures,
+                    rec,
+                });
+            }
+            Expr::App(f, a) => {
+                self.expr(f, vars, locals, code);
+                self.expr(a, vars, locals, code);
+                code.push(Instr::Apply);
+            }
+            Expr::Let(_, value, body) => {
+                self.expr(value, vars, locals, code);
+                code.push(Instr::Bind);
+                vars.push(Some(locals));
+                self.expr(body, vars, locals + 1, code);
+                vars.pop();
+                code.push(Instr::Unbind(1));
+            }
+            Expr::Con(data, ctor, fields) => {
+                for field in fields {
+                    self.expr(field, vars, locals, code);
+                }
+                code.push(Instr::Con {
+                    data: *data,
+                    ctor: *ctor,
+                    fields: fields.len(),
+                });
+            }
+            Expr::Case(target, arms) => {
+                self.expr(target, vars, locals, code);
+                let matched = code.len();
+                code.push(Instr::Match(vec![]));
+                let mut starts = vec![];
+                let mut jumps = vec![];
+                for (fields, body) in arms {
+                    let fields = fields.len();
+                    starts.push(code.len());
+                    vars.extend((locals..locals + fields).map(Some));
+                    self.expr(body, vars, locals + fields, code);
+                    vars.truncate(vars.len() - fields);
+                    code.push(Instr::Unbind(fields));
+                    jumps.push(code.len());
+                    code.push(Instr::Jump(0));
+                }
+                let end = code.len();
+                code[matched] = Instr::Match(starts);
+                for jump in jumps {
+                    code[jump] = Instr::Jump(end);
+                }
+            }
+            Expr::Hole(h) => code.push(Instr::Hole(*h)),
+            Expr::Erased => code.push(Instr::Erased),
+        }
+    }
+}
@@ -0,0 +1,940 @@
+use crate::autogen::ast::{Entry, Level as LevelTerm, Term};
+use crate::lang::conv::Unfolding;
+use crate::lang::core::{GlobalDef, Globals, HoleDef, Icit, Name, Namespace, Tm};
+use crate::lang::eval::{eval, normalize, quote, Closure, Env, Head, Val};
+use crate::lang::level::{Level, LevelVar};
+use crate::lang::pretty::pretty_core;
+use crate::lang::termination::check_structural;
+use crate::lang::unify::Metas;
+use crate::lang::{render_span, span_of, Span};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub struct TypeError {
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl TypeError {
+    /// Shows the error, below the part of `src` it is about if that is known
+    pub fn render(&self, src: &str) -> String {
+        match self.span {
+            Some(span) => format!("{}{}", render_span(src, span), self.message),
+            None => self.message.clone(),
+        }
+    }
+}
+
+/// A hole in a checked term, with what is known about the term that should fill it
+#[derive(Clone, Debug)]
+pub struct Goal {
+    pub span: Option<Span>,
+    /// The name of the hole, `_` for an anonymous one
+    pub name: Name,
+    /// The type the hole should have, in the context of the hole
+    pub typ: Tm,
+    /// The local variables in scope at the hole, from the outermost one, with their types
+    pub context: Vec<(Name, Tm)>,
+    /// The term that unification found for the hole, if it found one
+    pub solution: Option<Tm>,
+}
+
+impl Goal {
+    /// Describes the goal with its context, one variable per line
+    pub fn render(&self, globals: &Globals) -> String {
+        let names: Vec<Name> = self.context.iter().map(|(name, _)| name.clone()).collect();
+        let hole = match &*self.name {
+            "_" => "_".to_string(),
+            name => format!("?{}", name),
+        };
+        let mut out = format!(
+            "Hole `{}` should have type `{}`",
+            hole,
+            pretty_core(globals, &names, &self.typ)
+        );
+        if let Some(solution) = &self.solution {
+            out.push_str(&format!(
+                ", and was solved as `{}`",
+                pretty_core(globals, &names, solution)
+            ));
+        }
+        for (i, (name, typ)) in self.context.iter().enumerate() {
+            if &**name != "_" {
+                out.push_str(&format!(
+                    "\n  {} : {}",
+                    name,
+                    pretty_core(globals, &names[..i], typ)
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// The outcome of a top-level entry that passed the checker
+pub enum Checked {
+    /// The entry was a declaration, which was added to the globals with this id
+    Declared(usize),
+    /// The entry was an expression, with this normal form and type
+    Evaluated { value: Tm, typ: Tm },
+}
+
+/// Checks a top-level entry, adding it to `globals` and `names` if it is a declaration.
+/// The holes in the entry are added to `globals` as well, and their goals are returned.
+pub fn check_entry(
+    globals: &mut Globals,
+    names: &mut Namespace,
+    src: &str,
+    entry: &Entry,
+) -> Result<(Checked, Vec<Goal>), TypeError> {
+    match entry {
+        Entry::Declare {
+            name,
+            arg_type,
+            arg_value,
+        } => {
+            let checker = Checker::new(globals, names, src);
+            let def = checker.check_definition(name, arg_type, arg_value, false)?;
+            let (holes, goals) = (checker.holes(), checker.goals());
+            globals.holes.extend(holes);
+            let id = globals.define(def);
+            names.insert((*name).into(), id);
+            Ok((Checked::Declared(id), goals))
+        }
+        Entry::Eval { term } => {
+            let checker = Checker::new(globals, names, src);
+            let (tm, typ) = checker.infer(&Ctx::default(), term)?;
+            let typ = quote(globals, 0, &typ);
+            checker.solve_metas()?;
+            let checked = Checked::Evaluated {
+                value: normalize(globals, &vec![], &checker.zonk(&tm)),
+                typ: checker.zonk(&typ),
+            };
+            let (holes, goals) = (checker.holes(), checker.goals());
+            globals.holes.extend(holes);
+            Ok((checked, goals))
+        }
+    }
+}
+
+/// The local variables in scope while checking a term
+#[derive(Clone, Debug, Default)]
+pub struct Ctx {
+    /// The values of the variables, these are neutral for variables without a value
+    pub env: Env,
+    pub names: Vec<Name>,
+    pub types: Vec<Val>,
+}
+
+/// The definition that is being checked, which its value can refer to
+struct Recursive {
+    name: Name,
+    /// The id the definition gets when it is added to the globals
+    id: usize,
+    /// The universe parameters of the definition, which a reference to it passes on
+    levels: Vec<Level>,
+    typ: Val,
+}
+
+/// A hole that was checked, with the context it was checked in
+struct HoleGoal {
+    meta: usize,
+    /// The metavariable applied to the variables in scope
+    tm: Tm,
+    span: Option<Span>,
+    ctx: Ctx,
+    typ: Tm,
+}
+
+impl Ctx {
+    pub fn lvl(&self) -> usize {
+        self.env.len()
+    }
+
+    /// Adds a variable without a value
+    pub fn bind(&self, name: Name, typ: Val) -> Ctx {
+        self.define(name, typ, Val::var(self.lvl()))
+    }
+
+    /// Whether the variable with level `lvl` has no value
+    pub fn is_bound(&self, lvl: usize) -> bool {
+        matches!(&self.env[lvl], Val::Neutral(Head::Var(var), args) if *var == lvl && args.is_empty())
+    }
+
+    /// Adds a variable with a value
+    pub fn define(&self, name: Name, typ: Val, value: Val) -> Ctx {
+        let mut ctx = self.clone();
+        ctx.env.push(value);
+        ctx.names.push(name);
+        ctx.types.push(typ);
+        ctx
+    }
+}
+
+/// Checks surface terms against the global definitions, producing core terms
+pub struct Checker<'g, 'src> {
+    pub globals: &'g Globals,
+    /// The global definitions that can be referred to by name
+    pub names: &'g Namespace,
+    /// The source the checked terms were parsed from, used to locate errors
+    pub src: &'src str,
+    /// The universe levels and terms that are inferred while checking
+    metas: RefCell<Metas>,
+    /// The holes that were checked so far
+    goals: RefCell<Vec<HoleGoal>>,
+    /// The definition that is being checked, if it may refer to itself
+    recursive: RefCell<Option<Recursive>>,
+}
+
+impl<'g, 'src> Checker<'g, 'src> {
+    pub fn new(globals: &'g Globals, names: &'g Namespace, src: &'src str) -> Self {
+        Checker {
+            globals,
+            names,
+            src,
+            metas: RefCell::default(),
+            goals: RefCell::default(),
+            recursive: RefCell::default(),
+        }
+    }
+
+    /// Sets when global definitions are unfolded while comparing types, see `Unfolding`.
+    /// With lazy unfolding, types in error messages keep the names of the definitions they use.
+    pub fn with_unfolding(self, unfolding: Unfolding) -> Self {
+        self.metas.borrow_mut().unfolding = unfolding;
+        self
+    }
+
+    /// Checks `name : typ = value`, resulting in a definition that can be added to the globals next.
+    /// The value may refer to the definition itself, if it is structurally recursive or `partial`.
+    pub fn check_definition(
+        &self,
+        name: &'src str,
+        typ: &Term<'src>,
+        value: &Term<'src>,
+        partial: bool,
+    ) -> Result<GlobalDef, TypeError> {
+        let error = |message| TypeError {
+            span: span_of(self.src, name),
+            message,
+        };
+        let ctx = Ctx::default();
+        let (typ, _) = self.check_type(&ctx, typ)?;
+        let typ_val = eval(self.globals, &ctx.env, &typ);
+
+        //The definition refers to itself by the id it gets when it is added, with its own universes
+        let id = self.globals.defs.len();
+        let own_levels: Vec<Name> = self
+            .names
+            .universes()
+            .iter()
+            .filter(|u| typ.uses_level_param(u))
+            .cloned()
+            .collect();
+        *self.recursive.borrow_mut() = Some(Recursive {
+            name: name.into(),
+            id,
+            levels: own_levels
+                .iter()
+                .map(|u| Level::var(LevelVar::Param(u.clone())))
+                .collect(),
+            typ: typ_val.clone(),
+        });
+        let value = self.check(&ctx, value, &typ_val)?;
+        self.solve_metas()?;
+        let typ = self.zonk(&typ);
+        let value = self.zonk(&value);
+
+        //The definition is polymorphic over the universes it mentions
+        let levels: Vec<Name> = self
+            .names
+            .universes()
+            .iter()
+            .filter(|u| typ.uses_level_param(u) || value.uses_level_param(u))
+            .cloned()
+            .collect();
+        let recursive = value.find_global(&|g| g == id).is_some();
+        if recursive && levels != own_levels {
+            return Err(error(format!(
+                "`{}` refers to itself, so it can only use the universes in its type",
+                name
+            )));
+        }
+
+        //A partial definition may not terminate, so it can't be used by definitions that have to
+        let is_partial = |g: usize| self.globals.defs.get(g).is_some_and(|def| def.partial);
+        if let Some(g) = typ.find_global(&is_partial) {
+            return Err(error(format!(
+                "The type of `{}` uses the partial definition `{}`, which can't be used in types",
+                name, self.globals.defs[g].name
+            )));
+        }
+        if let Some(g) = value.find_global(&is_partial).filter(|_| !partial) {
+            return Err(error(format!(
+                "`{}` uses the partial definition `{}`, so it should be declared `partial` as well",
+                name, self.globals.defs[g].name
+            )));
+        }
+
+        let def = GlobalDef {
+            name: name.into(),
+            levels,
+            typ_val: eval(self.globals, &ctx.env, &typ),
+            value_val: eval(self.globals, &ctx.env, &value),
+            typ,
+            value,
+            recursive,
+            partial,
+        };
+        if recursive && !partial {
+            if let Err(call) = check_structural(self.globals, id, &def.value) {
+                //The call is shown with the definition added, as it refers to it
+                let mut globals = self.globals.clone();
+                globals.define(def);
+                let shown = pretty_core(&globals, &call.names, &call.call);
+                return Err(error(if call.decreasing.is_empty() {
+                    format!(
+                        "`{}` may not terminate, as no argument of the recursive call `{}` is smaller than the parameter \
+                         it is passed for. A parameter gets smaller by taking one of its fields with an eliminator, \
+                         definitions that don't have to terminate can be declared `partial`",
+                        name, shown
+                    )
+                } else {
+                    format!(
+                        "`{}` may not terminate, as the recursive call `{}` doesn't make the same argument smaller \
+                         as the calls before it",
+                        name, shown
+                    )
+                }));
+            }
+        }
+        Ok(def)
+    }
+
+    /// Solves the universe levels that were inferred so far, and checks that every metavariable was solved, see `zonk`.
+    /// The holes that weren't solved are left as holes, which are added to the globals with `holes`.
+    pub fn solve_metas(&self) -> Result<(), TypeError> {
+        let mut metas = self.metas.borrow_mut();
+        metas.freeze_holes(self.globals.holes.len());
+        if let Some(unsolved) = metas.unsolved() {
+            return Err(TypeError {
+                span: unsolved.span,
+                message: unsolved.description.clone(),
+            });
+        }
+        metas.levels.solve().map_err(|constraint| TypeError {
+            span: constraint.span,
+            message: format!(
+                "Universe inconsistency: `{}` should be contained in `{}`",
+                pretty_core(self.globals, &[], &Tm::Type(constraint.lhs)),
+                pretty_core(self.globals, &[], &Tm::Type(constraint.rhs))
+            ),
+        })
+    }
+
+    /// The holes that were left unsolved by `solve_metas`, which should be added to the globals in this order
+    pub fn holes(&self) -> Vec<HoleDef> {
+        let metas = self.metas.borrow();
+        let goals = self.goals.borrow();
+        metas
+            .frozen
+            .iter()
+            .map(|m| {
+                let ctx = &goals.iter().find(|goal| goal.meta == *m).unwrap().ctx;
+                HoleDef {
+                    name: metas.terms[*m].hole.clone().unwrap(),
+                    typ: metas.zonk(&metas.terms[*m].typ),
+                    context: (0..ctx.lvl()).filter(|lvl| ctx.is_bound(*lvl)).count(),
+                }
+            })
+            .collect()
+    }
+
+    /// The goals of the holes that were written as `?name`, and of the anonymous holes that weren't solved
+    pub fn goals(&self) -> Vec<Goal> {
+        let metas = self.metas.borrow();
+        let goals = self.goals.borrow();
+        goals
+            .iter()
+            .filter_map(|goal| {
+                let name = metas.terms[goal.meta].hole.clone().unwrap();
+                let solved = !metas.frozen.contains(&goal.meta);
+                if solved && &*name == "_" {
+                    return None;
+                }
+                let ctx = &goal.ctx;
+                let context = (0..ctx.lvl())
+                    .map(|lvl| {
+                        let typ = quote(self.globals, lvl, &ctx.types[lvl]);
+                        (ctx.names[lvl].clone(), metas.zonk(&typ))
+                    })
+                    .collect();
+                Some(Goal {
+                    span: goal.span,
+                    name,
+                    typ: metas.zonk(&goal.typ),
+                    context,
+                    solution: solved.then(|| metas.zonk(&goal.tm)),
+                })
+            })
+            .collect()
+    }
+
+    /// Fills in the inferred terms and universe levels in a checked term, after `solve_metas`
+    pub fn zonk(&self, tm: &Tm) -> Tm {
+        self.metas.borrow().zonk(tm)
+    }
+
+    /// Checks that `term` is a type, returning the level of its universe
+    pub fn check_type(&self, ctx: &Ctx, term: &Term<'src>) -> Result<(Tm, Level), TypeError> {
+        let (tm, typ) = self.infer(ctx, term)?;
+        let typ = self.force(&typ);
+        //A type that isn't known yet, such as that of a hole, is solved with a universe
+        if let Val::Neutral(Head::Meta(_), _) = typ {
+            let level = self.metas.borrow_mut().levels.fresh();
+            if self.convertible(ctx, term, &typ, &Val::Type(level.clone()), false) {
+                return Ok((tm, level));
+            }
+        }
+        match typ {
+            Val::Type(level) => Ok((tm, level)),
+            _ => Err(self.error(
+                term,
+                format!(
+                    "Expected a type, but found a term of type `{}`",
+                    self.show(ctx, &typ)
+                ),
+            )),
+        }
+    }
+
+    /// Requires the universe level `lhs` to be at most `rhs`, returns false if it is known not to be
+    pub fn level_leq(&self, term: &Term<'src>, lhs: &Level, rhs: &Level) -> bool {
+        let mut metas = self.metas.borrow_mut();
+        metas.levels.span = anchor(self.src, term);
+        metas.levels.leq(lhs, rhs)
+    }
+
+    pub fn check(&self, ctx: &Ctx, term: &Term<'src>, expected: &Val) -> Result<Tm, TypeError> {
+        match (term, &self.force(expected)) {
+            (Term::Hole { .. } | Term::Var { name: "_" }, _) => Ok(self.hole(ctx, term, expected)),
+            (
+                Term::FunConstruct {
+                    name,
+                    arg_type,
+                    body,
+                },
+                Val::Pi(_, Icit::Explicit, dom, cod),
+            )
+            | (
+                Term::ImplicitFunConstruct {
+                    name,
+                    arg_type,
+                    body,
+                },
+                Val::Pi(_, Icit::Implicit, dom, cod),
+            ) => {
+                let icit = icit_of(term);
+                let (arg_type, _) = self.check_type(ctx, arg_type)?;
+                let arg_type_val = eval(self.globals, &ctx.env, &arg_type);
+                if !self.convertible(ctx, term, &arg_type_val, dom, false) {
+                    return Err(self.error(
+                        term,
+                        format!(
+                            "Type mismatch: the argument `{}` should have type `{}`, but was annotated with `{}`",
+                            name,
+                            self.show(ctx, dom),
+                            self.show(ctx, &arg_type_val)
+                        ),
+                    ));
+                }
+                let body_ctx = ctx.bind((*name).into(), arg_type_val);
+                let body_type = cod.apply(self.globals, Val::var(ctx.lvl()));
+                let body = self.check(&body_ctx, body, &body_type)?;
+                Ok(Tm::Lam {
+                    name: (*name).into(),
+                    icit,
+                    typ: Rc::new(arg_type),
+                    body: Rc::new(body),
+                })
+            }
+            (
+                Term::Let {
+                    name,
+                    arg_type,
+                    arg_value,
+                    body,
+                },
+                _,
+            ) => {
+                let (typ, typ_val, value, value_val) = self.check_let(ctx, arg_type, arg_value)?;
+                let body_ctx = ctx.define((*name).into(), typ_val, value_val);
+                let body = self.check(&body_ctx, body, expected)?;
+                Ok(Tm::Let {
+                    name: (*name).into(),
+                    typ: Rc::new(typ),
+                    value: Rc::new(value),
+                    body: Rc::new(body),
+                })
+            }
+            //The lambda of an implicit function is inserted, with its argument in scope by the name in the type
+            (_, Val::Pi(name, Icit::Implicit, dom, cod)) => {
+                let body_ctx = ctx.bind(name.clone(), (**dom).clone());
+                let body_type = cod.apply(self.globals, Val::var(ctx.lvl()));
+                let body = self.check(&body_ctx, term, &body_type)?;
+                Ok(Tm::Lam {
+                    name: name.clone(),
+                    icit: Icit::Implicit,
+                    typ: Rc::new(quote(self.globals, ctx.lvl(), dom)),
+                    body: Rc::new(body),
+                })
+            }
+            _ => {
+                let (tm, typ) = self.infer(ctx, term)?;
+                let (tm, typ) = match term {
+                    Term::ImplicitFunConstruct { .. } => (tm, typ),
+                    _ => self.insert_implicits(ctx, term, tm, typ),
+                };
+                if !self.convertible(ctx, term, &typ, expected, true) {
+                    return Err(self.error(
+                        term,
+                        format!(
+                            "Type mismatch: expected `{}`, but found `{}`",
+                            self.show(ctx, expected),
+                            self.show(ctx, &typ)
+                        ),
+                    ));
+                }
+                Ok(tm)
+            }
+        }
+    }
+
+    pub fn infer(&self, ctx: &Ctx, term: &Term<'src>) -> Result<(Tm, Val), TypeError> {
+        match term {
+            Term::Type { .. } => Ok((Tm::Type(Level::zero()), Val::Type(Level::constant(1)))),
+            Term::Universe { level, .. } => {
+                let level = self.level(level)?;
+                Ok((Tm::Type(level.clone()), Val::Type(level.succ())))
+            }
+            //The type of a hole is inferred as well
+            Term::Hole { .. } | Term::Var { name: "_" } => {
+                let level = self.metas.borrow_mut().levels.fresh();
+                let span = anchor(self.src, term);
+                let typ = self.fresh_meta(
+                    ctx,
+                    &Val::Type(level),
+                    span,
+                    format!(
+                        "Could not infer the type of the hole `{}`",
+                        &self.src[span.unwrap().0..span.unwrap().1]
+                    ),
+                );
+                let typ = eval(self.globals, &ctx.env, &typ);
+                Ok((self.hole(ctx, term, &typ), typ))
+            }
+            Term::Var { name } => {
+                let recursive = self.recursive.borrow();
+                let recursive = recursive.as_ref().filter(|r| &*r.name == *name);
+                if let Some(i) = ctx.names.iter().rposition(|n| &**n == *name) {
+                    Ok((Tm::Var(ctx.lvl() - i - 1), ctx.types[i].clone()))
+                } else if let Some(recursive) = recursive {
+                    Ok((
+                        Tm::Global(recursive.id, recursive.levels.clone()),
+                        recursive.typ.clone(),
+                    ))
+                } else if let Some(id) = self.names.lookup(name) {
+                    //Universe levels that aren't given are inferred
+                    let levels = (0..self.globals.defs[id].levels.len())
+                        .map(|_| self.metas.borrow_mut().levels.fresh())
+                        .collect();
+                    Ok(self.global(id, levels))
+                } else {
+                    Err(self.error(term, format!("Unknown variable `{}`", name)))
+                }
+            }
+            Term::Instantiate { name, levels } => {
+                let id = match self.names.lookup(name) {
+                    Some(id) if !ctx.names.iter().any(|n| &**n == *name) => id,
+                    Some(_) => {
+                        return Err(self.error(
+                            term,
+                            format!(
+                                "The local variable `{}` can't be given universe levels",
+                                name
+                            ),
+                        ))
+                    }
+                    None => return Err(self.error(term, format!("Unknown variable `{}`", name))),
+                };
+                let expected = self.globals.defs[id].levels.len();
+                if levels.len() != expected {
+                    return Err(self.error(
+                        term,
+                        format!(
+                            "`{}` is polymorphic over {} universe(s), but {} level(s) were given",
+                            name,
+                            expected,
+                            levels.len()
+                        ),
+                    ));
+                }
+                let levels = levels
+                    .iter()
+                    .map(|l| self.level(l))
+                    .collect::<Result<_, _>>()?;
+                Ok(self.global(id, levels))
+            }
+            Term::Let {
+                name,
+                arg_type,
+                arg_value,
+                body,
+            } => {
+                let (typ, typ_val, value, value_val) = self.check_let(ctx, arg_type, arg_value)?;
+                let body_ctx = ctx.define((*name).into(), typ_val, value_val);
+                let (body, body_type) = self.infer(&body_ctx, body)?;
+                Ok((
+                    Tm::Let {
+                        name: (*name).into(),
+                        typ: Rc::new(typ),
+                        value: Rc::new(value),
+                        body: Rc::new(body),
+                    },
+                    body_type,
+                ))
+            }
+            Term::FunType {
+                name,
+                arg_type,
+                body_type,
+            }
+            | Term::ImplicitFunType {
+                name,
+                arg_type,
+                body_type,
+            } => {
+                let (dom, dom_level) = self.check_type(ctx, arg_type)?;
+                let dom_val = eval(self.globals, &ctx.env, &dom);
+                let (cod, cod_level) =
+                    self.check_type(&ctx.bind((*name).into(), dom_val), body_type)?;
+                Ok((
+                    Tm::Pi {
+                        name: (*name).into(),
+                        icit: icit_of(term),
+                        dom: Rc::new(dom),
+                        cod: Rc::new(cod),
+                    },
+                    Val::Type(dom_level.max(&cod_level)),
+                ))
+            }
+            Term::FunConstruct {
+                name,
+                arg_type,
+                body,
+            }
+            | Term::ImplicitFunConstruct {
+                name,
+                arg_type,
+                body,
+            } => {
+                let icit = icit_of(term);
+                let (typ, _) = self.check_type(ctx, arg_type)?;
+                let typ_val = eval(self.globals, &ctx.env, &typ);
+                let body_ctx = ctx.bind((*name).into(), typ_val.clone());
+                let (body, body_type) = self.infer(&body_ctx, body)?;
+                let cod = quote(self.globals, body_ctx.lvl(), &body_type);
+                Ok((
+                    Tm::Lam {
+                        name: (*name).into(),
+                        icit,
+                        typ: Rc::new(typ),
+                        body: Rc::new(body),
+                    },
+                    Val::Pi(
+                        (*name).into(),
+                        icit,
+                        Rc::new(typ_val),
+                        Closure {
+                            env: ctx.env.clone(),
+                            body: Rc::new(cod),
+                        },
+                    ),
+                ))
+            }
+            Term::FunDestruct { func, arg } | Term::ImplicitFunDestruct { func, arg } => {
+                let icit = icit_of(term);
+                let (func_tm, func_type) = self.infer(ctx, func)?;
+                //The implicit arguments before an explicit argument are inferred
+                let (func_tm, func_type) = match icit {
+                    Icit::Explicit => self.insert_implicits(ctx, func, func_tm, func_type),
+                    Icit::Implicit => (func_tm, func_type),
+                };
+                //A function whose type isn't known yet, such as a hole, gets a function type
+                let forced = match self.force(&func_type) {
+                    forced @ Val::Neutral(Head::Meta(_), _) => {
+                        self.fresh_pi(ctx, func, &forced, icit).unwrap_or(forced)
+                    }
+                    forced => forced,
+                };
+                let (dom, cod) = match forced {
+                    Val::Pi(_, i, dom, cod) if i == icit => (dom, cod),
+                    _ => {
+                        let expected = match icit {
+                            Icit::Explicit => "a function",
+                            Icit::Implicit => "a function with an implicit argument",
+                        };
+                        return Err(self.error(
+                            func,
+                            format!(
+                                "Expected {}, but found a term of type `{}`",
+                                expected,
+                                self.show(ctx, &func_type)
+                            ),
+                        ));
+                    }
+                };
+                let arg_tm = self.check(ctx, arg, &dom)?;
+                let arg_val = eval(self.globals, &ctx.env, &arg_tm);
+                Ok((
+                    Tm::App(Rc::new(func_tm), Rc::new(arg_tm), icit),
+                    cod.apply(self.globals, arg_val),
+                ))
+            }
+        }
+    }
+
+    fn check_let(
+        &self,
+        ctx: &Ctx,
+        arg_type: &Term<'src>,
+        arg_value: &Term<'src>,
+    ) -> Result<(Tm, Val, Tm, Val), TypeError> {
+        let (typ, _) = self.check_type(ctx, arg_type)?;
+        let typ_val = eval(self.globals, &ctx.env, &typ);
+        let value = self.check(ctx, arg_value, &typ_val)?;
+        let value_val = eval(self.globals, &ctx.env, &value);
+        Ok((typ, typ_val, value, value_val))
+    }
+
+    /// Applies `tm` to new metavariables for the implicit arguments at the start of its type `typ`
+    /// The type is not brought to weak head normal form, so it keeps the names of the definitions it uses
+    fn insert_implicits(&self, ctx: &Ctx, term: &Term<'src>, tm: Tm, typ: Val) -> (Tm, Val) {
+        let mut tm = tm;
+        let mut typ = typ;
+        while let Val::Pi(name, Icit::Implicit, dom, cod) = self.force(&typ) {
+            let span = anchor(self.src, term);
+            let description = match span {
+                Some((start, end)) => format!(
+                    "Could not infer the implicit argument `{}` of `{}`",
+                    name,
+                    &self.src[start..end]
+                ),
+                None => format!("Could not infer the implicit argument `{}`", name),
+            };
+            let meta = self.fresh_meta(ctx, &dom, span, description);
+            let meta_val = eval(self.globals, &ctx.env, &meta);
+            tm = Tm::App(Rc::new(tm), Rc::new(meta), Icit::Implicit);
+            typ = cod.apply(self.globals, meta_val);
+        }
+        (tm, typ)
+    }
+
+    /// A function type whose argument and result types are new metavariables, if `typ` can be unified with it
+    fn fresh_pi(&self, ctx: &Ctx, term: &Term<'src>, typ: &Val, icit: Icit) -> Option<Val> {
+        let span = anchor(self.src, term);
+        let description = |part| match span {
+            Some((start, end)) => format!(
+                "Could not infer the {} type of `{}`",
+                part,
+                &self.src[start..end]
+            ),
+            None => format!("Could not infer the {} type of a function", part),
+        };
+        let level = self.metas.borrow_mut().levels.fresh();
+        let dom = self.fresh_meta(ctx, &Val::Type(level), span, description("argument"));
+        let dom = eval(self.globals, &ctx.env, &dom);
+        let name: Name = "x".into();
+        let cod_ctx = ctx.bind(name.clone(), dom.clone());
+        let level = self.metas.borrow_mut().levels.fresh();
+        let cod = self.fresh_meta(&cod_ctx, &Val::Type(level), span, description("result"));
+        let pi = Val::Pi(
+            name,
+            icit,
+            Rc::new(dom),
+            Closure {
+                env: ctx.env.clone(),
+                body: Rc::new(cod),
+            },
+        );
+        self.convertible(ctx, term, typ, &pi, false).then_some(pi)
+    }
+
+    /// A new metavariable for the hole `term`, whose goal is recorded
+    fn hole(&self, ctx: &Ctx, term: &Term<'src>, typ: &Val) -> Tm {
+        let name = match term {
+            Term::Hole { name } => *name,
+            _ => "_",
+        };
+        let span = anchor(self.src, term);
+        let m = self
+            .metas
+            .borrow_mut()
+            .fresh_hole(self.meta_type(ctx, typ), span, name.into());
+        let tm = self.applied_meta(ctx, m);
+        self.goals.borrow_mut().push(HoleGoal {
+            meta: m,
+            tm: tm.clone(),
+            span,
+            ctx: ctx.clone(),
+            typ: quote(self.globals, ctx.lvl(), typ),
+        });
+        tm
+    }
+
+    /// A new metavariable of type `typ`, applied to the variables in scope that don't have a value
+    fn fresh_meta(&self, ctx: &Ctx, typ: &Val, span: Option<Span>, description: String) -> Tm {
+        let m = self
+            .metas
+            .borrow_mut()
+            .fresh(self.meta_type(ctx, typ), span, description);
+        self.applied_meta(ctx, m)
+    }
+
+    /// The type of a metavariable of type `typ` in `ctx`
+    fn meta_type(&self, ctx: &Ctx, typ: &Val) -> Tm {
+        //The metavariable is a closed function of the variables, the variables with a value are `let`s in its type
+        let mut closed = quote(self.globals, ctx.lvl(), typ);
+        for lvl in (0..ctx.lvl()).rev() {
+            let name = ctx.names[lvl].clone();
+            let typ = Rc::new(quote(self.globals, lvl, &ctx.types[lvl]));
+            closed = if ctx.is_bound(lvl) {
+                Tm::Pi {
+                    name,
+                    icit: Icit::Explicit,
+                    dom: typ,
+                    cod: Rc::new(closed),
+                }
+            } else {
+                Tm::Let {
+                    name,
+                    typ,
+                    value: Rc::new(quote(self.globals, lvl, &ctx.env[lvl])),
+                    body: Rc::new(closed),
+                }
+            };
+        }
+        closed
+    }
+
+    /// The metavariable `m` applied to the variables in scope that don't have a value
+    fn applied_meta(&self, ctx: &Ctx, m: usize) -> Tm {
+        let vars = (0..ctx.lvl()).filter(|lvl| ctx.is_bound(*lvl));
+        Tm::Meta(m).apply_all(vars.map(|lvl| Tm::Var(ctx.lvl() - 1 - lvl)))
+    }
+
+    /// Replaces solved metavariables at the head of a value, see `Metas::force`
+    fn force(&self, val: &Val) -> Val {
+        self.metas.borrow().force(self.globals, val)
+    }
+
+    /// A reference to a global definition with the given universe levels, and its type
+    fn global(&self, id: usize, levels: Vec<Level>) -> (Tm, Val) {
+        let def = &self.globals.defs[id];
+        let typ = if levels.is_empty() {
+            def.typ_val.clone()
+        } else {
+            eval(self.globals, &vec![], &def.instantiate(&def.typ, &levels))
+        };
+        (Tm::Global(id, levels), typ)
+    }
+
+    fn level(&self, level: &LevelTerm<'src>) -> Result<Level, TypeError> {
+        let error = |name: &str, message| TypeError {
+            span: span_of(self.src, name),
+            message,
+        };
+        let number = |n: &str| {
+            n.parse::<usize>()
+                .map_err(|_| error(n, format!("The universe level `{}` is too large", n)))
+        };
+        match level {
+            LevelTerm::Const { value } => number(value).map(Level::constant),
+            LevelTerm::LevelVar { name }
+                if self.names.universes().iter().any(|u| &**u == *name) =>
+            {
+                Ok(Level::var(LevelVar::Param((*name).into())))
+            }
+            LevelTerm::LevelVar { name } => Err(error(
+                name,
+                format!(
+                    "Unknown universe `{}`, universes are declared with `universe {}`",
+                    name, name
+                ),
+            )),
+            LevelTerm::Succ { level, offset } => Ok(self.level(level)?.plus(number(offset)?)),
+            LevelTerm::Max { left, right } => Ok(self.level(left)?.max(&self.level(right)?)),
+        }
+    }
+
+    /// Unifies two types, where `a` may be in a smaller universe than `b` if `cumulative` is set
+    fn convertible(
+        &self,
+        ctx: &Ctx,
+        term: &Term<'src>,
+        a: &Val,
+        b: &Val,
+        cumulative: bool,
+    ) -> bool {
+        let mut metas = self.metas.borrow_mut();
+        metas.levels.span = anchor(self.src, term);
+        metas.unify(self.globals, ctx.lvl(), a, b, cumulative)
+    }
+
+    /// Formats a value as source, using the names of the variables in `ctx`
+    pub fn show(&self, ctx: &Ctx, val: &Val) -> String {
+        pretty_core(
+            self.globals,
+            &ctx.names,
+            &self.zonk(&quote(self.globals, ctx.lvl(), val)),
+        )
+    }
+
+    fn error(&self, term: &Term<'src>, message: String) -> TypeError {
+        TypeError {
+            span: anchor(self.src, term),
+            message,
+        }
+    }
+}
+
+/// The position of a name in the term, used to point at the term in error messages
+fn anchor(src: &str, term: &Term) -> Option<Span> {
+    match term {
+        Term::Type { keyword } | Term::Universe { keyword, .. } => span_of(src, keyword),
+        Term::Var { name } | Term::Instantiate { name, .. } => span_of(src, name),
+        //The span includes the `?`
+        Term::Hole { name } => span_of(src, name).map(|(start, end)| (start - 1, end)),
+        Term::Let { name, arg_type, .. }
+        | Term::FunType { name, arg_type, .. }
+        | Term::ImplicitFunType { name, arg_type, .. }
+        | Term::FunConstruct { name, arg_type, .. }
+        | Term::ImplicitFunConstruct { name, arg_type, .. } => {
+            span_of(src, name).or_else(|| anchor(src, arg_type))
+        }
+        Term::FunDestruct { func, arg } | Term::ImplicitFunDestruct { func, arg } => {
+            anchor(src, func).or_else(|| anchor(src, arg))
+        }
+    }
+}
+
+/// Whether a binder or application in the source is implicit
+fn icit_of(term: &Term) -> Icit {
+    match term {
+        Term::ImplicitFunType { .. }
+        | Term::ImplicitFunConstruct { .. }
+        | Term::ImplicitFunDestruct { .. } => Icit::Implicit,
+        _ => Icit::Explicit,
+    }
+}
@@ -0,0 +1,356 @@
+use crate::lang::core::{Globals, Name};
+use crate::lang::erase::{erase_def, Expr};
+use crate::lang::pretty::pretty_core;
+use crate::lang::vm::VmError;
+
+/// The runtime of generated programs, which is included in them.
+/// Functions are closures, and global functions that get fewer arguments than they take are partial applications.
+const RUNTIME: &str = r#"mod rt {
+    use std::rc::Rc;
+
+    /// A value at run time, types and proofs are erased so they have none
+    #[derive(Clone)]
+    pub enum Value {
+        Fun(Rc<dyn Fn(Value) -> Value>),
+        /// A global function that takes this many arguments, applied to fewer of them
+        Pap(usize, fn(&[Value]) -> Value, Rc<[Value]>),
+        /// A constructor, by its index in its data type and its name, applied to its fields
+        Con(usize, &'static str, Rc<[Value]>),
+        Erased,
+    }
+
+    pub fn lam(f: impl Fn(Value) -> Value + 'static) -> Value {
+        Value::Fun(Rc::new(f))
+    }
+
+    /// A function that gets itself as its first argument, so it can call itself
+    pub fn fix(f: impl Fn(Value, Value) -> Value + 'static) -> Value {
+        fix_rc(Rc::new(f))
+    }
+
+    fn fix_rc(f: Rc<dyn Fn(Value, Value) -> Value>) -> Value {
+        Value::Fun(Rc::new(move |arg| f(fix_rc(f.clone()), arg)))
+    }
+
+    pub fn pap(arity: usize, code: fn(&[Value]) -> Value) -> Value {
+        Value::Pap(arity, code, Rc::from(vec![]))
+    }
+
+    pub fn apply(f: Value, arg: Value) -> Value {
+        match f {
+            Value::Fun(f) => f(arg),
+            Value::Pap(arity, code, args) => {
+                let args: Rc<[Value]> = args.iter().cloned().chain([arg]).collect();
+                if args.len() == arity {
+                    code(&args)
+                } else {
+                    Value::Pap(arity, code, args)
+                }
+            }
+            //A type applied to something is still a type
+            Value::Erased => Value::Erased,
+            Value::Con(..) => unreachable!("Applied a value that is not a function"),
+        }
+    }
+
+    pub fn con(tag: usize, name: &'static str, fields: Vec<Value>) -> Value {
+        Value::Con(tag, name, fields.into())
+    }
+
+    /// The index of the constructor of a value, and its fields
+    pub fn fields(value: Value) -> (usize, Rc<[Value]>) {
+        match value {
+            Value::Con(tag, _, fields) => (tag, fields),
+            _ => unreachable!("Matched a value that is not constructed"),
+        }
+    }
+
+    pub fn show(value: &Value) -> String {
+        match value {
+            Value::Fun(_) | Value::Pap(..) => "<function>".to_string(),
+            Value::Erased => "<erased>".to_string(),
+            Value::Con(_, name, fields) => {
+                let mut out = name.to_string();
+                for field in fields.iter() {
+                    match field {
+                        Value::Con(_, _, fields) if !fields.is_empty() => {
+                            out.push_str(&format!(" ({})", show(field)))
+                        }
+                        _ => out.push_str(&format!(" {}", show(field))),
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+"#;
+
+/// Names that generated functions can't have, as they are Rust keywords or taken by the generated code
+const RESERVED: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro",
+    "main", "match", "mod", "move", "mut", "priv", "pub", "ref", "return", "rt", "self", "static",
+    "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "use", "where", "while",
+    "yield",
+];
+
+/// Compiles the global definitions to a Rust program, with a function for every definition.
+/// Types are erased like for the VM, see `erase`. The `main` function of the program shows the values of
+/// the definitions in `shown`, like `jonla run` does.
+pub fn emit_rust(globals: &Globals, shown: &[(Name, usize)]) -> String {
+    let exprs: Vec<Expr> = (0..globals.defs.len())
+        .map(|id| erase_def(globals, id))
+        .collect();
+    let mut names: Vec<String> = vec![];
+    for (id, def) in globals.defs.iter().enumerate() {
+        let name = identifier(&def.name);
+        if RESERVED.contains(&name.as_str()) || names.contains(&name) {
+            names.push(format!("{}_{}", name, id));
+        } else {
+            names.push(name);
+        }
+    }
+    let arities = exprs.iter().map(|expr| lams(expr).0.len()).collect();
+    let gen = Gen {
+        globals,
+        names,
+        arities,
+    };
+
+    let mut out = String::from("//! Generated by jonla\n#![allow(unused, non_snake_case)]\n\n");
+    out.push_str(RUNTIME);
+    for (id, expr) in exprs.iter().enumerate() {
+        let def = &globals.defs[id];
+        let (params, body) = lams(expr);
+        let vars: Vec<String> = params
+            .iter()
+            .enumerate()
+            .map(|(lvl, name)| var(name, lvl))
+            .collect();
+        out.push_str(&format!(
+            "\n/// `{} : {}`\nfn {}({}) -> rt::Value {{\n    {}\n}}\n",
+            def.name,
+            pretty_core(globals, &[], &def.typ),
+            gen.names[id],
+            vars.iter()
+                .map(|v| format!("{}: rt::Value", v))
+                .collect::<Vec<_>>()
+                .join(", "),
+            gen.expr(body, &mut vars.clone(), 1)
+        ));
+    }
+
+    out.push_str("\nfn main() {\n");
+    for (name, id) in shown {
+        out.push_str(&format!(
+            "    println!(\"{{}} = {{}}\", {:?}, rt::show(&{}));\n",
+            name,
+            gen.expr(&Expr::Global(*id), &mut vec![], 1)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The names of the lambdas an expression starts with, and their body
+fn lams(mut expr: &Expr) -> (Vec<Name>, &Expr) {
+    let mut names = vec![];
+    while let Expr::Lam(name, body) = expr {
+        names.push(name.clone());
+        expr = body;
+    }
+    (names, expr)
+}
+
+/// A Rust identifier for a name, with the characters that can't be in one replaced
+fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The Rust variable for a variable with de Bruijn level `lvl`.
+/// The level keeps variables with the same name apart, and them from the other identifiers.
+fn var(name: &str, lvl: usize) -> String {
+    format!("{}_{}", identifier(name), lvl)
+}
+
+struct Gen<'g> {
+    globals: &'g Globals,
+    /// The Rust function for every global definition
+    names: Vec<String>,
+    /// The number of lambdas every global definition starts with, which its function takes as arguments
+    arities: Vec<usize>,
+}
+
+impl Gen<'_> {
+    /// The Rust expression for `expr`, where `vars` are the Rust variables for its variables by de Bruijn level.
+    /// Lines after the first are indented `indent` levels.
+    fn expr(&self, expr: &Expr, vars: &mut Vec<String>, indent: usize) -> String {
+        let pad = "    ".repeat(indent);
+        match expr {
+            Expr::Var(ix) => format!("{}.clone()", vars[vars.len() - 1 - ix]),
+            Expr::Global(id) => match self.arities[*id] {
+                0 => format!("{}()", self.names[*id]),
+                arity => format!(
+                    "rt::pap({}, |args| {}({}))",
+                    arity,
+                    self.names[*id],
+                    (0..arity)
+                        .map(|i| format!("args[{}].clone()", i))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+            Expr::App(..) => {
+                let mut args = vec![];
+                let mut head = expr;
+                while let Expr::App(f, a) = head {
+                    args.push(&**a);
+                    head = f;
+                }
+                args.reverse();
+                let mut args = args.into_iter();
+                //A global function that gets all its arguments is called directly
+                let mut out = match head {
+                    Expr::Global(id)
+                        if self.arities[*id] > 0 && args.len() >= self.arities[*id] =>
+                    {
+                        let direct: Vec<String> = args
+                            .by_ref()
+                            .take(self.arities[*id])
+                            .map(|a| self.expr(a, vars, indent))
+                            .collect();
+                        format!("{}({})", self.names[*id], direct.join(", "))
+                    }
+                    _ => self.expr(head, vars, indent),
+                };
+                for arg in args {
+                    out = format!("rt::apply({}, {})", out, self.expr(arg, vars, indent));
+                }
+                out
+            }
+            Expr::Lam(name, body) => self.closure(
+                std::slice::from_ref(name),
+                body,
+                vars,
+                indent,
+                |params, body| format!("rt::lam(move |{}| {})", params, body),
+            ),
+            Expr::Rec(name, body) => self.closure(
+                &["rec".into(), name.clone()],
+                body,
+                vars,
+                indent,
+                |params, body| format!("rt::fix(move |{}| {})", params, body),
+            ),
+            Expr::Let(name, value, body) => {
+                let value = self.expr(value, vars, indent + 1);
+                let bound = var(name, vars.len());
+                vars.push(bound.clone());
+                let out = format!(
+                    "{{\n{pad}    let {} = {};\n{pad}    {}\n{pad}}}",
+                    bound,
+                    value,
+                    self.expr(body, vars, indent + 1),
+                    pad = pad
+                );
+                vars.pop();
+                out
+            }
+            Expr::Con(d, c, fields) => format!(
+                "rt::con({}, {:?}, vec![{}])",
+                c,
+                &*self.globals.datas[*d].ctors[*c].name,
+                fields
+                    .iter()
+                    .map(|f| self.expr(f, vars, indent))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Expr::Case(target, arms) => {
+                let mut out = format!(
+                    "{{\n{pad}    let (tag, fields) = rt::fields({});\n{pad}    match tag {{\n",
+                    self.expr(target, vars, indent + 1),
+                    pad = pad
+                );
+                for (c, (fields, body)) in arms.iter().enumerate() {
+                    out.push_str(&format!("{pad}        {} => {{\n", c, pad = pad));
+                    for (i, field) in fields.iter().enumerate() {
+                        vars.push(var(field, vars.len()));
+                        out.push_str(&format!(
+                            "{pad}            let {} = fields[{}].clone();\n",
+                            vars.last().unwrap(),
+                            i,
+                            pad = pad
+                        ));
+                    }
+                    out.push_str(&format!(
+                        "{pad}            {}\n{pad}        }}\n",
+                        self.expr(body, vars, indent + 3),
+                        pad = pad
+                    ));
+                    vars.truncate(vars.len() - fields.len());
+                }
+                out.push_str(&format!(
+                    "{pad}        _ => unreachable!(),\n{pad}    }}\n{pad}}}",
+                    pad = pad
+                ));
+                out
+            }
+            Expr::Hole(hole) => {
+                format!("panic!({:?})", VmError { hole: *hole }.render(self.globals))
+            }
+            Expr::Erased => "rt::Value::Erased".to_string(),
+        }
+    }
+
+    /// A Rust closure that binds `params`, made by `make` from its parameters and its body.
+    /// The variables it uses are cloned first, so the closure can own them.
+    fn closure(
+        &self,
+        params: &[Name],
+        body: &Expr,
+        vars: &mut Vec<String>,
+        indent: usize,
+        make: impl Fn(&str, &str) -> String,
+    ) -> String {
+        let pad = "    ".repeat(indent);
+        let mut free = vec![];
+        body.free_vars(params.len(), &mut free);
+        free.sort_unstable();
+        free.dedup();
+        let captures: Vec<String> = free
+            .iter()
+            .map(|ix| vars[vars.len() - 1 - ix].clone())
+            .collect();
+        let depth = vars.len();
+        vars.extend(
+            params
+                .iter()
+                .enumerate()
+                .map(|(i, name)| var(name, depth + i)),
+        );
+        let inner = if captures.is_empty() {
+            indent
+        } else {
+            indent + 1
+        };
+        let closure = make(&vars[depth..].join(", "), &self.expr(body, vars, inner));
+        vars.truncate(depth);
+        if captures.is_empty() {
+            return closure;
+        }
+        let mut out = "{\n".to_string();
+        for capture in captures {
+            out.push_str(&format!(
+                "{pad}    let {c} = {c}.clone();\n",
+                pad = pad,
+                c = capture
+            ));
+        }
+        out.push_str(&format!("{pad}    {}\n{pad}}}", closure, pad = pad));
+        out
+    }
+}
@@ -0,0 +1,57 @@
+use crate::lang::core::Globals;
+use crate::lang::eval::{apply, unfold, whnf, Val};
+
+/// When global definitions are unfolded while comparing values
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Unfolding {
+    /// A definition is only unfolded if it can't be compared by name and arguments,
+    /// so `and p q` is equal to `and p q` without unfolding `and`
+    #[default]
+    Lazy,
+    /// Definitions are unfolded before values are compared
+    Eager,
+}
+
+/// Checks whether two values are definitionally equal, in a context with `lvl` variables.
+/// Values are equal up to beta (applying a lambda), delta (unfolding a definition) and eta (`f` is `/ x : A. f x`).
+/// The values can't contain unsolved metavariables, see `Metas::unify` for values that can.
+pub fn conv(globals: &Globals, lvl: usize, a: &Val, b: &Val, unfolding: Unfolding) -> bool {
+    let (a, b) = match unfolding {
+        Unfolding::Lazy => (a.clone(), b.clone()),
+        Unfolding::Eager => (whnf(globals, a), whnf(globals, b)),
+    };
+    let under = |c1: &Val, c2: &Val| conv(globals, lvl + 1, c1, c2, unfolding);
+    match (&a, &b) {
+        (Val::Type(l1), Val::Type(l2)) => l1 == l2,
+        (Val::Pi(_, i1, d1, c1), Val::Pi(_, i2, d2, c2)) => {
+            i1 == i2
+                && conv(globals, lvl, d1, d2, unfolding)
+                && under(
+                    &c1.apply(globals, Val::var(lvl)),
+                    &c2.apply(globals, Val::var(lvl)),
+                )
+        }
+        (Val::Lam(_, _, _, c1), Val::Lam(_, _, _, c2)) => under(
+            &c1.apply(globals, Val::var(lvl)),
+            &c2.apply(globals, Val::var(lvl)),
+        ),
+        (Val::Lam(_, icit, _, body), f @ Val::Neutral(..))
+        | (f @ Val::Neutral(..), Val::Lam(_, icit, _, body)) => under(
+            &body.apply(globals, Val::var(lvl)),
+            &apply(globals, f.clone(), Val::var(lvl), *icit),
+        ),
+        (Val::Neutral(h1, s1), Val::Neutral(h2, s2))
+            if h1 == h2
+                && s1.len() == s2.len()
+                && s1.iter().zip(s2.iter()).all(|((a1, i1), (a2, i2))| {
+                    i1 == i2 && conv(globals, lvl, a1, a2, unfolding)
+                }) =>
+        {
+            true
+        }
+        _ => match (unfold(globals, &a), unfold(globals, &b)) {
+            (None, None) => false,
+            (a1, b1) => conv(globals, lvl, &a1.unwrap_or(a), &b1.unwrap_or(b), unfolding),
+        },
+    }
+}
@@ -0,0 +1,430 @@
+use crate::lang::eval::Val;
+use crate::lang::level::{Level, LevelVar};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub type Name = Rc<str>;
+
+/// Whether an argument is given explicitly, or inferred by the checker
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Icit {
+    Explicit,
+    Implicit,
+}
+
+/// A checked term.
+/// Local variables are de Bruijn indices, global definitions are referred to by their index in `Globals`.
+#[derive(Clone, Debug)]
+pub enum Tm {
+    /// The universe at a level
+    Type(Level),
+    Var(usize),
+    /// A global definition, with values for its universe parameters
+    Global(usize, Vec<Level>),
+    Let {
+        name: Name,
+        typ: Rc<Tm>,
+        value: Rc<Tm>,
+        body: Rc<Tm>,
+    },
+    Pi {
+        name: Name,
+        icit: Icit,
+        dom: Rc<Tm>,
+        cod: Rc<Tm>,
+    },
+    Lam {
+        name: Name,
+        icit: Icit,
+        typ: Rc<Tm>,
+        body: Rc<Tm>,
+    },
+    App(Rc<Tm>, Rc<Tm>, Icit),
+    /// A data type, by its index in `Globals::datas`
+    Data(usize),
+    /// A constructor of a data type, by the index of the data type and of the constructor
+    Ctor(usize, usize),
+    /// The eliminator of a data type
+    Elim(usize),
+    /// A metavariable of the checker, which stands for a term it has to infer.
+    /// It is a closed term, applied to the variables in scope where it was made.
+    Meta(usize),
+    /// A hole that was left unfilled, by its index in `Globals::holes`.
+    /// Like a metavariable, it is applied to the variables in scope where it was written.
+    Hole(usize),
+}
+
+impl Tm {
+    /// Whether the variable with de Bruijn index `ix` occurs in this term
+    pub fn uses_var(&self, ix: usize) -> bool {
+        match self {
+            Tm::Type(_)
+            | Tm::Global(..)
+            | Tm::Data(_)
+            | Tm::Ctor(..)
+            | Tm::Elim(_)
+            | Tm::Meta(_)
+            | Tm::Hole(_) => false,
+            Tm::Var(i) => *i == ix,
+            Tm::Let {
+                typ, value, body, ..
+            } => typ.uses_var(ix) || value.uses_var(ix) || body.uses_var(ix + 1),
+            Tm::Pi {
+                dom: typ,
+                cod: body,
+                ..
+            }
+            | Tm::Lam { typ, body, .. } => typ.uses_var(ix) || body.uses_var(ix + 1),
+            Tm::App(f, a, _) => f.uses_var(ix) || a.uses_var(ix),
+        }
+    }
+
+    /// The first global definition in this term for which `f` holds
+    pub fn find_global(&self, f: &dyn Fn(usize) -> bool) -> Option<usize> {
+        match self {
+            Tm::Global(id, _) if f(*id) => Some(*id),
+            Tm::Type(_)
+            | Tm::Var(_)
+            | Tm::Global(..)
+            | Tm::Data(_)
+            | Tm::Ctor(..)
+            | Tm::Elim(_)
+            | Tm::Meta(_)
+            | Tm::Hole(_) => None,
+            Tm::Let {
+                typ, value, body, ..
+            } => typ
+                .find_global(f)
+                .or_else(|| value.find_global(f))
+                .or_else(|| body.find_global(f)),
+            Tm::Pi {
+                dom: typ,
+                cod: body,
+                ..
+            }
+            | Tm::Lam { typ, body, .. } => typ.find_global(f).or_else(|| body.find_global(f)),
+            Tm::App(g, a, _) => g.find_global(f).or_else(|| a.find_global(f)),
+        }
+    }
+
+    /// Whether the data type with index `data` occurs in this term
+    pub fn uses_data(&self, data: usize) -> bool {
+        match self {
+            Tm::Data(d) => *d == data,
+            Tm::Type(_)
+            | Tm::Var(_)
+            | Tm::Global(..)
+            | Tm::Ctor(..)
+            | Tm::Elim(_)
+            | Tm::Meta(_)
+            | Tm::Hole(_) => false,
+            Tm::Let {
+                typ, value, body, ..
+            } => typ.uses_data(data) || value.uses_data(data) || body.uses_data(data),
+            Tm::Pi {
+                dom: typ,
+                cod: body,
+                ..
+            }
+            | Tm::Lam { typ, body, .. } => typ.uses_data(data) || body.uses_data(data),
+            Tm::App(f, a, _) => f.uses_data(data) || a.uses_data(data),
+        }
+    }
+
+    /// Splits an application into its head and arguments
+    pub fn spine(&self) -> (&Tm, Vec<&Tm>) {
+        match self {
+            Tm::App(f, a, _) => {
+                let (head, mut args) = f.spine();
+                args.push(a);
+                (head, args)
+            }
+            _ => (self, vec![]),
+        }
+    }
+
+    /// Applies this term to the explicit arguments `args`
+    pub fn apply_all(self, args: impl IntoIterator<Item = Tm>) -> Tm {
+        args.into_iter()
+            .fold(self, |f, a| Tm::App(Rc::new(f), Rc::new(a), Icit::Explicit))
+    }
+
+    /// Moves a term to another context.
+    /// The term is in a context where the variable with de Bruijn level `l` is at level `levels[l]` of the new context,
+    /// which contains `depth` variables.
+    pub fn rebase(&self, levels: &[usize], depth: usize) -> Tm {
+        self.rebase_under(levels, depth, 0)
+    }
+
+    /// Like `rebase`, for a term that is under `bound` binders of its own
+    fn rebase_under(&self, levels: &[usize], depth: usize, bound: usize) -> Tm {
+        self.map_vars(bound, &|ix, bound| {
+            let lvl = levels[levels.len() - 1 - (ix - bound)];
+            Tm::Var(depth + bound - 1 - lvl)
+        })
+    }
+
+    /// Replaces the variables that are free in this term, which is under `bound` binders of its own.
+    /// `f` gets the de Bruijn index of a free variable and the number of binders it is under.
+    fn map_vars(&self, bound: usize, f: &dyn Fn(usize, usize) -> Tm) -> Tm {
+        let go = |tm: &Rc<Tm>, bound| Rc::new(tm.map_vars(bound, f));
+        match self {
+            Tm::Var(ix) if *ix < bound => Tm::Var(*ix),
+            Tm::Var(ix) => f(*ix, bound),
+            Tm::Type(_)
+            | Tm::Global(..)
+            | Tm::Data(_)
+            | Tm::Ctor(..)
+            | Tm::Elim(_)
+            | Tm::Meta(_)
+            | Tm::Hole(_) => self.clone(),
+            Tm::Let {
+                name,
+                typ,
+                value,
+                body,
+            } => Tm::Let {
+                name: name.clone(),
+                typ: go(typ, bound),
+                value: go(value, bound),
+                body: go(body, bound + 1),
+            },
+            Tm::Pi {
+                name,
+                icit,
+                dom,
+                cod,
+            } => Tm::Pi {
+                name: name.clone(),
+                icit: *icit,
+                dom: go(dom, bound),
+                cod: go(cod, bound + 1),
+            },
+            Tm::Lam {
+                name,
+                icit,
+                typ,
+                body,
+            } => Tm::Lam {
+                name: name.clone(),
+                icit: *icit,
+                typ: go(typ, bound),
+                body: go(body, bound + 1),
+            },
+            Tm::App(f, a, icit) => Tm::App(go(f, bound), go(a, bound), *icit),
+        }
+    }
+
+    /// Replaces the innermost `args.len()` variables by `args`, which are in the context around them.
+    /// The last argument replaces the innermost variable.
+    pub fn instantiate_vars(&self, args: &[Tm]) -> Tm {
+        let n = args.len();
+        self.map_vars(0, &|ix, bound| match ix - bound {
+            j if j < n => args[n - 1 - j].map_vars(0, &|ix, _| Tm::Var(ix + bound)),
+            j => Tm::Var(j - n + bound),
+        })
+    }
+
+    /// Replaces the universe variables for which `f` gives a level
+    pub fn subst_levels(&self, f: &dyn Fn(&LevelVar) -> Option<Level>) -> Tm {
+        let go = |tm: &Rc<Tm>| Rc::new(tm.subst_levels(f));
+        match self {
+            Tm::Type(level) => Tm::Type(level.subst(f)),
+            Tm::Global(id, levels) => Tm::Global(*id, levels.iter().map(|l| l.subst(f)).collect()),
+            Tm::Var(_) | Tm::Data(_) | Tm::Ctor(..) | Tm::Elim(_) | Tm::Meta(_) | Tm::Hole(_) => {
+                self.clone()
+            }
+            Tm::Let {
+                name,
+                typ,
+                value,
+                body,
+            } => Tm::Let {
+                name: name.clone(),
+                typ: go(typ),
+                value: go(value),
+                body: go(body),
+            },
+            Tm::Pi {
+                name,
+                icit,
+                dom,
+                cod,
+            } => Tm::Pi {
+                name: name.clone(),
+                icit: *icit,
+                dom: go(dom),
+                cod: go(cod),
+            },
+            Tm::Lam {
+                name,
+                icit,
+                typ,
+                body,
+            } => Tm::Lam {
+                name: name.clone(),
+                icit: *icit,
+                typ: go(typ),
+                body: go(body),
+            },
+            Tm::App(f, a, icit) => Tm::App(go(f), go(a), *icit),
+        }
+    }
+
+    /// Whether the universe variable `name` occurs in this term
+    pub fn uses_level_param(&self, name: &str) -> bool {
+        let in_level = |level: &Level| level.params().any(|p| &**p == name);
+        match self {
+            Tm::Type(level) => in_level(level),
+            Tm::Global(_, levels) => levels.iter().any(in_level),
+            Tm::Var(_) | Tm::Data(_) | Tm::Ctor(..) | Tm::Elim(_) | Tm::Meta(_) | Tm::Hole(_) => {
+                false
+            }
+            Tm::Let {
+                typ, value, body, ..
+            } => {
+                typ.uses_level_param(name)
+                    || value.uses_level_param(name)
+                    || body.uses_level_param(name)
+            }
+            Tm::Pi {
+                dom: typ,
+                cod: body,
+                ..
+            }
+            | Tm::Lam { typ, body, .. } => {
+                typ.uses_level_param(name) || body.uses_level_param(name)
+            }
+            Tm::App(f, a, _) => f.uses_level_param(name) || a.uses_level_param(name),
+        }
+    }
+}
+
+/// A checked top-level definition
+#[derive(Clone, Debug)]
+pub struct GlobalDef {
+    /// The name of the definition, qualified with its module if it has one
+    pub name: Name,
+    /// The universes the definition is polymorphic over
+    pub levels: Vec<Name>,
+    pub typ: Tm,
+    pub value: Tm,
+    pub typ_val: Val,
+    pub value_val: Val,
+    /// Whether the definition refers to itself, in which case it was checked to be structurally recursive
+    pub recursive: bool,
+    /// Whether the definition was declared `partial`, so it may not terminate.
+    /// It is never unfolded, and can only be used by other partial definitions.
+    pub partial: bool,
+}
+
+impl GlobalDef {
+    /// Gives the universe parameters of the definition the values `levels`, in its type or value
+    pub fn instantiate(&self, tm: &Tm, levels: &[Level]) -> Tm {
+        tm.subst_levels(&|v| match v {
+            LevelVar::Param(name) => self
+                .levels
+                .iter()
+                .position(|p| p == name)
+                .map(|i| levels[i].clone()),
+            LevelVar::Meta(_) => None,
+        })
+    }
+}
+
+/// A checked data type.
+/// Its type and the types of its constructors are in normal form, so they are a chain of `Pi`s.
+#[derive(Clone, Debug)]
+pub struct DataDef {
+    pub name: Name,
+    /// The name of the eliminator
+    pub elim_name: Name,
+    /// The type of the data type: its parameters, then its indices, then a universe
+    pub typ: Tm,
+    pub params: usize,
+    pub indices: usize,
+    pub ctors: Vec<CtorDef>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CtorDef {
+    pub name: Name,
+    /// The type of the constructor: the parameters of the data type, then its fields, then the data type applied to
+    /// the parameters and the indices of the constructed value
+    pub typ: Tm,
+    pub fields: usize,
+}
+
+impl DataDef {
+    /// The number of arguments the eliminator takes before it reduces:
+    /// the parameters, the motive, a method for every constructor, the indices and the target
+    pub fn elim_arity(&self) -> usize {
+        self.params + 1 + self.ctors.len() + self.indices + 1
+    }
+
+    /// Whether all values of the data type are the same, as it has one constructor without fields, such as `Eq`.
+    /// Its values are proofs that carry no information, so they are erased when compiling, see `erase`.
+    pub fn is_irrelevant(&self) -> bool {
+        matches!(self.ctors.as_slice(), [ctor] if ctor.fields == 0)
+    }
+}
+
+/// A hole that was left in a checked definition.
+/// It stands for an unknown term, so it does not reduce.
+#[derive(Clone, Debug)]
+pub struct HoleDef {
+    /// The name of the hole, `_` for an anonymous one
+    pub name: Name,
+    /// The type of the hole, as a closed chain of `Pi`s over the variables in scope where it was written
+    pub typ: Tm,
+    /// The number of variables the hole is applied to before the arguments it was given in the source
+    pub context: usize,
+}
+
+/// All top-level definitions that were checked so far, from every module
+#[derive(Clone, Debug, Default)]
+pub struct Globals {
+    pub defs: Vec<GlobalDef>,
+    pub datas: Vec<DataDef>,
+    pub holes: Vec<HoleDef>,
+}
+
+impl Globals {
+    pub fn define(&mut self, def: GlobalDef) -> usize {
+        self.defs.push(def);
+        self.defs.len() - 1
+    }
+}
+
+/// The global definitions that are visible by name, such as in a single file.
+/// A definition can be shadowed by a later one with the same name, but terms that referred to it keep doing so.
+#[derive(Clone, Debug, Default)]
+pub struct Namespace {
+    names: HashMap<Name, usize>,
+    /// The universes declared with `universe`, in order
+    universes: Vec<Name>,
+}
+
+impl Namespace {
+    /// Finds the most recent definition with this name
+    pub fn lookup(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub fn insert(&mut self, name: Name, id: usize) {
+        self.names.insert(name, id);
+    }
+
+    pub fn universes(&self) -> &[Name] {
+        &self.universes
+    }
+
+    /// Declares a universe, returns false if it was already declared
+    pub fn declare_universe(&mut self, name: Name) -> bool {
+        if self.universes.contains(&name) {
+            return false;
+        }
+        self.universes.push(name);
+        true
+    }
+}
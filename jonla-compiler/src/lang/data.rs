@@ -0,0 +1,374 @@
+use crate::autogen::ast::{Constructor, Param, Term};
+use crate::lang::check::{Checker, Ctx, TypeError};
+use crate::lang::core::{CtorDef, DataDef, GlobalDef, Globals, Icit, Name, Namespace, Tm};
+use crate::lang::eval::{eval, normalize, skip_pis};
+use crate::lang::level::{Level, LevelVar};
+use crate::lang::pretty::pretty_core;
+use crate::lang::span_of;
+use std::rc::Rc;
+
+/// Checks a `data` declaration, adding the data type, its constructors and its eliminator `D.elim` to the globals.
+/// The names of globals are made with `qualify`, the definitions are added to `names` unqualified.
+/// Returns the names that were defined.
+#[allow(clippy::too_many_arguments)]
+pub fn check_data<'src>(
+    globals: &mut Globals,
+    names: &mut Namespace,
+    src: &'src str,
+    name: &'src str,
+    params: &[Param<'src>],
+    typ: &Term<'src>,
+    ctors: &[Constructor<'src>],
+    qualify: impl Fn(&str) -> Name,
+) -> Result<Vec<(Name, usize)>, TypeError> {
+    let error = |span_name: &str, message: String| TypeError {
+        span: span_of(src, span_name),
+        message,
+    };
+    let mut defined = vec![];
+
+    //Data types are not universe polymorphic, so their universes are known
+    let universes = names.universes().to_vec();
+    let monomorphic = |tm: &Tm, name: &str| match universes.iter().find(|u| tm.uses_level_param(u))
+    {
+        Some(u) => Err(error(
+            name,
+            format!(
+                "Data types can't be universe polymorphic, but `{}` uses `{}`",
+                name, u
+            ),
+        )),
+        None => Ok(()),
+    };
+    //The types of a data declaration have to be complete, as they are checked for positivity
+    let no_holes = |checker: &Checker| match checker.goals().first() {
+        Some(goal) => Err(TypeError {
+            span: goal.span,
+            message: format!(
+                "Holes can't be left in a data declaration, `{}` has a hole",
+                name
+            ),
+        }),
+        None => Ok(()),
+    };
+
+    //The parameters are in scope in the type and in the constructors
+    let mut ctx = Ctx::default();
+    let mut param_types = vec![];
+    let checker = Checker::new(globals, names, src);
+    for Param::Param { name, arg_type } in params {
+        let (tm, _) = checker.check_type(&ctx, arg_type)?;
+        let val = eval(globals, &ctx.env, &tm);
+        param_types.push(((*name).into(), tm));
+        ctx = ctx.bind((*name).into(), val);
+    }
+    let (typ_tm, _) = checker.check_type(&ctx, typ)?;
+    checker.solve_metas()?;
+    no_holes(&checker)?;
+    let param_types = param_types
+        .into_iter()
+        .map(|(name, tm)| (name, checker.zonk(&tm)))
+        .collect::<Vec<(Name, Tm)>>();
+    let full_type = normalize(
+        globals,
+        &vec![],
+        &pis(param_types.clone(), checker.zonk(&typ_tm)),
+    );
+    monomorphic(&full_type, name)?;
+    let mut indices = 0;
+    let mut result = skip_pis(&full_type, params.len());
+    while let Tm::Pi { cod, .. } = result {
+        indices += 1;
+        result = cod;
+    }
+    let Tm::Type(level) = result else {
+        return Err(error(
+            name,
+            format!("The type of `{}` should end in a universe", name),
+        ));
+    };
+    let level = level.clone();
+
+    //The parameters with the inferred universe levels filled in
+    let mut ctx = Ctx::default();
+    for (name, tm) in &param_types {
+        let val = eval(globals, &ctx.env, tm);
+        ctx = ctx.bind(name.clone(), val);
+    }
+
+    let d = globals.datas.len();
+    globals.datas.push(DataDef {
+        name: qualify(name),
+        elim_name: qualify(&format!("{}.elim", name)),
+        typ: full_type.clone(),
+        params: params.len(),
+        indices,
+        ctors: vec![],
+    });
+    let id = define(globals, qualify(name), vec![], full_type, Tm::Data(d));
+    names.insert(name.into(), id);
+    defined.push((name.into(), id));
+
+    for Constructor::Constructor {
+        name: ctor_name,
+        arg_type,
+    } in ctors
+    {
+        let checker = Checker::new(globals, names, src);
+        let (tm, ctor_level) = checker.check_type(&ctx, arg_type)?;
+        //The constructor type ends in the data type, so it is in the data type's universe if all fields are
+        if !checker.level_leq(arg_type, &ctor_level, &level) {
+            return Err(error(
+                ctor_name,
+                format!(
+                    "The fields of `{}` should be in the universe of `{}`, which is `{}`",
+                    ctor_name,
+                    name,
+                    pretty_core(globals, &[], &Tm::Type(level.clone()))
+                ),
+            ));
+        }
+        checker.solve_metas()?;
+        no_holes(&checker)?;
+        let ctor_type = normalize(
+            globals,
+            &vec![],
+            &pis(param_types.clone(), checker.zonk(&tm)),
+        );
+        monomorphic(&ctor_type, ctor_name)?;
+        let fields = check_ctor_type(&globals.datas[d], d, &ctor_type)
+            .map_err(|message| error(ctor_name, message))?;
+
+        let c = globals.datas[d].ctors.len();
+        globals.datas[d].ctors.push(CtorDef {
+            name: qualify(ctor_name),
+            typ: ctor_type.clone(),
+            fields,
+        });
+        let id = define(
+            globals,
+            qualify(ctor_name),
+            vec![],
+            ctor_type,
+            Tm::Ctor(d, c),
+        );
+        names.insert((*ctor_name).into(), id);
+        defined.push(((*ctor_name).into(), id));
+    }
+
+    let elim_name = format!("{}.elim", name);
+    let elim_type = elim_type(&globals.datas[d], d, ctors);
+    let id = define(
+        globals,
+        qualify(&elim_name),
+        vec![MOTIVE_UNIVERSE.into()],
+        elim_type,
+        Tm::Elim(d),
+    );
+    names.insert(elim_name.as_str().into(), id);
+    defined.push((elim_name.into(), id));
+    Ok(defined)
+}
+
+/// The universe of the motive, which the eliminator is polymorphic over
+const MOTIVE_UNIVERSE: &str = "u";
+
+/// Adds a constant to the globals
+fn define(globals: &mut Globals, name: Name, levels: Vec<Name>, typ: Tm, value: Tm) -> usize {
+    let typ_val = eval(globals, &vec![], &typ);
+    let value_val = eval(globals, &vec![], &value);
+    globals.define(GlobalDef {
+        name,
+        levels,
+        typ,
+        value,
+        typ_val,
+        value_val,
+        recursive: false,
+        partial: false,
+    })
+}
+
+/// A chain of `Pi`s with the given binders around `body`
+fn pis(binders: Vec<(Name, Tm)>, body: Tm) -> Tm {
+    binders
+        .into_iter()
+        .rev()
+        .fold(body, |cod, (name, dom)| Tm::Pi {
+            name,
+            icit: Icit::Explicit,
+            dom: Rc::new(dom),
+            cod: Rc::new(cod),
+        })
+}
+
+/// Checks that the normalized type of a constructor constructs the data type `d`,
+/// and that the data type only occurs strictly positively in its fields. Returns the number of fields.
+fn check_ctor_type(data: &DataDef, d: usize, typ: &Tm) -> Result<usize, String> {
+    let mut fields = 0;
+    let mut result = skip_pis(typ, data.params);
+    while let Tm::Pi { dom, cod, .. } = result {
+        check_positive(data, d, dom, data.params + fields)?;
+        fields += 1;
+        result = cod;
+    }
+
+    let (head, args) = result.spine();
+    if !matches!(head, Tm::Data(h) if *h == d) || args.len() != data.params + data.indices {
+        return Err(format!(
+            "A constructor of `{}` should result in `{}` applied to {} argument(s)",
+            data.name,
+            data.name,
+            data.params + data.indices
+        ));
+    }
+    check_params(data, &args, data.params + fields)?;
+    if args[data.params..].iter().any(|i| i.uses_data(d)) {
+        return Err(format!(
+            "The indices of a constructor of `{}` can't contain `{}`",
+            data.name, data.name
+        ));
+    }
+    Ok(fields)
+}
+
+/// Checks that the type of a field, in a context with `depth` variables, is either a type without the data type `d`,
+/// or a function type ending in `d`, where `d` doesn't occur in the arguments
+fn check_positive(data: &DataDef, d: usize, typ: &Tm, depth: usize) -> Result<(), String> {
+    if !typ.uses_data(d) {
+        return Ok(());
+    }
+    match typ {
+        Tm::Pi { dom, .. } if dom.uses_data(d) => Err(format!(
+            "`{}` occurs in a non-positive position, to the left of an arrow",
+            data.name
+        )),
+        Tm::Pi { cod, .. } => check_positive(data, d, cod, depth + 1),
+        _ => {
+            let (head, args) = typ.spine();
+            if !matches!(head, Tm::Data(h) if *h == d) || args.iter().any(|a| a.uses_data(d)) {
+                return Err(format!(
+                    "`{}` can only occur in a field as the result of the field's type",
+                    data.name
+                ));
+            }
+            check_params(data, &args, depth)
+        }
+    }
+}
+
+/// Checks that the data type is applied to its own parameters, in a context with `depth` variables
+fn check_params(data: &DataDef, args: &[&Tm], depth: usize) -> Result<(), String> {
+    let same =
+        (0..data.params).all(|i| matches!(args.get(i), Some(Tm::Var(ix)) if *ix == depth - 1 - i));
+    if same {
+        Ok(())
+    } else {
+        Err(format!(
+            "`{}` should be applied to its parameters, in the same order",
+            data.name
+        ))
+    }
+}
+
+/// The type of the eliminator of `d`:
+/// `(ps : Params) -> (P : (is : Indices) -> D ps is -> Type u) -> Methods -> (is : Indices) -> (t : D ps is) -> P is t`.
+/// The method for a constructor `c` is `(fs : Fields) -> Hypotheses -> P is (c ps fs)`.
+fn elim_type(data: &DataDef, d: usize, ctors: &[Constructor]) -> Tm {
+    let n = data.params;
+    let k = data.indices;
+    let var = |depth: usize, lvl: usize| Tm::Var(depth - 1 - lvl);
+    let mut binders: Vec<(Name, Tm)> = vec![];
+
+    //The parameters and indices are in the type of the data type, with the indices after the parameters
+    let mut data_binders = vec![];
+    let mut typ = &data.typ;
+    while let Tm::Pi { name, dom, cod, .. } = typ {
+        data_binders.push((name.clone(), (**dom).clone()));
+        typ = cod;
+    }
+    binders.extend(data_binders[..n].iter().cloned());
+
+    //The motive, in a context with the parameters
+    let applied_data = |depth: usize, indices: &mut dyn Iterator<Item = Tm>| -> Tm {
+        Tm::Data(d)
+            .apply_all((0..n).map(|l| var(depth, l)))
+            .apply_all(indices)
+    };
+    let motive_type = pis(
+        data_binders[n..].to_vec(),
+        pis(
+            vec![(
+                "t".into(),
+                applied_data(n + k, &mut (n..n + k).map(|l| var(n + k, l))),
+            )],
+            Tm::Type(Level::var(LevelVar::Param(MOTIVE_UNIVERSE.into()))),
+        ),
+    );
+    binders.push(("P".into(), motive_type));
+    let motive = n;
+
+    //The methods
+    for (c, ctor) in data.ctors.iter().enumerate() {
+        let start = n + 1 + c;
+        //The level of every variable in the context of the constructor type
+        let mut levels = (0..n).collect::<Vec<_>>();
+        let mut method_binders = vec![];
+        let mut recursive = vec![];
+        let mut typ = skip_pis(&ctor.typ, n);
+        while let Tm::Pi { name, dom, cod, .. } = typ {
+            let depth = start + method_binders.len();
+            if dom.uses_data(d) {
+                recursive.push((method_binders.len(), levels.clone(), (**dom).clone()));
+            }
+            method_binders.push((name.clone(), dom.rebase(&levels, depth)));
+            levels.push(depth);
+            typ = cod;
+        }
+
+        //The induction hypotheses `(ys : Bs) -> P is (f ys)`
+        for (field, field_levels, field_type) in recursive {
+            let depth = start + method_binders.len();
+            let mut levels = field_levels;
+            let mut hyp_binders = vec![];
+            let mut typ = &field_type;
+            while let Tm::Pi { name, dom, cod, .. } = typ {
+                hyp_binders.push((name.clone(), dom.rebase(&levels, depth + hyp_binders.len())));
+                levels.push(depth + hyp_binders.len() - 1);
+                typ = cod;
+            }
+            let inner = depth + hyp_binders.len();
+            let (_, args) = typ.spine();
+            let target = var(inner, start + field).apply_all((depth..inner).map(|l| var(inner, l)));
+            let hyp = var(inner, motive)
+                .apply_all(args[n..].iter().map(|i| i.rebase(&levels, inner)))
+                .apply_all([target]);
+            method_binders.push(("ih".into(), pis(hyp_binders, hyp)));
+        }
+
+        let depth = start + method_binders.len();
+        let (_, args) = typ.spine();
+        let constructed = Tm::Ctor(d, c)
+            .apply_all((0..n).map(|l| var(depth, l)))
+            .apply_all((0..ctor.fields).map(|f| var(depth, start + f)));
+        let result = var(depth, motive)
+            .apply_all(args[n..].iter().map(|i| i.rebase(&levels, depth)))
+            .apply_all([constructed]);
+
+        let Constructor::Constructor { name, .. } = &ctors[c];
+        binders.push((format!("case_{}", name).into(), pis(method_binders, result)));
+    }
+
+    //The indices and the target
+    let start = n + 1 + data.ctors.len();
+    let index_levels = (0..n).chain(start..start + k).collect::<Vec<_>>();
+    for (i, (name, dom)) in data_binders[n..].iter().enumerate() {
+        binders.push((name.clone(), dom.rebase(&index_levels[..n + i], start + i)));
+    }
+    let depth = start + k;
+    let target_type = applied_data(depth, &mut (start..start + k).map(|l| var(depth, l)));
+    binders.push(("t".into(), target_type));
+    let depth = depth + 1;
+    let result = var(depth, motive).apply_all((start..start + k + 1).map(|l| var(depth, l)));
+    pis(binders, result)
+}
@@ -0,0 +1,244 @@
+use crate::lang::check::Ctx;
+use crate::lang::core::{Globals, Name, Tm};
+use crate::lang::eval::{eval, skip_pis, whnf, Head, Val};
+use std::rc::Rc;
+
+/// A term with its types erased, which only describes how to compute its value.
+/// Variables are de Bruijn indices, like in `Tm`, and binders keep their names so generated code can use them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    Var(usize),
+    Global(usize),
+    Lam(Name, Rc<Expr>),
+    App(Rc<Expr>, Rc<Expr>),
+    Let(Name, Rc<Expr>, Rc<Expr>),
+    /// A function that can call itself, as variable 1 in its body, next to its argument as variable 0
+    Rec(Name, Rc<Expr>),
+    /// A constructor, by the index of the data type and of the constructor, applied to its fields
+    Con(usize, usize, Vec<Expr>),
+    /// Matches a constructed value, with an arm for every constructor that binds its fields
+    Case(Rc<Expr>, Vec<(Vec<Name>, Expr)>),
+    /// A hole that was left unfilled, by its index in `Globals::holes`
+    Hole(usize),
+    /// A type, a proof, or anything else that can't affect the value of a program
+    Erased,
+}
+
+/// Erases the types in a global definition, which is a closed term
+pub fn erase_def(globals: &Globals, id: usize) -> Expr {
+    erase(globals, &Ctx::default(), &globals.defs[id].value)
+}
+
+/// Erases the types in `tm`, everything that computes a type and the proofs, in a context with the types of its variables
+pub fn erase(globals: &Globals, ctx: &Ctx, tm: &Tm) -> Expr {
+    if is_type(globals, ctx, tm) || is_proof(globals, ctx, tm) {
+        return Expr::Erased;
+    }
+    match tm {
+        Tm::Var(ix) => Expr::Var(*ix),
+        Tm::Global(id, _) => Expr::Global(*id),
+        Tm::Let {
+            name,
+            typ,
+            value,
+            body,
+        } => {
+            let inner = ctx.define(
+                name.clone(),
+                eval(globals, &ctx.env, typ),
+                eval(globals, &ctx.env, value),
+            );
+            Expr::Let(
+                name.clone(),
+                Rc::new(erase(globals, ctx, value)),
+                Rc::new(erase(globals, &inner, body)),
+            )
+        }
+        Tm::Lam {
+            name, typ, body, ..
+        } => {
+            let inner = ctx.bind(name.clone(), eval(globals, &ctx.env, typ));
+            Expr::Lam(name.clone(), Rc::new(erase(globals, &inner, body)))
+        }
+        Tm::App(f, a, _) => Expr::App(
+            Rc::new(erase(globals, ctx, f)),
+            Rc::new(erase(globals, ctx, a)),
+        ),
+        Tm::Ctor(d, c) => {
+            //A value only holds its fields, the eliminator gets the parameters of the data type as its own arguments
+            let data = &globals.datas[*d];
+            let fields = data.ctors[*c].fields;
+            let con = Expr::Con(*d, *c, (0..fields).rev().map(Expr::Var).collect());
+            lams(data.params, "_", lams(fields, "x", con))
+        }
+        Tm::Elim(d) => eliminator(globals, *d),
+        Tm::Hole(h) => Expr::Hole(*h),
+        Tm::Type(_) | Tm::Pi { .. } | Tm::Data(_) | Tm::Meta(_) => Expr::Erased,
+    }
+}
+
+/// Wraps `body` in `n` lambdas, which bind variables named `name`
+fn lams(n: usize, name: &str, body: Expr) -> Expr {
+    (0..n).fold(body, |body, _| Expr::Lam(name.into(), Rc::new(body)))
+}
+
+/// The eliminator of data type `d` as a recursive function on its target.
+/// It takes the parameters, the motive, the methods and the indices first, which it only passes on to the methods.
+/// A method is applied to the fields of the target, and then to the result of eliminating every recursive field.
+/// The target of a proof is erased, so the eliminator of a proof-irrelevant data type doesn't look at it.
+fn eliminator(globals: &Globals, d: usize) -> Expr {
+    let data = &globals.datas[d];
+    let methods = data.params + 1;
+    //The function that recurses and its target come after the arguments before the target
+    let rec = data.elim_arity() - 1;
+    let target = rec + 1;
+    let arms: Vec<(Vec<Name>, Expr)> = data
+        .ctors
+        .iter()
+        .enumerate()
+        .map(|(c, ctor)| {
+            let depth = target + 1 + ctor.fields;
+            let var = |depth: usize, lvl: usize| Expr::Var(depth - 1 - lvl);
+            let mut body = var(depth, methods + c);
+            let mut field_type = skip_pis(&ctor.typ, data.params);
+            let mut hyps = vec![];
+            for field in 0..ctor.fields {
+                let Tm::Pi { dom, cod, .. } = field_type else {
+                    unreachable!("Constructor type has fewer fields than expected")
+                };
+                field_type = cod;
+                body = Expr::App(Rc::new(body), Rc::new(var(depth, target + 1 + field)));
+                if !dom.uses_data(d) {
+                    continue;
+                }
+                //A recursive field that is a function is eliminated after it is applied
+                let mut args = 0;
+                let mut typ = &**dom;
+                while let Tm::Pi { cod, .. } = typ {
+                    args += 1;
+                    typ = cod;
+                }
+                let inner = depth + args;
+                let applied = (depth..inner).fold(var(inner, target + 1 + field), |f, a| {
+                    Expr::App(Rc::new(f), Rc::new(var(inner, a)))
+                });
+                hyps.push(lams(
+                    args,
+                    "y",
+                    Expr::App(Rc::new(var(inner, rec)), Rc::new(applied)),
+                ));
+            }
+            let body = hyps
+                .into_iter()
+                .fold(body, |f, hyp| Expr::App(Rc::new(f), Rc::new(hyp)));
+            (vec!["x".into(); ctor.fields], body)
+        })
+        .collect();
+    let body = match arms.as_slice() {
+        [(_, body)] if data.is_irrelevant() => body.clone(),
+        _ => Expr::Case(Rc::new(Expr::Var(0)), arms),
+    };
+    let elim = Expr::Rec("t".into(), Rc::new(body));
+    let elim = lams(data.indices, "i", elim);
+    let elim = lams(data.ctors.len(), "method", elim);
+    lams(data.params, "p", lams(1, "motive", elim))
+}
+
+/// Whether `tm` is a type, or a function that results in a type, so it can't affect the value of a program.
+/// This only looks at the type of `tm` when it is an application, otherwise it errs on the side of keeping terms.
+fn is_type(globals: &Globals, ctx: &Ctx, tm: &Tm) -> bool {
+    match tm {
+        Tm::Type(_) | Tm::Pi { .. } | Tm::Data(_) => true,
+        Tm::Lam {
+            name, typ, body, ..
+        } => is_type(
+            globals,
+            &ctx.bind(name.clone(), eval(globals, &ctx.env, typ)),
+            body,
+        ),
+        Tm::Let {
+            name,
+            typ,
+            value,
+            body,
+        } => is_type(
+            globals,
+            &ctx.define(
+                name.clone(),
+                eval(globals, &ctx.env, typ),
+                eval(globals, &ctx.env, value),
+            ),
+            body,
+        ),
+        _ => type_of_spine(globals, ctx, tm).is_some_and(|typ| is_arity(globals, ctx.lvl(), &typ)),
+    }
+}
+
+/// Whether `tm` is a value of a proof-irrelevant data type, such as a proof of an equation, see `DataDef::is_irrelevant`.
+/// Like `is_type`, this only looks at the type of applications and variables.
+fn is_proof(globals: &Globals, ctx: &Ctx, tm: &Tm) -> bool {
+    let Some(typ) = type_of_spine(globals, ctx, tm) else {
+        return false;
+    };
+    match whnf(globals, &typ) {
+        Val::Neutral(Head::Data(d), _) => globals.datas[d].is_irrelevant(),
+        _ => false,
+    }
+}
+
+/// The type of `tm` if it is an application of a variable, a global, a constructor or a hole, or one of those
+fn type_of_spine(globals: &Globals, ctx: &Ctx, tm: &Tm) -> Option<Val> {
+    let (head, args) = tm.spine();
+    let typ = match head {
+        Tm::Var(ix) => ctx.types[ctx.lvl() - 1 - ix].clone(),
+        Tm::Global(id, levels) => {
+            let def = &globals.defs[*id];
+            eval(globals, &vec![], &def.instantiate(&def.typ, levels))
+        }
+        Tm::Ctor(d, c) => eval(globals, &vec![], &globals.datas[*d].ctors[*c].typ),
+        Tm::Hole(h) => eval(globals, &vec![], &globals.holes[*h].typ),
+        _ => return None,
+    };
+    args.iter()
+        .try_fold(typ, |typ, arg| match whnf(globals, &typ) {
+            Val::Pi(_, _, _, cod) => Some(cod.apply(globals, eval(globals, &ctx.env, arg))),
+            _ => None,
+        })
+}
+
+/// Whether `typ` is a universe, or a function type that results in a universe
+fn is_arity(globals: &Globals, lvl: usize, typ: &Val) -> bool {
+    match whnf(globals, typ) {
+        Val::Type(_) => true,
+        Val::Pi(_, _, _, cod) => is_arity(globals, lvl + 1, &cod.apply(globals, Val::var(lvl))),
+        _ => false,
+    }
+}
+
+impl Expr {
+    /// Adds the de Bruijn indices of the variables that are bound outside of this expression to `out`,
+    /// where it is under `bound` binders of its own
+    pub fn free_vars(&self, bound: usize, out: &mut Vec<usize>) {
+        match self {
+            Expr::Var(ix) if *ix >= bound => out.push(ix - bound),
+            Expr::Var(_) | Expr::Global(_) | Expr::Hole(_) | Expr::Erased => {}
+            Expr::Lam(_, body) => body.free_vars(bound + 1, out),
+            Expr::Rec(_, body) => body.free_vars(bound + 2, out),
+            Expr::App(f, a) => {
+                f.free_vars(bound, out);
+                a.free_vars(bound, out);
+            }
+            Expr::Let(_, value, body) => {
+                value.free_vars(bound, out);
+                body.free_vars(bound + 1, out);
+            }
+            Expr::Con(_, _, fields) => fields.iter().for_each(|f| f.free_vars(bound, out)),
+            Expr::Case(target, arms) => {
+                target.free_vars(bound, out);
+                for (fields, body) in arms {
+                    body.free_vars(bound + fields.len(), out);
+                }
+            }
+        }
+    }
+}
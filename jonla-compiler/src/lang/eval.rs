@@ -0,0 +1,306 @@
+use crate::lang::core::{DataDef, Globals, Icit, Name, Tm};
+use crate::lang::level::Level;
+use std::rc::Rc;
+
+/// A term evaluated to weak head normal form.
+/// Variables without a value are de Bruijn levels, so values can be moved under binders without shifting.
+#[derive(Clone, Debug)]
+pub enum Val {
+    Type(Level),
+    /// A head that can't be reduced (yet), applied to arguments
+    Neutral(Head, Spine),
+    Pi(Name, Icit, Rc<Val>, Closure),
+    Lam(Name, Icit, Rc<Val>, Closure),
+}
+
+/// The arguments a head is applied to
+pub type Spine = Vec<(Val, Icit)>;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Head {
+    /// A variable without a value, as a de Bruijn level
+    Var(usize),
+    Data(usize),
+    Ctor(usize, usize),
+    /// An eliminator, which reduces when it is applied to all its arguments and its target is a constructor
+    Elim(usize),
+    /// A metavariable of the checker, which may have been solved since this value was made
+    Meta(usize),
+    /// A hole that was left unfilled, which never reduces
+    Hole(usize),
+    /// A global definition with values for its universe parameters, which is unfolded when its value is needed.
+    /// Keeping it folded lets values be shown with the names of definitions, see `whnf`.
+    Global(usize, Vec<Level>),
+}
+
+/// A term with one free variable, together with the values of the variables around it
+#[derive(Clone, Debug)]
+pub struct Closure {
+    pub env: Env,
+    pub body: Rc<Tm>,
+}
+
+pub type Env = Vec<Val>;
+
+impl Val {
+    pub fn var(lvl: usize) -> Val {
+        Val::Neutral(Head::Var(lvl), vec![])
+    }
+}
+
+impl Closure {
+    pub fn apply(&self, globals: &Globals, arg: Val) -> Val {
+        let mut env = self.env.clone();
+        env.push(arg);
+        eval(globals, &env, &self.body)
+    }
+}
+
+pub fn eval(globals: &Globals, env: &Env, tm: &Tm) -> Val {
+    match tm {
+        Tm::Type(level) => Val::Type(level.clone()),
+        Tm::Var(ix) => env[env.len() - 1 - ix].clone(),
+        Tm::Global(id, levels) => Val::Neutral(Head::Global(*id, levels.clone()), vec![]),
+        Tm::Let { value, body, .. } => {
+            let mut env = env.clone();
+            env.push(eval(globals, &env, value));
+            eval(globals, &env, body)
+        }
+        Tm::Pi {
+            name,
+            icit,
+            dom,
+            cod,
+        } => Val::Pi(
+            name.clone(),
+            *icit,
+            Rc::new(eval(globals, env, dom)),
+            Closure {
+                env: env.clone(),
+                body: cod.clone(),
+            },
+        ),
+        Tm::Lam {
+            name,
+            icit,
+            typ,
+            body,
+        } => Val::Lam(
+            name.clone(),
+            *icit,
+            Rc::new(eval(globals, env, typ)),
+            Closure {
+                env: env.clone(),
+                body: body.clone(),
+            },
+        ),
+        Tm::App(f, a, icit) => apply(globals, eval(globals, env, f), eval(globals, env, a), *icit),
+        Tm::Data(d) => Val::Neutral(Head::Data(*d), vec![]),
+        Tm::Ctor(d, c) => Val::Neutral(Head::Ctor(*d, *c), vec![]),
+        Tm::Elim(d) => Val::Neutral(Head::Elim(*d), vec![]),
+        Tm::Meta(m) => Val::Neutral(Head::Meta(*m), vec![]),
+        Tm::Hole(h) => Val::Neutral(Head::Hole(*h), vec![]),
+    }
+}
+
+pub fn apply(globals: &Globals, f: Val, a: Val, icit: Icit) -> Val {
+    match f {
+        Val::Lam(_, _, _, body) => body.apply(globals, a),
+        Val::Neutral(head, mut args) => {
+            args.push((a, icit));
+            match head {
+                Head::Elim(d) if args.len() == globals.datas[d].elim_arity() => {
+                    iota(globals, d, &args).unwrap_or(Val::Neutral(head, args))
+                }
+                _ => Val::Neutral(head, args),
+            }
+        }
+        Val::Type(_) | Val::Pi(..) => unreachable!("Applied a value that is not a function"),
+    }
+}
+
+/// Unfolds the global definition at the head of a value, if it has one that can be unfolded.
+/// A partial definition is never unfolded, as that may not terminate. A recursive definition is only unfolded if
+/// it reduces, not if it gets stuck on an eliminator, as the recursive calls in it could be unfolded forever.
+pub fn unfold(globals: &Globals, val: &Val) -> Option<Val> {
+    let Val::Neutral(Head::Global(id, levels), spine) = val else {
+        return None;
+    };
+    //A definition that refers to itself can't be unfolded while it is checked
+    let def = globals.defs.get(*id)?;
+    if def.partial {
+        return None;
+    }
+    let value = if levels.is_empty() {
+        def.value_val.clone()
+    } else {
+        eval(globals, &vec![], &def.instantiate(&def.value, levels))
+    };
+    let unfolded = spine
+        .iter()
+        .fold(value, |f, (a, icit)| apply(globals, f, a.clone(), *icit));
+    if !def.recursive {
+        return Some(unfolded);
+    }
+    match whnf(globals, &unfolded) {
+        Val::Neutral(Head::Elim(_), _) => None,
+        reduced => Some(reduced),
+    }
+}
+
+/// Unfolds the global definitions at the head of a value, until its head is something else, see `unfold`
+pub fn whnf(globals: &Globals, val: &Val) -> Val {
+    match unfold(globals, val) {
+        Some(unfolded) => whnf(globals, &unfolded),
+        None => val.clone(),
+    }
+}
+
+/// Converts a value back to a term, in a context with `lvl` variables.
+/// Global definitions are kept folded, see `normalize` for the term with all definitions unfolded.
+pub fn quote(globals: &Globals, lvl: usize, val: &Val) -> Tm {
+    quote_with(globals, lvl, val, false)
+}
+
+fn quote_with(globals: &Globals, lvl: usize, val: &Val, unfold: bool) -> Tm {
+    let go = |lvl, val: &Val| Rc::new(quote_with(globals, lvl, val, unfold));
+    if let Some(unfolded) = unfold.then(|| self::unfold(globals, val)).flatten() {
+        return quote_with(globals, lvl, &unfolded, unfold);
+    }
+    match val {
+        Val::Type(level) => Tm::Type(level.clone()),
+        Val::Neutral(head, args) => args.iter().fold(quote_head(lvl, head), |f, (a, icit)| {
+            Tm::App(Rc::new(f), go(lvl, a), *icit)
+        }),
+        Val::Pi(name, icit, dom, cod) => Tm::Pi {
+            name: name.clone(),
+            icit: *icit,
+            dom: go(lvl, dom),
+            cod: go(lvl + 1, &cod.apply(globals, Val::var(lvl))),
+        },
+        Val::Lam(name, icit, typ, body) => Tm::Lam {
+            name: name.clone(),
+            icit: *icit,
+            typ: go(lvl, typ),
+            body: go(lvl + 1, &body.apply(globals, Val::var(lvl))),
+        },
+    }
+}
+
+/// Converts a head back to a term, in a context with `lvl` variables
+pub fn quote_head(lvl: usize, head: &Head) -> Tm {
+    match head {
+        Head::Var(var) => Tm::Var(lvl - var - 1),
+        Head::Data(d) => Tm::Data(*d),
+        Head::Ctor(d, c) => Tm::Ctor(*d, *c),
+        Head::Elim(d) => Tm::Elim(*d),
+        Head::Meta(m) => Tm::Meta(*m),
+        Head::Hole(h) => Tm::Hole(*h),
+        Head::Global(id, levels) => Tm::Global(*id, levels.clone()),
+    }
+}
+
+/// Reduces a fully applied eliminator, if its target is a constructor.
+/// The method for the constructor is applied to its fields, and then to the result of eliminating every recursive field.
+fn iota(globals: &Globals, d: usize, args: &[(Val, Icit)]) -> Option<Val> {
+    let data = &globals.datas[d];
+    //The target may be a global definition that unfolds to a constructor
+    let Val::Neutral(Head::Ctor(_, c), ctor_args) = whnf(globals, &args.last()?.0) else {
+        return None;
+    };
+    let ctor = &data.ctors[c];
+    if ctor_args.len() != data.params + ctor.fields {
+        return None;
+    }
+
+    //Evaluate the induction hypotheses in a context with the constructor arguments, the motive and the methods
+    let mut env = ctor_args.iter().map(|(a, _)| a.clone()).collect::<Env>();
+    env.extend(
+        args[data.params..data.params + 1 + data.ctors.len()]
+            .iter()
+            .map(|(a, _)| a.clone()),
+    );
+    let (method, _) = args[data.params + 1 + c].clone();
+    let fields = ctor_args[data.params..].iter().map(|(a, _)| a.clone());
+    let hyps = induction_hypotheses(data, d, c, env.len())
+        .into_iter()
+        .map(|tm| eval(globals, &env, &tm))
+        .collect::<Vec<_>>();
+    Some(
+        fields
+            .chain(hyps)
+            .fold(method, |f, a| apply(globals, f, a, Icit::Explicit)),
+    )
+}
+
+/// The induction hypotheses for the recursive fields of constructor `c`, in a context with the parameters,
+/// the fields, the motive and the methods, that contains `depth` variables.
+/// A recursive field `x : (ys : Bs) -> D ps is` has hypothesis `/ ys : Bs. D.elim ps P ms is (x ys)`.
+pub fn induction_hypotheses(data: &DataDef, d: usize, c: usize, depth: usize) -> Vec<Tm> {
+    let ctor = &data.ctors[c];
+    let outer = data.params + ctor.fields;
+    let elim_args = (outer..outer + 1 + data.ctors.len()).collect::<Vec<_>>();
+
+    let mut hyps = vec![];
+    let mut field_type = skip_pis(&ctor.typ, data.params);
+    for field in 0..ctor.fields {
+        let Tm::Pi { dom, cod, .. } = field_type else {
+            unreachable!("Constructor type has fewer fields than expected")
+        };
+        field_type = cod;
+        if !dom.uses_data(d) {
+            continue;
+        }
+
+        //The field type is in a context with the parameters and the fields before it
+        let mut levels = (0..data.params + field).collect::<Vec<_>>();
+        let mut binders = vec![];
+        let mut typ = &**dom;
+        while let Tm::Pi { name, dom, cod, .. } = typ {
+            binders.push((name.clone(), dom.rebase(&levels, depth + binders.len())));
+            levels.push(depth + binders.len() - 1);
+            typ = cod;
+        }
+        let inner = depth + binders.len();
+        let var = |lvl: usize| Tm::Var(inner - 1 - lvl);
+        let (_, result_args) = typ.spine();
+        let target = var(data.params + field).apply_all((depth..inner).map(var));
+        let elim = Tm::Elim(d)
+            .apply_all((0..data.params).map(var))
+            .apply_all(elim_args.iter().map(|&l| var(l)))
+            .apply_all(
+                result_args[data.params..]
+                    .iter()
+                    .map(|i| i.rebase(&levels, inner)),
+            )
+            .apply_all([target]);
+        hyps.push(
+            binders
+                .into_iter()
+                .rev()
+                .fold(elim, |body, (name, typ)| Tm::Lam {
+                    name,
+                    icit: Icit::Explicit,
+                    typ: Rc::new(typ),
+                    body: Rc::new(body),
+                }),
+        );
+    }
+    hyps
+}
+
+/// The codomain of a chain of `Pi`s, after `n` of them
+pub fn skip_pis(mut tm: &Tm, n: usize) -> &Tm {
+    for _ in 0..n {
+        match tm {
+            Tm::Pi { cod, .. } => tm = cod,
+            _ => unreachable!("Expected a function type"),
+        }
+    }
+    tm
+}
+
+/// Evaluates a term fully, including under binders and in the values of global definitions
+pub fn normalize(globals: &Globals, env: &Env, tm: &Tm) -> Tm {
+    quote_with(globals, env.len(), &eval(globals, env, tm), true)
+}
@@ -0,0 +1,230 @@
+use crate::lang::core::{Globals, Icit, Name, Tm};
+use crate::lang::level::{Level, LevelVar};
+use serde_json::{json, Value};
+use std::rc::Rc;
+
+/// A declaration for the kernel to check, which is fully elaborated: its terms have no metavariables or holes.
+/// The constructors and the eliminator of a data type are defined by the kernel, so they aren't declared.
+#[derive(Clone, Debug)]
+pub enum Decl {
+    Data {
+        name: Name,
+        elim_name: Name,
+        /// The type of the data type, its parameters and indices then a universe
+        typ: Tm,
+        params: usize,
+        /// The names and types of the constructors, which start with the parameters of the data type
+        ctors: Vec<(Name, Tm)>,
+    },
+    Def {
+        name: Name,
+        levels: Vec<Name>,
+        typ: Tm,
+        value: Tm,
+        partial: bool,
+    },
+}
+
+/// The first line of an export, which identifies the format
+const HEADER: &str = r#"{"format":"jonla-export","version":1}"#;
+
+/// Exports the global definitions, for the kernel to check them again, see `kernel::check_decls`.
+/// The export is a line with `HEADER`, then a JSON object per line for every declaration.
+/// A term is an array with the kind of term and then its parts, such as `["app","explicit",f,a]`,
+/// where local variables are de Bruijn indices and global definitions are referred to by their id.
+/// The constructors and eliminators of data types are left out, as the kernel defines them itself.
+pub fn export(globals: &Globals) -> Result<String, String> {
+    let mut out = format!("{}\n", HEADER);
+    for def in &globals.defs {
+        let decl = match &def.value {
+            Tm::Data(d) => {
+                let data = &globals.datas[*d];
+                let ctors: Vec<Value> = data
+                    .ctors
+                    .iter()
+                    .map(|ctor| Ok(json!({"name": &*ctor.name, "type": term(&ctor.typ)?})))
+                    .collect::<Result<_, String>>()?;
+                json!({"data": {
+                    "name": &*data.name,
+                    "elim_name": &*data.elim_name,
+                    "type": term(&data.typ)?,
+                    "params": data.params,
+                    "ctors": ctors,
+                }})
+            }
+            Tm::Ctor(..) | Tm::Elim(_) => continue,
+            value => {
+                let levels: Vec<&str> = def.levels.iter().map(|l| &**l).collect();
+                json!({"def": {
+                    "name": &*def.name,
+                    "levels": levels,
+                    "type": term(&def.typ).map_err(|e| format!("`{}`: {}", def.name, e))?,
+                    "value": term(value).map_err(|e| format!("`{}`: {}", def.name, e))?,
+                    "partial": def.partial,
+                }})
+            }
+        };
+        out.push_str(&decl.to_string());
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn term(tm: &Tm) -> Result<Value, String> {
+    let icit = |icit: &Icit| match icit {
+        Icit::Explicit => "explicit",
+        Icit::Implicit => "implicit",
+    };
+    Ok(match tm {
+        Tm::Type(l) => json!(["type", level(l)?]),
+        Tm::Var(ix) => json!(["var", ix]),
+        Tm::Global(id, levels) => {
+            let levels: Vec<Value> = levels.iter().map(level).collect::<Result<_, _>>()?;
+            json!(["global", id, levels])
+        }
+        Tm::Let {
+            name,
+            typ,
+            value,
+            body,
+        } => json!(["let", &**name, term(typ)?, term(value)?, term(body)?]),
+        Tm::Pi {
+            name,
+            icit: i,
+            dom,
+            cod,
+        } => json!(["pi", &**name, icit(i), term(dom)?, term(cod)?]),
+        Tm::Lam {
+            name,
+            icit: i,
+            typ,
+            body,
+        } => json!(["lam", &**name, icit(i), term(typ)?, term(body)?]),
+        Tm::App(f, a, i) => json!(["app", icit(i), term(f)?, term(a)?]),
+        Tm::Data(d) => json!(["data", d]),
+        Tm::Ctor(d, c) => json!(["ctor", d, c]),
+        Tm::Elim(d) => json!(["elim", d]),
+        Tm::Meta(_) => return Err("Unsolved metavariables can't be exported".into()),
+        Tm::Hole(_) => return Err("Holes can't be exported".into()),
+    })
+}
+
+/// A level is an object with its constant and the offset of every universe parameter in it
+fn level(level: &Level) -> Result<Value, String> {
+    let mut vars = serde_json::Map::new();
+    for (var, offset) in &level.vars {
+        match var {
+            LevelVar::Param(name) => vars.insert(name.to_string(), json!(offset)),
+            LevelVar::Meta(_) => return Err("Unsolved universe levels can't be exported".into()),
+        };
+    }
+    Ok(json!({"constant": level.constant, "vars": vars}))
+}
+
+/// Reads the declarations in an export, see `export`
+pub fn import(src: &str) -> Result<Vec<Decl>, String> {
+    let mut lines = src.lines().enumerate();
+    match lines.next() {
+        Some((_, line)) if line.trim() == HEADER => {}
+        _ => return Err("Not a Jonla export, it should start with its header".into()),
+    }
+    lines
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| line_decl(line).map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
+}
+
+fn line_decl(line: &str) -> Result<Decl, String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    decl(&value).ok_or_else(|| "Invalid declaration".to_string())
+}
+
+fn decl(value: &Value) -> Option<Decl> {
+    let name = |v: &Value| -> Option<Name> { v.as_str().map(Into::into) };
+    if let Some(data) = value.get("data") {
+        return Some(Decl::Data {
+            name: name(data.get("name")?)?,
+            elim_name: name(data.get("elim_name")?)?,
+            typ: from_term(data.get("type")?)?,
+            params: data.get("params")?.as_u64()? as usize,
+            ctors: data
+                .get("ctors")?
+                .as_array()?
+                .iter()
+                .map(|ctor| Some((name(ctor.get("name")?)?, from_term(ctor.get("type")?)?)))
+                .collect::<Option<_>>()?,
+        });
+    }
+    let def = value.get("def")?;
+    Some(Decl::Def {
+        name: name(def.get("name")?)?,
+        levels: def
+            .get("levels")?
+            .as_array()?
+            .iter()
+            .map(name)
+            .collect::<Option<_>>()?,
+        typ: from_term(def.get("type")?)?,
+        value: from_term(def.get("value")?)?,
+        partial: def.get("partial")?.as_bool()?,
+    })
+}
+
+fn from_term(value: &Value) -> Option<Tm> {
+    let parts = value.as_array()?;
+    let part = |i: usize| parts.get(i);
+    let num = |i: usize| Some(part(i)?.as_u64()? as usize);
+    let name = |i: usize| -> Option<Name> { part(i)?.as_str().map(Into::into) };
+    let sub = |i: usize| Some(Rc::new(from_term(part(i)?)?));
+    let icit = |i: usize| match part(i)?.as_str()? {
+        "explicit" => Some(Icit::Explicit),
+        "implicit" => Some(Icit::Implicit),
+        _ => None,
+    };
+    Some(match part(0)?.as_str()? {
+        "type" => Tm::Type(from_level(part(1)?)?),
+        "var" => Tm::Var(num(1)?),
+        "global" => Tm::Global(
+            num(1)?,
+            part(2)?
+                .as_array()?
+                .iter()
+                .map(from_level)
+                .collect::<Option<_>>()?,
+        ),
+        "let" => Tm::Let {
+            name: name(1)?,
+            typ: sub(2)?,
+            value: sub(3)?,
+            body: sub(4)?,
+        },
+        "pi" => Tm::Pi {
+            name: name(1)?,
+            icit: icit(2)?,
+            dom: sub(3)?,
+            cod: sub(4)?,
+        },
+        "lam" => Tm::Lam {
+            name: name(1)?,
+            icit: icit(2)?,
+            typ: sub(3)?,
+            body: sub(4)?,
+        },
+        "app" => Tm::App(sub(2)?, sub(3)?, icit(1)?),
+        "data" => Tm::Data(num(1)?),
+        "ctor" => Tm::Ctor(num(1)?, num(2)?),
+        "elim" => Tm::Elim(num(1)?),
+        _ => return None,
+    })
+}
+
+fn from_level(value: &Value) -> Option<Level> {
+    let vars = value.get("vars")?.as_object()?;
+    vars.iter().try_fold(
+        Level::constant(value.get("constant")?.as_u64()? as usize),
+        |level, (name, offset)| {
+            let var = Level::var(LevelVar::Param(name.as_str().into()));
+            Some(level.max(&var.plus(offset.as_u64()? as usize)))
+        },
+    )
+}
@@ -0,0 +1,258 @@
+use crate::lang::core::{DataDef, Icit, Name, Tm};
+use crate::lang::kernel::level::Level;
+use std::rc::Rc;
+
+/// The universe of the motive, which the eliminator is polymorphic over
+pub const MOTIVE_UNIVERSE: &str = "u";
+
+/// A chain of `Pi`s with the given binders around `body`
+fn pis(binders: Vec<(Name, Tm)>, body: Tm) -> Tm {
+    binders
+        .into_iter()
+        .rev()
+        .fold(body, |cod, (name, dom)| Tm::Pi {
+            name,
+            icit: Icit::Explicit,
+            dom: Rc::new(dom),
+            cod: Rc::new(cod),
+        })
+}
+
+/// Checks that the normalized type of a constructor constructs the data type `d`,
+/// and that the data type only occurs strictly positively in its fields. Returns the number of fields.
+pub fn check_ctor_type(data: &DataDef, d: usize, typ: &Tm) -> Result<usize, String> {
+    let mut fields = 0;
+    let mut result = skip_pis(typ, data.params);
+    while let Tm::Pi { dom, cod, .. } = result {
+        check_positive(data, d, dom, data.params + fields)?;
+        fields += 1;
+        result = cod;
+    }
+
+    let (head, args) = result.spine();
+    if !matches!(head, Tm::Data(h) if *h == d) || args.len() != data.params + data.indices {
+        return Err(format!(
+            "A constructor of `{}` should result in `{}` applied to {} argument(s)",
+            data.name,
+            data.name,
+            data.params + data.indices
+        ));
+    }
+    check_params(data, &args, data.params + fields)?;
+    if args[data.params..].iter().any(|i| i.uses_data(d)) {
+        return Err(format!(
+            "The indices of a constructor of `{}` can't contain `{}`",
+            data.name, data.name
+        ));
+    }
+    Ok(fields)
+}
+
+/// Checks that the type of a field, in a context with `depth` variables, is either a type without the data type `d`,
+/// or a function type ending in `d`, where `d` doesn't occur in the arguments
+fn check_positive(data: &DataDef, d: usize, typ: &Tm, depth: usize) -> Result<(), String> {
+    if !typ.uses_data(d) {
+        return Ok(());
+    }
+    match typ {
+        Tm::Pi { dom, .. } if dom.uses_data(d) => Err(format!(
+            "`{}` occurs in a non-positive position, to the left of an arrow",
+            data.name
+        )),
+        Tm::Pi { cod, .. } => check_positive(data, d, cod, depth + 1),
+        _ => {
+            let (head, args) = typ.spine();
+            if !matches!(head, Tm::Data(h) if *h == d) || args.iter().any(|a| a.uses_data(d)) {
+                return Err(format!(
+                    "`{}` can only occur in a field as the result of the field's type",
+                    data.name
+                ));
+            }
+            check_params(data, &args, depth)
+        }
+    }
+}
+
+/// Checks that the data type is applied to its own parameters, in a context with `depth` variables
+fn check_params(data: &DataDef, args: &[&Tm], depth: usize) -> Result<(), String> {
+    let same =
+        (0..data.params).all(|i| matches!(args.get(i), Some(Tm::Var(ix)) if *ix == depth - 1 - i));
+    if same {
+        Ok(())
+    } else {
+        Err(format!(
+            "`{}` should be applied to its parameters, in the same order",
+            data.name
+        ))
+    }
+}
+
+/// The type of the eliminator of `d`:
+/// `(ps : Params) -> (P : (is : Indices) -> D ps is -> Type u) -> Methods -> (is : Indices) -> (t : D ps is) -> P is t`.
+/// The method for a constructor `c` is `(fs : Fields) -> Hypotheses -> P is (c ps fs)`.
+pub fn elim_type(data: &DataDef, d: usize) -> Tm {
+    let n = data.params;
+    let k = data.indices;
+    let var = |depth: usize, lvl: usize| Tm::Var(depth - 1 - lvl);
+    let mut binders: Vec<(Name, Tm)> = vec![];
+
+    //The parameters and indices are in the type of the data type, with the indices after the parameters
+    let mut data_binders = vec![];
+    let mut typ = &data.typ;
+    while let Tm::Pi { name, dom, cod, .. } = typ {
+        data_binders.push((name.clone(), (**dom).clone()));
+        typ = cod;
+    }
+    binders.extend(data_binders[..n].iter().cloned());
+
+    //The motive, in a context with the parameters
+    let applied_data = |depth: usize, indices: &mut dyn Iterator<Item = Tm>| -> Tm {
+        Tm::Data(d)
+            .apply_all((0..n).map(|l| var(depth, l)))
+            .apply_all(indices)
+    };
+    let motive_type = pis(
+        data_binders[n..].to_vec(),
+        pis(
+            vec![(
+                "t".into(),
+                applied_data(n + k, &mut (n..n + k).map(|l| var(n + k, l))),
+            )],
+            Tm::Type(Level::param(MOTIVE_UNIVERSE.into()).export()),
+        ),
+    );
+    binders.push(("P".into(), motive_type));
+    let motive = n;
+
+    //The methods
+    for (c, ctor) in data.ctors.iter().enumerate() {
+        let start = n + 1 + c;
+        //The level of every variable in the context of the constructor type
+        let mut levels = (0..n).collect::<Vec<_>>();
+        let mut method_binders = vec![];
+        let mut recursive = vec![];
+        let mut typ = skip_pis(&ctor.typ, n);
+        while let Tm::Pi { name, dom, cod, .. } = typ {
+            let depth = start + method_binders.len();
+            if dom.uses_data(d) {
+                recursive.push((method_binders.len(), levels.clone(), (**dom).clone()));
+            }
+            method_binders.push((name.clone(), dom.rebase(&levels, depth)));
+            levels.push(depth);
+            typ = cod;
+        }
+
+        //The induction hypotheses `(ys : Bs) -> P is (f ys)`
+        for (field, field_levels, field_type) in recursive {
+            let depth = start + method_binders.len();
+            let mut levels = field_levels;
+            let mut hyp_binders = vec![];
+            let mut typ = &field_type;
+            while let Tm::Pi { name, dom, cod, .. } = typ {
+                hyp_binders.push((name.clone(), dom.rebase(&levels, depth + hyp_binders.len())));
+                levels.push(depth + hyp_binders.len() - 1);
+                typ = cod;
+            }
+            let inner = depth + hyp_binders.len();
+            let (_, args) = typ.spine();
+            let target = var(inner, start + field).apply_all((depth..inner).map(|l| var(inner, l)));
+            let hyp = var(inner, motive)
+                .apply_all(args[n..].iter().map(|i| i.rebase(&levels, inner)))
+                .apply_all([target]);
+            method_binders.push(("ih".into(), pis(hyp_binders, hyp)));
+        }
+
+        let depth = start + method_binders.len();
+        let (_, args) = typ.spine();
+        let constructed = Tm::Ctor(d, c)
+            .apply_all((0..n).map(|l| var(depth, l)))
+            .apply_all((0..ctor.fields).map(|f| var(depth, start + f)));
+        let result = var(depth, motive)
+            .apply_all(args[n..].iter().map(|i| i.rebase(&levels, depth)))
+            .apply_all([constructed]);
+
+        //The method is named after the constructor, without the module it is in
+        let name = ctor.name.rsplit('.').next().unwrap_or(&ctor.name);
+        binders.push((format!("case_{}", name).into(), pis(method_binders, result)));
+    }
+
+    //The indices and the target
+    let start = n + 1 + data.ctors.len();
+    let index_levels = (0..n).chain(start..start + k).collect::<Vec<_>>();
+    for (i, (name, dom)) in data_binders[n..].iter().enumerate() {
+        binders.push((name.clone(), dom.rebase(&index_levels[..n + i], start + i)));
+    }
+    let depth = start + k;
+    let target_type = applied_data(depth, &mut (start..start + k).map(|l| var(depth, l)));
+    binders.push(("t".into(), target_type));
+    let depth = depth + 1;
+    let result = var(depth, motive).apply_all((start..start + k + 1).map(|l| var(depth, l)));
+    pis(binders, result)
+}
+
+/// The induction hypotheses for the recursive fields of constructor `c`, in a context with the parameters,
+/// the fields, the motive and the methods, that contains `depth` variables.
+/// A recursive field `x : (ys : Bs) -> D ps is` has hypothesis `/ ys : Bs. D.elim ps P ms is (x ys)`.
+pub fn induction_hypotheses(data: &DataDef, d: usize, c: usize, depth: usize) -> Vec<Tm> {
+    let ctor = &data.ctors[c];
+    let outer = data.params + ctor.fields;
+    let elim_args = (outer..outer + 1 + data.ctors.len()).collect::<Vec<_>>();
+
+    let mut hyps = vec![];
+    let mut field_type = skip_pis(&ctor.typ, data.params);
+    for field in 0..ctor.fields {
+        let Tm::Pi { dom, cod, .. } = field_type else {
+            unreachable!("Constructor type has fewer fields than expected")
+        };
+        field_type = cod;
+        if !dom.uses_data(d) {
+            continue;
+        }
+
+        //The field type is in a context with the parameters and the fields before it
+        let mut levels = (0..data.params + field).collect::<Vec<_>>();
+        let mut binders = vec![];
+        let mut typ = &**dom;
+        while let Tm::Pi { name, dom, cod, .. } = typ {
+            binders.push((name.clone(), dom.rebase(&levels, depth + binders.len())));
+            levels.push(depth + binders.len() - 1);
+            typ = cod;
+        }
+        let inner = depth + binders.len();
+        let var = |lvl: usize| Tm::Var(inner - 1 - lvl);
+        let (_, result_args) = typ.spine();
+        let target = var(data.params + field).apply_all((depth..inner).map(var));
+        let elim = Tm::Elim(d)
+            .apply_all((0..data.params).map(var))
+            .apply_all(elim_args.iter().map(|&l| var(l)))
+            .apply_all(
+                result_args[data.params..]
+                    .iter()
+                    .map(|i| i.rebase(&levels, inner)),
+            )
+            .apply_all([target]);
+        hyps.push(
+            binders
+                .into_iter()
+                .rev()
+                .fold(elim, |body, (name, typ)| Tm::Lam {
+                    name,
+                    icit: Icit::Explicit,
+                    typ: Rc::new(typ),
+                    body: Rc::new(body),
+                }),
+        );
+    }
+    hyps
+}
+
+/// The codomain of a chain of `Pi`s, after `n` of them
+fn skip_pis(mut tm: &Tm, n: usize) -> &Tm {
+    for _ in 0..n {
+        match tm {
+            Tm::Pi { cod, .. } => tm = cod,
+            _ => unreachable!("Expected a function type"),
+        }
+    }
+    tm
+}
@@ -0,0 +1,258 @@
+use crate::lang::core::{Icit, Name, Tm};
+use crate::lang::kernel::data::induction_hypotheses;
+use crate::lang::kernel::level::{Level, Univs};
+use crate::lang::kernel::Globals;
+use std::rc::Rc;
+
+/// A term evaluated to weak head normal form.
+/// Variables without a value are de Bruijn levels, so values can be moved under binders without shifting.
+#[derive(Clone, Debug)]
+pub enum Val {
+    Type(Level),
+    /// A head that can't be reduced (yet), applied to arguments
+    Neutral(Head, Vec<(Val, Icit)>),
+    Pi(Icit, Rc<Val>, Closure),
+    Lam(Icit, Rc<Val>, Closure),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Head {
+    /// A variable without a value, as a de Bruijn level
+    Var(usize),
+    Data(usize),
+    Ctor(usize, usize),
+    /// An eliminator, which reduces when it is applied to all its arguments and its target is a constructor
+    Elim(usize),
+    /// A definition with values for its universe parameters, which is unfolded when its value is needed
+    Global(usize, Vec<Level>),
+}
+
+/// The values of the universe parameters and of the variables that a term is evaluated with
+#[derive(Clone, Debug)]
+pub struct Env {
+    pub univs: Rc<Univs>,
+    pub vals: Vec<Val>,
+}
+
+/// A term with one free variable, together with the values of the variables around it
+#[derive(Clone, Debug)]
+pub struct Closure {
+    pub env: Env,
+    pub body: Rc<Tm>,
+}
+
+impl Val {
+    pub fn var(lvl: usize) -> Val {
+        Val::Neutral(Head::Var(lvl), vec![])
+    }
+}
+
+impl Env {
+    /// An environment without variables, where the universe parameters have the values `univs`
+    pub fn new(univs: Vec<(Name, Level)>) -> Env {
+        Env {
+            univs: univs.into(),
+            vals: vec![],
+        }
+    }
+
+    pub fn push(&self, val: Val) -> Env {
+        let mut env = self.clone();
+        env.vals.push(val);
+        env
+    }
+}
+
+impl Closure {
+    pub fn apply(&self, globals: &Globals, arg: Val) -> Val {
+        eval(globals, &self.env.push(arg), &self.body)
+    }
+}
+
+/// Evaluates a term that the kernel checked, so it has no metavariables or holes, and its levels are valid
+pub fn eval(globals: &Globals, env: &Env, tm: &Tm) -> Val {
+    let closure = |body: &Rc<Tm>| Closure {
+        env: env.clone(),
+        body: body.clone(),
+    };
+    let level = |level| Level::import(level, &env.univs).expect("The kernel checked the level");
+    match tm {
+        Tm::Type(l) => Val::Type(level(l)),
+        Tm::Var(ix) => env.vals[env.vals.len() - 1 - ix].clone(),
+        Tm::Global(id, levels) => Val::Neutral(
+            Head::Global(*id, levels.iter().map(level).collect()),
+            vec![],
+        ),
+        Tm::Let { value, body, .. } => eval(globals, &env.push(eval(globals, env, value)), body),
+        Tm::Pi { icit, dom, cod, .. } => {
+            Val::Pi(*icit, Rc::new(eval(globals, env, dom)), closure(cod))
+        }
+        Tm::Lam {
+            icit, typ, body, ..
+        } => Val::Lam(*icit, Rc::new(eval(globals, env, typ)), closure(body)),
+        Tm::App(f, a, icit) => apply(globals, eval(globals, env, f), eval(globals, env, a), *icit),
+        Tm::Data(d) => Val::Neutral(Head::Data(*d), vec![]),
+        Tm::Ctor(d, c) => Val::Neutral(Head::Ctor(*d, *c), vec![]),
+        Tm::Elim(d) => Val::Neutral(Head::Elim(*d), vec![]),
+        Tm::Meta(_) | Tm::Hole(_) => unreachable!("The kernel evaluates a term after checking it"),
+    }
+}
+
+pub fn apply(globals: &Globals, f: Val, a: Val, icit: Icit) -> Val {
+    match f {
+        Val::Lam(_, _, body) => body.apply(globals, a),
+        Val::Neutral(head, mut args) => {
+            args.push((a, icit));
+            match head {
+                Head::Elim(d) if args.len() == globals.datas[d].elim_arity() => {
+                    iota(globals, d, &args).unwrap_or(Val::Neutral(head, args))
+                }
+                _ => Val::Neutral(head, args),
+            }
+        }
+        Val::Type(_) | Val::Pi(..) => unreachable!("Applied a value that is not a function"),
+    }
+}
+
+/// Unfolds the definition at the head of a value. Partial definitions are never unfolded, and recursive ones only
+/// if they reduce, not if they get stuck on an eliminator, as their recursive calls could be unfolded forever.
+fn unfold(globals: &Globals, val: &Val) -> Option<Val> {
+    let Val::Neutral(Head::Global(id, levels), spine) = val else {
+        return None;
+    };
+    //The definition being checked has no value yet
+    let def = globals.defs.get(*id).filter(|def| !def.partial)?;
+    let value = eval(globals, &def.env(levels), &def.value);
+    let unfolded = spine
+        .iter()
+        .fold(value, |f, (a, icit)| apply(globals, f, a.clone(), *icit));
+    if !def.recursive {
+        return Some(unfolded);
+    }
+    match whnf(globals, &unfolded) {
+        Val::Neutral(Head::Elim(_), _) => None,
+        reduced => Some(reduced),
+    }
+}
+
+/// Unfolds the definitions at the head of a value, until its head is something else
+pub fn whnf(globals: &Globals, val: &Val) -> Val {
+    match unfold(globals, val) {
+        Some(unfolded) => whnf(globals, &unfolded),
+        None => val.clone(),
+    }
+}
+
+/// Reduces a fully applied eliminator, if its target is a constructor.
+/// The method for the constructor is applied to its fields, and then to the result of eliminating every recursive field.
+fn iota(globals: &Globals, d: usize, args: &[(Val, Icit)]) -> Option<Val> {
+    let data = &globals.datas[d];
+    let Val::Neutral(Head::Ctor(_, c), ctor_args) = whnf(globals, &args.last()?.0) else {
+        return None;
+    };
+    let ctor = &data.ctors[c];
+    if ctor_args.len() != data.params + ctor.fields {
+        return None;
+    }
+
+    //The induction hypotheses are in a context with the constructor arguments, the motive and the methods
+    let mut env = Env::new(vec![]);
+    env.vals.extend(ctor_args.iter().map(|(a, _)| a.clone()));
+    env.vals.extend(
+        args[data.params..data.params + 1 + data.ctors.len()]
+            .iter()
+            .map(|(a, _)| a.clone()),
+    );
+    let (method, _) = args[data.params + 1 + c].clone();
+    let fields = ctor_args[data.params..].iter().map(|(a, _)| a.clone());
+    let hyps = induction_hypotheses(data, d, c, env.vals.len())
+        .into_iter()
+        .map(|tm| eval(globals, &env, &tm))
+        .collect::<Vec<_>>();
+    Some(
+        fields
+            .chain(hyps)
+            .fold(method, |f, a| apply(globals, f, a, Icit::Explicit)),
+    )
+}
+
+/// Checks whether two values are definitionally equal, in a context with `lvl` variables.
+/// Definitions are only unfolded if the values can't be compared by their heads and arguments.
+pub fn conv(globals: &Globals, lvl: usize, a: &Val, b: &Val) -> bool {
+    let under = |c1: &Closure, c2: &Closure| {
+        conv(
+            globals,
+            lvl + 1,
+            &c1.apply(globals, Val::var(lvl)),
+            &c2.apply(globals, Val::var(lvl)),
+        )
+    };
+    match (a, b) {
+        (Val::Type(l1), Val::Type(l2)) => l1 == l2,
+        (Val::Pi(i1, d1, c1), Val::Pi(i2, d2, c2)) => {
+            i1 == i2 && conv(globals, lvl, d1, d2) && under(c1, c2)
+        }
+        (Val::Lam(_, _, c1), Val::Lam(_, _, c2)) => under(c1, c2),
+        //Eta: `f` is `/ x : A. f x`
+        (Val::Lam(icit, _, body), f @ Val::Neutral(..))
+        | (f @ Val::Neutral(..), Val::Lam(icit, _, body)) => conv(
+            globals,
+            lvl + 1,
+            &body.apply(globals, Val::var(lvl)),
+            &apply(globals, f.clone(), Val::var(lvl), *icit),
+        ),
+        (Val::Neutral(h1, s1), Val::Neutral(h2, s2))
+            if h1 == h2
+                && s1.len() == s2.len()
+                && s1
+                    .iter()
+                    .zip(s2)
+                    .all(|((a1, i1), (a2, i2))| i1 == i2 && conv(globals, lvl, a1, a2)) =>
+        {
+            true
+        }
+        _ => match (unfold(globals, a), unfold(globals, b)) {
+            (None, None) => false,
+            (a1, b1) => conv(
+                globals,
+                lvl,
+                a1.as_ref().unwrap_or(a),
+                b1.as_ref().unwrap_or(b),
+            ),
+        },
+    }
+}
+
+/// Converts a value back to a term, in a context with `lvl` variables. Definitions are kept folded.
+pub fn quote(globals: &Globals, lvl: usize, val: &Val) -> Tm {
+    let go = |lvl, val: &Val| Rc::new(quote(globals, lvl, val));
+    //The names of binders aren't kept in values, they are named when the term is shown
+    match val {
+        Val::Type(level) => Tm::Type(level.export()),
+        Val::Neutral(head, args) => {
+            let head = match head {
+                Head::Var(var) => Tm::Var(lvl - var - 1),
+                Head::Data(d) => Tm::Data(*d),
+                Head::Ctor(d, c) => Tm::Ctor(*d, *c),
+                Head::Elim(d) => Tm::Elim(*d),
+                Head::Global(id, levels) => {
+                    Tm::Global(*id, levels.iter().map(Level::export).collect())
+                }
+            };
+            args.iter()
+                .fold(head, |f, (a, icit)| Tm::App(Rc::new(f), go(lvl, a), *icit))
+        }
+        Val::Pi(icit, dom, cod) => Tm::Pi {
+            name: "_".into(),
+            icit: *icit,
+            dom: go(lvl, dom),
+            cod: go(lvl + 1, &cod.apply(globals, Val::var(lvl))),
+        },
+        Val::Lam(icit, typ, body) => Tm::Lam {
+            name: "_".into(),
+            icit: *icit,
+            typ: go(lvl, typ),
+            body: go(lvl + 1, &body.apply(globals, Val::var(lvl))),
+        },
+    }
+}
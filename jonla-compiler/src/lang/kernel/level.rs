@@ -0,0 +1,143 @@
+use crate::lang::core::Name;
+use crate::lang::level;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// A universe level: the maximum of a constant and of universe parameters plus an offset.
+/// Exported levels are converted to these when the kernel reads them, so it doesn't rely on the type checker's
+/// level arithmetic. They have no metavariables, as the levels of checked definitions are solved.
+#[derive(Clone, Debug, Default)]
+pub struct Level {
+    constant: usize,
+    params: BTreeMap<Name, usize>,
+}
+
+/// The values of the universe parameters of the definition a term is in
+pub type Univs = [(Name, Level)];
+
+impl Level {
+    pub fn param(name: Name) -> Level {
+        Level {
+            constant: 0,
+            params: BTreeMap::from([(name, 0)]),
+        }
+    }
+
+    /// The level that an exported level stands for, where the universe parameters have the values in `univs`
+    pub fn import(level: &level::Level, univs: &Univs) -> Result<Level, String> {
+        let constant = Level {
+            constant: level.constant,
+            params: BTreeMap::new(),
+        };
+        level
+            .vars
+            .iter()
+            .try_fold(constant, |result, (var, offset)| {
+                let level::LevelVar::Param(name) = var else {
+                    return Err("A universe level was left unsolved".to_string());
+                };
+                match univs.iter().find(|(u, _)| u == name) {
+                    Some((_, value)) => Ok(result.max(&value.plus(*offset))),
+                    None => Err(format!("Unknown universe `{}`", name)),
+                }
+            })
+    }
+
+    /// Shows an exported level, where every universe parameter stands for itself
+    pub fn show(level: &level::Level) -> String {
+        let univs: Vec<(Name, Level)> = level
+            .vars
+            .keys()
+            .filter_map(|var| match var {
+                level::LevelVar::Param(u) => Some((u.clone(), Level::param(u.clone()))),
+                level::LevelVar::Meta(_) => None,
+            })
+            .collect();
+        Level::import(level, &univs).map_or("?".to_string(), |level| level.to_string())
+    }
+
+    /// The level as it is exported, the inverse of `import` with every parameter standing for itself
+    pub fn export(&self) -> level::Level {
+        level::Level {
+            constant: self.constant,
+            vars: self
+                .params
+                .iter()
+                .map(|(name, offset)| (level::LevelVar::Param(name.clone()), *offset))
+                .collect(),
+        }
+    }
+
+    fn plus(&self, n: usize) -> Level {
+        Level {
+            constant: self.constant + n,
+            params: self
+                .params
+                .iter()
+                .map(|(u, k)| (u.clone(), k + n))
+                .collect(),
+        }
+    }
+
+    pub fn succ(&self) -> Level {
+        self.plus(1)
+    }
+
+    pub fn max(&self, other: &Level) -> Level {
+        let mut params = self.params.clone();
+        for (u, k) in &other.params {
+            let offset = params.entry(u.clone()).or_insert(*k);
+            *offset = (*offset).max(*k);
+        }
+        Level {
+            constant: self.constant.max(other.constant),
+            params,
+        }
+    }
+
+    /// Whether this level is at most `other` for every value of the parameters.
+    /// The parameters are at least 0, so `other` is at least its constant and each of its offsets.
+    pub fn leq(&self, other: &Level) -> bool {
+        let other_min = other
+            .params
+            .values()
+            .copied()
+            .fold(other.constant, usize::max);
+        self.constant <= other_min
+            && self
+                .params
+                .iter()
+                .all(|(u, k)| other.params.get(u).is_some_and(|m| k <= m))
+    }
+}
+
+/// Levels are equal if they are equal for every value of the parameters, such as `max 1 (u + 1)` and `u + 1`
+impl PartialEq for Level {
+    fn eq(&self, other: &Level) -> bool {
+        self.leq(other) && other.leq(self)
+    }
+}
+
+impl Eq for Level {}
+
+/// Shows a level as the maximum of its parts, such as `max 1 (u + 1)`
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts: Vec<String> = self
+            .params
+            .iter()
+            .map(|(u, k)| match k {
+                0 => u.to_string(),
+                k => format!("({} + {})", u, k),
+            })
+            .collect();
+        if self.constant > 0 || parts.is_empty() {
+            parts.insert(0, self.constant.to_string());
+        }
+        let shown = parts
+            .into_iter()
+            .reduce(|l, r| format!("(max {} {})", l, r))
+            .unwrap_or_default();
+        write!(f, "{}", shown)
+    }
+}
@@ -0,0 +1,537 @@
+//! The kernel checks exported definitions again, independently of the type checker that elaborated them.
+//! It only shares the core terms and the export format with the rest of the compiler, so a mistake in the
+//! elaborator, its evaluator or its termination checker can't make the kernel accept an ill-typed definition.
+
+use crate::lang::core::{CtorDef, DataDef, Icit, Name, Tm};
+use crate::lang::export::Decl;
+use data::{check_ctor_type, elim_type, MOTIVE_UNIVERSE};
+use eval::{conv, eval, quote, whnf, Closure, Env, Val};
+use level::Level;
+use std::rc::Rc;
+use termination::is_structural;
+
+mod data;
+mod eval;
+mod level;
+mod termination;
+
+/// The constants the kernel defined, with the same ids as the globals they were exported from
+#[derive(Clone, Debug, Default)]
+pub struct Globals {
+    pub defs: Vec<Def>,
+    pub datas: Vec<DataDef>,
+}
+
+/// A checked definition, or a data type, constructor or eliminator that the kernel defined
+#[derive(Clone, Debug)]
+pub struct Def {
+    pub name: Name,
+    pub levels: Vec<Name>,
+    pub typ: Tm,
+    pub value: Tm,
+    /// Whether the definition refers to itself, in which case it is structurally recursive
+    pub recursive: bool,
+    pub partial: bool,
+}
+
+impl Def {
+    /// The environment to evaluate the type or value of the definition in, with its universe parameters set to `levels`
+    fn env(&self, levels: &[Level]) -> Env {
+        Env::new(
+            self.levels
+                .iter()
+                .cloned()
+                .zip(levels.iter().cloned())
+                .collect(),
+        )
+    }
+}
+
+/// An error in a declaration that the kernel checked
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KernelError {
+    /// The name of the declaration
+    pub name: Name,
+    pub message: String,
+}
+
+/// Checks the declarations in order, returning the globals they define.
+/// Definitions get the same ids as in the globals the declarations were exported from, see `export`.
+pub fn check_decls(decls: &[Decl]) -> Result<Globals, KernelError> {
+    let mut globals = Globals::default();
+    for decl in decls {
+        match decl {
+            Decl::Data {
+                name,
+                elim_name,
+                typ,
+                params,
+                ctors,
+            } => check_data(&mut globals, name, elim_name, typ, *params, ctors),
+            Decl::Def {
+                name,
+                levels,
+                typ,
+                value,
+                partial,
+            } => check_def(&mut globals, name, levels, typ, value, *partial),
+        }
+        .map_err(|message| KernelError {
+            name: match decl {
+                Decl::Data { name, .. } | Decl::Def { name, .. } => name.clone(),
+            },
+            message,
+        })?;
+    }
+    Ok(globals)
+}
+
+/// Checks a data type and its constructors, and defines them with the eliminator of the data type
+fn check_data(
+    globals: &mut Globals,
+    name: &Name,
+    elim_name: &Name,
+    typ: &Tm,
+    params: usize,
+    ctors: &[(Name, Tm)],
+) -> Result<(), String> {
+    //Data types are not universe polymorphic
+    let kernel = Kernel {
+        globals,
+        levels: &[],
+        current: None,
+    };
+    kernel.sort(&Ctx::new(&[]), typ)?;
+    let mut indices: usize = 0;
+    let mut result = typ;
+    while let Tm::Pi { cod, .. } = result {
+        indices += 1;
+        result = cod;
+    }
+    let Tm::Type(level) = result else {
+        return Err(format!("The type of `{}` should end in a universe", name));
+    };
+    let level = Level::import(level, &[])?;
+    let Some(indices) = indices.checked_sub(params) else {
+        return Err(format!("`{}` has fewer than {} parameters", name, params));
+    };
+
+    let d = globals.datas.len();
+    globals.datas.push(DataDef {
+        name: name.clone(),
+        elim_name: elim_name.clone(),
+        typ: typ.clone(),
+        params,
+        indices,
+        ctors: vec![],
+    });
+    define(globals, name.clone(), vec![], typ.clone(), Tm::Data(d));
+
+    for (ctor_name, ctor_type) in ctors {
+        let kernel = Kernel {
+            globals,
+            levels: &[],
+            current: None,
+        };
+        //The constructor starts with the parameters of the data type, and its fields are in its universe
+        let mut ctx = Ctx::new(&[]);
+        let (mut data_params, mut ctor_params) = (typ, ctor_type);
+        for _ in 0..params {
+            let (
+                Tm::Pi {
+                    dom: d1, cod: c1, ..
+                },
+                Tm::Pi {
+                    dom: d2, cod: c2, ..
+                },
+            ) = (data_params, ctor_params)
+            else {
+                return Err(format!(
+                    "`{}` should start with the parameters of `{}`",
+                    ctor_name, name
+                ));
+            };
+            kernel.sort(&ctx, d2)?;
+            let param = eval(globals, &ctx.env, d1);
+            if !conv(globals, ctx.lvl(), &param, &eval(globals, &ctx.env, d2)) {
+                return Err(format!(
+                    "`{}` should start with the parameters of `{}`",
+                    ctor_name, name
+                ));
+            }
+            ctx = ctx.bind(param);
+            (data_params, ctor_params) = (c1, c2);
+        }
+        let ctor_level = kernel.sort(&ctx, ctor_params)?;
+        if !ctor_level.leq(&level) {
+            return Err(format!(
+                "The fields of `{}` should be in the universe of `{}`",
+                ctor_name, name
+            ));
+        }
+        let fields = check_ctor_type(&globals.datas[d], d, ctor_type)?;
+        let c = globals.datas[d].ctors.len();
+        globals.datas[d].ctors.push(CtorDef {
+            name: ctor_name.clone(),
+            typ: ctor_type.clone(),
+            fields,
+        });
+        define(
+            globals,
+            ctor_name.clone(),
+            vec![],
+            ctor_type.clone(),
+            Tm::Ctor(d, c),
+        );
+    }
+
+    let elim_type = elim_type(&globals.datas[d], d);
+    define(
+        globals,
+        elim_name.clone(),
+        vec![MOTIVE_UNIVERSE.into()],
+        elim_type,
+        Tm::Elim(d),
+    );
+    Ok(())
+}
+
+/// Checks a definition and defines it. Its value can refer to it if it is structurally recursive or partial,
+/// and it can only use partial definitions if it is partial itself.
+fn check_def(
+    globals: &mut Globals,
+    name: &Name,
+    levels: &[Name],
+    typ: &Tm,
+    value: &Tm,
+    partial: bool,
+) -> Result<(), String> {
+    let id = globals.defs.len();
+    let mut kernel = Kernel {
+        globals,
+        levels,
+        current: None,
+    };
+    let ctx = Ctx::new(levels);
+    kernel.sort(&ctx, typ)?;
+    let typ_val = eval(globals, &ctx.env, typ);
+    kernel.current = Some(typ_val.clone());
+    kernel.check(&ctx, value, &typ_val)?;
+
+    let is_partial = |g: usize| globals.defs.get(g).is_some_and(|def| def.partial);
+    if let Some(g) = typ.find_global(&is_partial) {
+        return Err(format!(
+            "The type of `{}` uses the partial definition `{}`",
+            name, globals.defs[g].name
+        ));
+    }
+    if let Some(g) = value.find_global(&is_partial).filter(|_| !partial) {
+        return Err(format!(
+            "`{}` uses the partial definition `{}`, but isn't partial",
+            name, globals.defs[g].name
+        ));
+    }
+    let recursive = value.find_global(&|g| g == id).is_some();
+    if recursive && !partial && !is_structural(globals, id, value) {
+        return Err(format!("`{}` is not structurally recursive", name));
+    }
+
+    globals.defs.push(Def {
+        name: name.clone(),
+        levels: levels.to_vec(),
+        typ: typ.clone(),
+        value: value.clone(),
+        recursive,
+        partial,
+    });
+    Ok(())
+}
+
+/// The local variables in scope while checking a term
+#[derive(Clone, Debug)]
+struct Ctx {
+    env: Env,
+    types: Vec<Val>,
+}
+
+impl Ctx {
+    /// A context without variables, in a definition with the universe parameters `levels`
+    fn new(levels: &[Name]) -> Ctx {
+        let univs = levels
+            .iter()
+            .map(|u| (u.clone(), Level::param(u.clone())))
+            .collect();
+        Ctx {
+            env: Env::new(univs),
+            types: vec![],
+        }
+    }
+
+    fn lvl(&self) -> usize {
+        self.env.vals.len()
+    }
+
+    fn bind(&self, typ: Val) -> Ctx {
+        self.define(typ, Val::var(self.lvl()))
+    }
+
+    fn define(&self, typ: Val, value: Val) -> Ctx {
+        let mut ctx = self.clone();
+        ctx.env.vals.push(value);
+        ctx.types.push(typ);
+        ctx
+    }
+}
+
+/// Checks core terms against the globals
+struct Kernel<'g> {
+    globals: &'g Globals,
+    /// The universe parameters that terms can use
+    levels: &'g [Name],
+    /// The type of the definition being checked, which its value can refer to with the next id
+    current: Option<Val>,
+}
+
+impl Kernel<'_> {
+    /// Checks that `tm` is a type, returning its universe
+    fn sort(&self, ctx: &Ctx, tm: &Tm) -> Result<Level, String> {
+        match whnf(self.globals, &self.infer(ctx, tm)?) {
+            Val::Type(level) => Ok(level),
+            typ => Err(format!(
+                "Expected a type, but found a term of type `{}`",
+                self.show(ctx, &typ)
+            )),
+        }
+    }
+
+    fn check(&self, ctx: &Ctx, tm: &Tm, typ: &Val) -> Result<(), String> {
+        let found = self.infer(ctx, tm)?;
+        if subtype(self.globals, ctx.lvl(), &found, typ) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Type mismatch: expected `{}`, but found `{}`",
+                self.show(ctx, typ),
+                self.show(ctx, &found)
+            ))
+        }
+    }
+
+    fn infer(&self, ctx: &Ctx, tm: &Tm) -> Result<Val, String> {
+        let globals = self.globals;
+        match tm {
+            Tm::Type(level) => Ok(Val::Type(Level::import(level, &ctx.env.univs)?.succ())),
+            Tm::Var(ix) => ctx
+                .types
+                .get(ctx.lvl().wrapping_sub(ix + 1))
+                .cloned()
+                .ok_or_else(|| format!("Unbound variable {}", ix)),
+            Tm::Global(id, levels) => {
+                let levels = levels
+                    .iter()
+                    .map(|l| Level::import(l, &ctx.env.univs))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match (globals.defs.get(*id), &self.current) {
+                    (Some(def), _) if def.levels.len() == levels.len() => {
+                        Ok(eval(globals, &def.env(&levels), &def.typ))
+                    }
+                    (Some(def), _) => Err(format!(
+                        "`{}` should get {} universe(s)",
+                        def.name,
+                        def.levels.len()
+                    )),
+                    //A recursive reference gets the universe parameters of the definition
+                    (None, Some(typ)) if *id == globals.defs.len() => {
+                        let own: Vec<Level> = self
+                            .levels
+                            .iter()
+                            .map(|u| Level::param(u.clone()))
+                            .collect();
+                        if levels != own {
+                            return Err(
+                                "A recursive reference should use the same universes".into()
+                            );
+                        }
+                        Ok(typ.clone())
+                    }
+                    (None, _) => Err(format!("Unknown global {}", id)),
+                }
+            }
+            Tm::Let {
+                name: _,
+                typ,
+                value,
+                body,
+            } => {
+                self.sort(ctx, typ)?;
+                let typ = eval(globals, &ctx.env, typ);
+                self.check(ctx, value, &typ)?;
+                let value = eval(globals, &ctx.env, value);
+                self.infer(&ctx.define(typ, value), body)
+            }
+            Tm::Pi { dom, cod, .. } => {
+                let l1 = self.sort(ctx, dom)?;
+                let inner = ctx.bind(eval(globals, &ctx.env, dom));
+                let l2 = self.sort(&inner, cod)?;
+                Ok(Val::Type(l1.max(&l2)))
+            }
+            Tm::Lam {
+                icit, typ, body, ..
+            } => {
+                self.sort(ctx, typ)?;
+                let dom = eval(globals, &ctx.env, typ);
+                let inner = ctx.bind(dom.clone());
+                let cod = quote(globals, inner.lvl(), &self.infer(&inner, body)?);
+                Ok(Val::Pi(
+                    *icit,
+                    Rc::new(dom),
+                    Closure {
+                        env: ctx.env.clone(),
+                        body: Rc::new(cod),
+                    },
+                ))
+            }
+            Tm::App(f, a, icit) => match whnf(globals, &self.infer(ctx, f)?) {
+                Val::Pi(i, dom, cod) if i == *icit => {
+                    self.check(ctx, a, &dom)?;
+                    Ok(cod.apply(globals, eval(globals, &ctx.env, a)))
+                }
+                typ => Err(format!(
+                    "Expected a function, but found a term of type `{}`",
+                    self.show(ctx, &typ)
+                )),
+            },
+            Tm::Data(d) => match globals.datas.get(*d) {
+                Some(data) => Ok(eval(globals, &Env::new(vec![]), &data.typ)),
+                None => Err(format!("Unknown data type {}", d)),
+            },
+            Tm::Ctor(d, c) => match globals.datas.get(*d).and_then(|data| data.ctors.get(*c)) {
+                Some(ctor) => Ok(eval(globals, &Env::new(vec![]), &ctor.typ)),
+                None => Err(format!("Unknown constructor {} of data type {}", c, d)),
+            },
+            Tm::Elim(_) => Err("An eliminator should be referred to by its definition".into()),
+            Tm::Meta(_) => Err("A metavariable was left unsolved".into()),
+            Tm::Hole(_) => Err("A hole was left unfilled".into()),
+        }
+    }
+
+    /// Shows a value in a context, the variables are shown by their level as there are no names
+    fn show(&self, ctx: &Ctx, val: &Val) -> String {
+        show(
+            self.globals,
+            ctx.lvl(),
+            &quote(self.globals, ctx.lvl(), val),
+        )
+    }
+}
+
+/// Whether a value of type `a` also has type `b`, as universes are cumulative.
+/// Function types are covariant in their codomain, other types have to be equal.
+fn subtype(globals: &Globals, lvl: usize, a: &Val, b: &Val) -> bool {
+    match (whnf(globals, a), whnf(globals, b)) {
+        (Val::Type(l1), Val::Type(l2)) => l1.leq(&l2),
+        (Val::Pi(i1, d1, c1), Val::Pi(i2, d2, c2)) => {
+            i1 == i2
+                && conv(globals, lvl, &d1, &d2)
+                && subtype(
+                    globals,
+                    lvl + 1,
+                    &c1.apply(globals, Val::var(lvl)),
+                    &c2.apply(globals, Val::var(lvl)),
+                )
+        }
+        (a, b) => conv(globals, lvl, &a, &b),
+    }
+}
+
+/// Adds a constant to the globals
+fn define(globals: &mut Globals, name: Name, levels: Vec<Name>, typ: Tm, value: Tm) {
+    globals.defs.push(Def {
+        name,
+        levels,
+        typ,
+        value,
+        recursive: false,
+        partial: false,
+    });
+}
+
+/// Shows a term in a context with `depth` variables, which are named by their level
+fn show(globals: &Globals, depth: usize, tm: &Tm) -> String {
+    //Terms that don't need parentheses as an argument
+    let atom = |depth, tm: &Tm| match tm {
+        Tm::Var(_) | Tm::Global(..) | Tm::Data(_) | Tm::Ctor(..) | Tm::Elim(_) => {
+            show(globals, depth, tm)
+        }
+        Tm::Type(level) if level.vars.is_empty() && level.constant == 0 => show(globals, depth, tm),
+        _ => format!("({})", show(globals, depth, tm)),
+    };
+    let var = |lvl: usize| format!("x{}", lvl);
+    match tm {
+        Tm::Type(level) if level.vars.is_empty() && level.constant == 0 => "Type".to_string(),
+        Tm::Type(level) => format!("Type {}", Level::show(level)),
+        Tm::Var(ix) => var(depth - 1 - ix),
+        Tm::Global(id, _) => globals.defs[*id].name.to_string(),
+        Tm::Data(d) => globals.datas[*d].name.to_string(),
+        Tm::Ctor(d, c) => globals.datas[*d].ctors[*c].name.to_string(),
+        Tm::Elim(d) => globals.datas[*d].elim_name.to_string(),
+        Tm::Meta(_) | Tm::Hole(_) => "?".to_string(),
+        Tm::Let {
+            typ, value, body, ..
+        } => format!(
+            "let {} : {} = {}; {}",
+            var(depth),
+            show(globals, depth, typ),
+            show(globals, depth, value),
+            show(globals, depth + 1, body)
+        ),
+        Tm::Pi {
+            icit: Icit::Explicit,
+            dom,
+            cod,
+            ..
+        } if !cod.uses_var(0) => {
+            let dom = match &**dom {
+                Tm::Pi { .. } | Tm::Lam { .. } | Tm::Let { .. } => atom(depth, dom),
+                _ => show(globals, depth, dom),
+            };
+            format!("{} -> {}", dom, show(globals, depth + 1, cod))
+        }
+        Tm::Pi { icit, dom, cod, .. } => format!(
+            "{} -> {}",
+            braced(
+                *icit,
+                &format!("{} : {}", var(depth), show(globals, depth, dom))
+            ),
+            show(globals, depth + 1, cod)
+        ),
+        Tm::Lam {
+            icit, typ, body, ..
+        } => {
+            let binder = format!("{} : {}", var(depth), show(globals, depth, typ));
+            let binder = match icit {
+                Icit::Explicit => binder,
+                Icit::Implicit => format!("{{{}}}", binder),
+            };
+            format!("/ {}. {}", binder, show(globals, depth + 1, body))
+        }
+        Tm::App(f, a, icit) => {
+            let f = match &**f {
+                Tm::Pi { .. } | Tm::Lam { .. } | Tm::Let { .. } => atom(depth, f),
+                _ => show(globals, depth, f),
+            };
+            match icit {
+                Icit::Explicit => format!("{} {}", f, atom(depth, a)),
+                Icit::Implicit => format!("{} {{{}}}", f, show(globals, depth, a)),
+            }
+        }
+    }
+}
+
+/// A binder of a function type, in parentheses or braces
+fn braced(icit: Icit, binder: &str) -> String {
+    match icit {
+        Icit::Explicit => format!("({})", binder),
+        Icit::Implicit => format!("{{{}}}", binder),
+    }
+}
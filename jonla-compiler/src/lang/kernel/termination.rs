@@ -0,0 +1,143 @@
+use crate::lang::core::Tm;
+use crate::lang::kernel::Globals;
+
+/// Whether the value of definition `id` is structurally recursive: some parameter, one of the lambdas the value
+/// starts with, gets a strictly smaller argument in every recursive call. A variable is smaller than a parameter
+/// if it is a field bound by a method of an eliminator applied to the parameter, or to something smaller than it.
+pub fn is_structural(globals: &Globals, id: usize, value: &Tm) -> bool {
+    let mut walker = Walker {
+        globals,
+        id,
+        params: 0,
+        below: vec![],
+        candidates: None,
+    };
+    let mut body = value;
+    while let Tm::Lam { body: b, .. } = body {
+        walker.params += 1;
+        walker.below.push(vec![]);
+        body = b;
+    }
+    walker.walk(body)
+}
+
+struct Walker<'g> {
+    globals: &'g Globals,
+    id: usize,
+    params: usize,
+    /// For every variable in scope, the parameters it is strictly smaller than
+    below: Vec<Vec<usize>>,
+    /// The parameters that every recursive call so far made smaller, `None` before the first call
+    candidates: Option<Vec<usize>>,
+}
+
+impl Walker<'_> {
+    fn smaller_than(&self, tm: &Tm) -> Vec<usize> {
+        match tm.spine().0 {
+            Tm::Var(ix) => self.below[self.below.len() - 1 - ix].clone(),
+            _ => vec![],
+        }
+    }
+
+    fn under(&mut self, below: Vec<usize>, tm: &Tm) -> bool {
+        self.below.push(below);
+        let result = self.walk(tm);
+        self.below.pop();
+        result
+    }
+
+    fn walk(&mut self, tm: &Tm) -> bool {
+        match tm {
+            Tm::Type(_)
+            | Tm::Var(_)
+            | Tm::Data(_)
+            | Tm::Ctor(..)
+            | Tm::Elim(_)
+            | Tm::Meta(_)
+            | Tm::Hole(_) => true,
+            Tm::Global(..) | Tm::App(..) => self.walk_spine(tm),
+            Tm::Let {
+                typ, value, body, ..
+            } => {
+                let below = self.smaller_than(value);
+                self.walk(typ) && self.walk(value) && self.under(below, body)
+            }
+            Tm::Pi {
+                dom: typ,
+                cod: body,
+                ..
+            }
+            | Tm::Lam { typ, body, .. } => self.walk(typ) && self.under(vec![], body),
+        }
+    }
+
+    fn walk_spine(&mut self, tm: &Tm) -> bool {
+        let (head, args) = tm.spine();
+        //Eliminators are referred to by the definitions for them
+        let head = match head {
+            Tm::Global(id, _) => match self.globals.defs.get(*id) {
+                Some(def) if matches!(def.value, Tm::Elim(_)) => &def.value,
+                _ => head,
+            },
+            _ => head,
+        };
+        match head {
+            Tm::Global(id, _) if *id == self.id => {
+                let decreasing: Vec<usize> = (0..args.len().min(self.params))
+                    .filter(|&i| self.smaller_than(args[i]).contains(&i))
+                    .filter(|i| self.candidates.as_ref().is_none_or(|c| c.contains(i)))
+                    .collect();
+                if decreasing.is_empty() {
+                    return false;
+                }
+                self.candidates = Some(decreasing);
+            }
+            Tm::Elim(d) if args.len() >= self.globals.datas[*d].elim_arity() => {
+                let data = &self.globals.datas[*d];
+                let target = args[data.elim_arity() - 1];
+                let mut below = self.smaller_than(target);
+                if let Tm::Var(ix) = target {
+                    let lvl = self.below.len() - 1 - ix;
+                    if lvl < self.params && !below.contains(&lvl) {
+                        below.push(lvl);
+                    }
+                }
+                let methods = data.params + 1..data.params + 1 + data.ctors.len();
+                for (i, arg) in args.iter().enumerate() {
+                    if !methods.contains(&i) {
+                        if !self.walk(arg) {
+                            return false;
+                        }
+                        continue;
+                    }
+                    //The method binds the fields of the target first, which are smaller than it
+                    let fields = data.ctors[i - methods.start].fields;
+                    let depth = self.below.len();
+                    let mut body = *arg;
+                    let mut ok = true;
+                    while let Tm::Lam { typ, body: b, .. } = body {
+                        if self.below.len() - depth == fields {
+                            break;
+                        }
+                        ok = ok && self.walk(typ);
+                        self.below.push(below.clone());
+                        body = b;
+                    }
+                    ok = ok && self.walk(body);
+                    self.below.truncate(depth);
+                    if !ok {
+                        return false;
+                    }
+                }
+                return true;
+            }
+            Tm::Global(..) => {}
+            _ => {
+                if !self.walk(head) {
+                    return false;
+                }
+            }
+        }
+        args.into_iter().all(|arg| self.walk(arg))
+    }
+}
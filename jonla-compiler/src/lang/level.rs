@@ -0,0 +1,219 @@
+use crate::lang::core::Name;
+use crate::lang::Span;
+use std::collections::BTreeMap;
+
+/// A variable in a universe level
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum LevelVar {
+    /// A universe declared with `universe`, which a definition can be polymorphic over
+    Param(Name),
+    /// A level that isn't known yet, which the checker solves at the end of a definition
+    Meta(usize),
+}
+
+/// A universe level in normal form: the maximum of a constant and of variables plus an offset.
+/// The constant is 0 if it is at most one of the offsets, so equal levels have the same representation.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Level {
+    pub constant: usize,
+    pub vars: BTreeMap<LevelVar, usize>,
+}
+
+impl Level {
+    pub fn zero() -> Level {
+        Level::default()
+    }
+
+    pub fn constant(constant: usize) -> Level {
+        Level {
+            constant,
+            vars: BTreeMap::new(),
+        }
+    }
+
+    pub fn var(var: LevelVar) -> Level {
+        Level {
+            constant: 0,
+            vars: BTreeMap::from([(var, 0)]),
+        }
+    }
+
+    /// The level `n` above this one
+    pub fn plus(&self, n: usize) -> Level {
+        Level {
+            constant: self.constant + n,
+            vars: self.vars.iter().map(|(v, k)| (v.clone(), k + n)).collect(),
+        }
+        .normalized()
+    }
+
+    /// The level above this one, which contains a universe at this level
+    pub fn succ(&self) -> Level {
+        self.plus(1)
+    }
+
+    pub fn max(&self, other: &Level) -> Level {
+        let mut vars = self.vars.clone();
+        for (v, k) in &other.vars {
+            let offset = vars.entry(v.clone()).or_insert(*k);
+            *offset = (*offset).max(*k);
+        }
+        Level {
+            constant: self.constant.max(other.constant),
+            vars,
+        }
+        .normalized()
+    }
+
+    fn normalized(mut self) -> Level {
+        if self.vars.values().any(|k| *k >= self.constant) {
+            self.constant = 0;
+        }
+        self
+    }
+
+    /// Whether this level is at most `other`, whatever the values of the variables are
+    pub fn leq(&self, other: &Level) -> bool {
+        let other_min = other
+            .vars
+            .values()
+            .copied()
+            .fold(other.constant, usize::max);
+        self.constant <= other_min
+            && self
+                .vars
+                .iter()
+                .all(|(v, k)| other.vars.get(v).is_some_and(|m| k <= m))
+    }
+
+    /// Replaces the variables for which `f` gives a level
+    pub fn subst(&self, f: &dyn Fn(&LevelVar) -> Option<Level>) -> Level {
+        self.vars
+            .iter()
+            .fold(Level::constant(self.constant), |level, (v, k)| {
+                let value = f(v).unwrap_or_else(|| Level::var(v.clone()));
+                level.max(&value.plus(*k))
+            })
+    }
+
+    pub fn has_metas(&self) -> bool {
+        self.vars.keys().any(|v| matches!(v, LevelVar::Meta(_)))
+    }
+
+    pub fn params(&self) -> impl Iterator<Item = &Name> {
+        self.vars.keys().filter_map(|v| match v {
+            LevelVar::Param(name) => Some(name),
+            LevelVar::Meta(_) => None,
+        })
+    }
+
+    /// A level that is at least this one minus `n`. Variables can't be lowered, so they are kept as they are.
+    fn minus(&self, n: usize) -> Level {
+        Level {
+            constant: self.constant.saturating_sub(n),
+            vars: self
+                .vars
+                .iter()
+                .map(|(v, k)| (v.clone(), k.saturating_sub(n)))
+                .collect(),
+        }
+        .normalized()
+    }
+}
+
+/// A constraint `lhs <= rhs` that must hold for the definition being checked
+#[derive(Clone, Debug)]
+pub struct LevelConstraint {
+    pub lhs: Level,
+    pub rhs: Level,
+    /// The part of the source that caused the constraint
+    pub span: Option<Span>,
+}
+
+/// The level metavariables of a definition, and the constraints on them
+#[derive(Clone, Debug, Default)]
+pub struct LevelMetas {
+    count: usize,
+    constraints: Vec<LevelConstraint>,
+    solution: Vec<Level>,
+    /// The span of the source that new constraints are about
+    pub span: Option<Span>,
+}
+
+impl LevelMetas {
+    pub fn fresh(&mut self) -> Level {
+        self.count += 1;
+        Level::var(LevelVar::Meta(self.count - 1))
+    }
+
+    /// Requires `lhs <= rhs`. Returns false if this is known to be false already,
+    /// constraints on metavariables are only checked by `solve`.
+    pub fn leq(&mut self, lhs: &Level, rhs: &Level) -> bool {
+        if !lhs.has_metas() && !rhs.has_metas() {
+            return lhs.leq(rhs);
+        }
+        self.constraints.push(LevelConstraint {
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+            span: self.span,
+        });
+        true
+    }
+
+    /// Requires `a = b`, see `leq`
+    pub fn equal(&mut self, a: &Level, b: &Level) -> bool {
+        self.leq(a, b) && self.leq(b, a)
+    }
+
+    /// Gives every metavariable the smallest level that satisfies the constraints.
+    /// Returns a constraint that can't be satisfied, with the solution filled in, if there is one.
+    pub fn solve(&mut self) -> Result<(), LevelConstraint> {
+        self.solution = vec![Level::zero(); self.count];
+        //Raise metavariables until every constraint holds.
+        //The least solution is found after one round per metavariable, unless the constraints are cyclic.
+        for _ in 0..=self.count {
+            let mut changed = false;
+            for i in 0..self.constraints.len() {
+                let constraint = &self.constraints[i];
+                let lhs = self.zonk(&constraint.lhs);
+                if lhs.leq(&self.zonk(&constraint.rhs)) {
+                    continue;
+                }
+                let raise = constraint.rhs.vars.iter().find_map(|(v, k)| match v {
+                    LevelVar::Meta(m) => Some((*m, *k)),
+                    LevelVar::Param(_) => None,
+                });
+                let Some((meta, offset)) = raise else {
+                    return Err(self.zonk_constraint(constraint));
+                };
+                self.solution[meta] = self.solution[meta].max(&lhs.minus(offset));
+                changed = true;
+            }
+            if !changed {
+                return Ok(());
+            }
+        }
+        let unsolved = self
+            .constraints
+            .iter()
+            .find(|c| !self.zonk(&c.lhs).leq(&self.zonk(&c.rhs)))
+            .expect("Solving stopped while all constraints hold");
+        Err(self.zonk_constraint(unsolved))
+    }
+
+    /// Replaces the metavariables in `level` by their solution
+    pub fn zonk(&self, level: &Level) -> Level {
+        level.subst(&|v| match v {
+            LevelVar::Meta(m) => self.solution.get(*m).cloned(),
+            LevelVar::Param(_) => None,
+        })
+    }
+
+    fn zonk_constraint(&self, constraint: &LevelConstraint) -> LevelConstraint {
+        LevelConstraint {
+            lhs: self.zonk(&constraint.lhs),
+            rhs: self.zonk(&constraint.rhs),
+            span: constraint.span,
+        }
+    }
+}
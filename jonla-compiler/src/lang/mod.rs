@@ -0,0 +1,54 @@
+use jonla_macros::parser::parser_result::ParseError;
+
+pub mod bytecode;
+pub mod check;
+pub mod codegen;
+pub mod conv;
+pub mod core;
+pub mod data;
+pub mod erase;
+pub mod eval;
+pub mod export;
+pub mod kernel;
+pub mod level;
+pub mod module;
+pub mod pretty;
+pub mod termination;
+pub mod unify;
+pub mod vm;
+
+/// A byte range `(start, end)` in a source file
+pub type Span = (usize, usize);
+
+/// Names in the AST are slices of the source, so their position can be recovered.
+/// Returns `None` for names that don't come from the source, such as the `_` of an arrow type.
+pub fn span_of(src: &str, name: &str) -> Option<Span> {
+    let start = (name.as_ptr() as usize).checked_sub(src.as_ptr() as usize)?;
+    (start + name.len() <= src.len()).then(|| (start, start + name.len()))
+}
+
+/// Shows the lines of `src` that contain `span`, with the span underlined
+pub fn render_span(src: &str, (start, end): Span) -> String {
+    let line_start = src[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = src[end..].find('\n').map(|i| end + i).unwrap_or(src.len());
+    format!(
+        "{}\n{: <3$}{:^<4$}\n",
+        &src[line_start..line_end],
+        "",
+        "",
+        src[line_start..start].chars().count(),
+        src[start..end].chars().count().max(1)
+    )
+}
+
+/// The part of `src` a parse error is about, from the start of the failed rule up to and including the unexpected character.
+/// A newline is not included, so the span stays on one line.
+pub fn parse_error_span(src: &str, err: &ParseError) -> Span {
+    let pos = err.pos.min(src.len());
+    let start = err.start.unwrap_or(pos).min(pos);
+    let end = match src[pos..].chars().next() {
+        Some(c) if c != '\n' => pos + c.len_utf8(),
+        _ => pos,
+    };
+    (start, end)
+}
@@ -0,0 +1,311 @@
+use crate::autogen::ast::{Constructor, Decl};
+use crate::autogen::parse::parse_program;
+use crate::lang::check::Checker;
+use crate::lang::core::{Globals, Name, Namespace};
+use crate::lang::data::check_data;
+use crate::lang::{parse_error_span, render_span, span_of, Span};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A file that was loaded and checked
+#[derive(Clone, Debug)]
+pub struct Module {
+    /// The path the module was loaded from, as given by the user or the importing file
+    pub path: PathBuf,
+    /// The name other modules use to qualify the definitions of this module, which is the file name without extension
+    pub name: Name,
+    /// The definitions of this module, by their unqualified name
+    pub defs: Vec<(Name, usize)>,
+    /// The contents of the file, for errors about the module after it was loaded
+    pub src: String,
+}
+
+/// An error in one of the loaded files
+#[derive(Clone, Debug)]
+pub struct LoadError {
+    pub path: PathBuf,
+    /// The contents of the file, empty if it could not be read
+    pub src: String,
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl LoadError {
+    /// Shows the error, below the part of the file it is about if that is known
+    pub fn render(&self) -> String {
+        match self.span {
+            Some(span) => format!(
+                "{}:\n{}{}",
+                self.path.display(),
+                render_span(&self.src, span),
+                self.message
+            ),
+            None => format!("{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Loads files together with the files they import.
+/// Every file is checked once, the definitions of all files are added to the same `Globals`.
+#[derive(Default)]
+pub struct Loader {
+    pub globals: Globals,
+    pub modules: Vec<Module>,
+    /// The goals of the holes in the loaded files, in the order they were checked.
+    /// Holes are not errors, so checking continues after them, but their goals are shown in the same way.
+    pub goals: Vec<LoadError>,
+    /// The index in `modules` of every loaded file, by canonical path
+    loaded: HashMap<PathBuf, usize>,
+    /// The canonical paths of the files that are being loaded, each one imported by the one before it
+    loading: Vec<PathBuf>,
+}
+
+impl Loader {
+    /// Loads the file at `path`, returning the index of its module.
+    /// Imports are loaded first, relative to the directory of the importing file.
+    /// Checking continues after a declaration with an error, so all errors in the file are returned.
+    pub fn load(&mut self, path: &Path) -> Result<usize, Vec<LoadError>> {
+        let error = |message| {
+            vec![LoadError {
+                path: path.to_path_buf(),
+                src: String::new(),
+                span: None,
+                message,
+            }]
+        };
+        let canonical = path
+            .canonicalize()
+            .map_err(|err| error(format!("Could not read file: {}", err)))?;
+        if let Some(&id) = self.loaded.get(&canonical) {
+            return Ok(id);
+        }
+        let src = std::fs::read_to_string(path)
+            .map_err(|err| error(format!("Could not read file: {}", err)))?;
+
+        self.loading.push(canonical.clone());
+        let id = self.load_source(path, src);
+        self.loading.pop();
+        let id = id?;
+        self.loaded.insert(canonical, id);
+        Ok(id)
+    }
+
+    /// Loads `src` as the contents of the file at `path`, such as a file that is being edited and wasn't saved.
+    /// The module is named after `path`, and its imports are loaded relative to it.
+    pub fn load_source(&mut self, path: &Path, src: String) -> Result<usize, Vec<LoadError>> {
+        let error = |src: &str, span, message| {
+            vec![LoadError {
+                path: path.to_path_buf(),
+                src: src.to_string(),
+                span,
+                message,
+            }]
+        };
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(name) if is_identifier(name) => Name::from(name),
+            _ => {
+                return Err(error(
+                    &src,
+                    None,
+                    format!(
+                        "The file name of `{}` can't be used as a module name, it should be an identifier",
+                        path.display()
+                    ),
+                ))
+            }
+        };
+        let decls = match parse_program(&src).inner {
+            Ok(ok) => ok.result,
+            Err(err) => {
+                let message = err
+                    .message()
+                    .unwrap_or_else(|| "Failed to parse".to_string());
+                return Err(error(&src, Some(parse_error_span(&src, &err)), message));
+            }
+        };
+
+        let defs = self.load_decls(path, &src, &name, &decls)?;
+        self.modules.push(Module {
+            path: path.to_path_buf(),
+            name,
+            defs,
+            src,
+        });
+        Ok(self.modules.len() - 1)
+    }
+
+    /// Checks the declarations of the module `name` in the file at `path`, returning its definitions.
+    /// A declaration with an error is left out, and the declarations after it are still checked,
+    /// except after an import that failed, as the declarations after it may use what it imports.
+    fn load_decls<'src>(
+        &mut self,
+        path: &Path,
+        src: &'src str,
+        name: &str,
+        decls: &[Decl<'src>],
+    ) -> Result<Vec<(Name, usize)>, Vec<LoadError>> {
+        let error = |span, message| LoadError {
+            path: path.to_path_buf(),
+            src: src.to_string(),
+            span,
+            message,
+        };
+
+        let mut errors = vec![];
+        let mut names = Namespace::default();
+        let mut defs: Vec<(Name, usize)> = vec![];
+        //The modules imported so far, by name
+        let mut imported: HashMap<Name, usize> = HashMap::new();
+        for decl in decls {
+            match decl {
+                Decl::Import { path: import } => {
+                    let span = span_of(src, import);
+                    let target = path.parent().unwrap_or_else(|| Path::new("")).join(import);
+                    if let Ok(canonical) = target.canonicalize() {
+                        if let Some(i) = self.loading.iter().position(|p| *p == canonical) {
+                            let cycle = self.loading[i..]
+                                .iter()
+                                .chain([&canonical])
+                                .map(|p| p.file_name().unwrap_or_default().to_string_lossy())
+                                .collect::<Vec<_>>();
+                            errors
+                                .push(error(span, format!("Import cycle: {}", cycle.join(" -> "))));
+                            return Err(errors);
+                        }
+                    }
+
+                    let id = match self.load(&target) {
+                        Ok(id) => id,
+                        Err(errs) => {
+                            match &errs[..] {
+                                //Errors about the file as a whole are shown at the import
+                                [err] if err.path == target && err.span.is_none() => {
+                                    errors.push(error(
+                                        span,
+                                        format!("Could not import `{}`: {}", import, err.message),
+                                    ))
+                                }
+                                _ => errors.extend(errs),
+                            }
+                            return Err(errors);
+                        }
+                    };
+                    let module = &self.modules[id];
+                    match imported.insert(module.name.clone(), id) {
+                        Some(other) if other != id => {
+                            errors.push(error(
+                                span,
+                                format!(
+                                    "Another module named `{}` was already imported, from `{}`",
+                                    module.name,
+                                    self.modules[other].path.display()
+                                ),
+                            ));
+                            return Err(errors);
+                        }
+                        _ => {}
+                    }
+                    for (def_name, id) in &module.defs {
+                        names.insert(format!("{}.{}", module.name, def_name).into(), *id);
+                    }
+                }
+                Decl::Def {
+                    name: def_name,
+                    arg_type,
+                    arg_value,
+                }
+                | Decl::PartialDef {
+                    name: def_name,
+                    arg_type,
+                    arg_value,
+                } => {
+                    if defs.iter().any(|(n, _)| &**n == *def_name) {
+                        errors.push(error(
+                            span_of(src, def_name),
+                            format!("`{}` is already defined in this module", def_name),
+                        ));
+                        continue;
+                    }
+                    let checker = Checker::new(&self.globals, &names, src);
+                    let partial = matches!(decl, Decl::PartialDef { .. });
+                    let mut def =
+                        match checker.check_definition(def_name, arg_type, arg_value, partial) {
+                            Ok(def) => def,
+                            Err(err) => {
+                                errors.push(error(err.span, err.message));
+                                continue;
+                            }
+                        };
+                    def.name = format!("{}.{}", name, def_name).into();
+                    let (holes, goals) = (checker.holes(), checker.goals());
+                    self.globals.holes.extend(holes);
+                    for goal in goals {
+                        let message = goal.render(&self.globals);
+                        self.goals.push(error(goal.span, message));
+                    }
+                    let id = self.globals.define(def);
+                    names.insert((*def_name).into(), id);
+                    defs.push(((*def_name).into(), id));
+                }
+                Decl::Universes { names: universes } => {
+                    for universe in universes {
+                        if !names.declare_universe((*universe).into()) {
+                            errors.push(error(
+                                span_of(src, universe),
+                                format!("The universe `{}` is already declared", universe),
+                            ));
+                        }
+                    }
+                }
+                Decl::Data {
+                    name: data_name,
+                    params,
+                    arg_type,
+                    constructors,
+                } => {
+                    let ctor_names = constructors
+                        .iter()
+                        .map(|Constructor::Constructor { name, .. }| *name);
+                    let defined_before = [*data_name]
+                        .into_iter()
+                        .chain(ctor_names)
+                        .find(|def_name| defs.iter().any(|(n, _)| &**n == *def_name));
+                    if let Some(def_name) = defined_before {
+                        errors.push(error(
+                            span_of(src, def_name),
+                            format!("`{}` is already defined in this module", def_name),
+                        ));
+                        continue;
+                    }
+                    let defined = check_data(
+                        &mut self.globals,
+                        &mut names,
+                        src,
+                        data_name,
+                        params,
+                        arg_type,
+                        constructors,
+                        |def_name| format!("{}.{}", name, def_name).into(),
+                    );
+                    match defined {
+                        Ok(defined) => defs.extend(defined),
+                        Err(err) => errors.push(error(err.span, err.message)),
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(defs)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,445 @@
+use crate::autogen::ast::{Constructor, Decl, Level as LevelTerm, Param, Term};
+use crate::lang::core::{Globals, Icit, Name, Tm};
+use crate::lang::level::{Level, LevelVar};
+use typed_arena::Arena;
+
+/// Formats a term as Jonla source, only adding the parentheses that are needed to parse it back.
+pub fn pretty_term(term: &Term) -> String {
+    let mut out = String::new();
+    write_term(term, &mut out);
+    out
+}
+
+/// Formats a declaration of a program as Jonla source
+pub fn pretty_decl(decl: &Decl) -> String {
+    let mut out = String::new();
+    match decl {
+        Decl::Def {
+            name,
+            arg_type,
+            arg_value,
+        }
+        | Decl::PartialDef {
+            name,
+            arg_type,
+            arg_value,
+        } => {
+            if let Decl::PartialDef { .. } = decl {
+                out.push_str("partial ");
+            }
+            out.push_str("def ");
+            out.push_str(name);
+            out.push_str(" : ");
+            write_term_nested(arg_type, &mut out);
+            out.push_str(" = ");
+            //The value ends at the end of the line, so only a `let` chain needs parentheses
+            match &**arg_value {
+                Term::Let { .. } => write_parenthesized(arg_value, &mut out),
+                _ => write_term(arg_value, &mut out),
+            }
+        }
+        Decl::Data {
+            name,
+            params,
+            arg_type,
+            constructors,
+        } => {
+            out.push_str("data ");
+            out.push_str(name);
+            for Param::Param { name, arg_type } in params {
+                out.push_str(" (");
+                out.push_str(name);
+                out.push_str(" : ");
+                write_term(arg_type, &mut out);
+                out.push(')');
+            }
+            out.push_str(" : ");
+            write_term(arg_type, &mut out);
+            out.push_str(" {");
+            for Constructor::Constructor { name, arg_type } in constructors {
+                out.push_str("\n    ");
+                out.push_str(name);
+                out.push_str(" : ");
+                write_term(arg_type, &mut out);
+            }
+            out.push_str("\n}");
+        }
+        Decl::Import { path } => {
+            out.push_str("import ");
+            out.push_str(path);
+        }
+        Decl::Universes { names } => {
+            out.push_str("universe");
+            for name in names {
+                out.push(' ');
+                out.push_str(name);
+            }
+        }
+    }
+    out
+}
+
+/// Corresponds to the `term` rule of the grammar
+fn write_term(term: &Term, out: &mut String) {
+    match term {
+        Term::Let {
+            name,
+            arg_type,
+            arg_value,
+            body,
+        } => {
+            out.push_str("let ");
+            out.push_str(name);
+            out.push_str(" : ");
+            write_term_nested(arg_type, out);
+            out.push_str(" = ");
+            write_term_nested(arg_value, out);
+            out.push('\n');
+            write_term(body, out);
+        }
+        Term::FunConstruct { .. } | Term::ImplicitFunConstruct { .. } => {
+            //Merge directly nested lambdas into a single binder list
+            let mut term = term;
+            let mut separator = "/ ";
+            while let Term::FunConstruct {
+                name,
+                arg_type,
+                body,
+            }
+            | Term::ImplicitFunConstruct {
+                name,
+                arg_type,
+                body,
+            } = term
+            {
+                out.push_str(separator);
+                let implicit = matches!(term, Term::ImplicitFunConstruct { .. });
+                if implicit {
+                    out.push('{');
+                }
+                out.push_str(name);
+                out.push_str(" : ");
+                write_term_nested(arg_type, out);
+                if implicit {
+                    out.push('}');
+                }
+                separator = ", ";
+                term = body;
+            }
+            out.push_str(". ");
+            write_term(term, out);
+        }
+        Term::FunType {
+            name,
+            arg_type,
+            body_type,
+        } if *name == "_" => {
+            write_subterm(arg_type, out);
+            out.push_str(" -> ");
+            write_term(body_type, out);
+        }
+        Term::FunType {
+            name,
+            arg_type,
+            body_type,
+        } => {
+            out.push('(');
+            out.push_str(name);
+            out.push_str(" : ");
+            write_term(arg_type, out);
+            out.push_str(") -> ");
+            write_term(body_type, out);
+        }
+        Term::ImplicitFunType {
+            name,
+            arg_type,
+            body_type,
+        } => {
+            out.push('{');
+            out.push_str(name);
+            out.push_str(" : ");
+            write_term(arg_type, out);
+            out.push_str("} -> ");
+            write_term(body_type, out);
+        }
+        _ => write_subterm(term, out),
+    }
+}
+
+/// A term that is followed by more syntax, so it may not end in a `let` chain or lambda body
+fn write_term_nested(term: &Term, out: &mut String) {
+    match term {
+        Term::Let { .. } | Term::FunConstruct { .. } | Term::ImplicitFunConstruct { .. } => {
+            write_parenthesized(term, out)
+        }
+        _ => write_term(term, out),
+    }
+}
+
+/// Corresponds to the `subterm` rule of the grammar
+fn write_subterm(term: &Term, out: &mut String) {
+    match term {
+        Term::FunDestruct { func, arg } => {
+            write_subterm(func, out);
+            out.push(' ');
+            write_subsubterm(arg, out);
+        }
+        Term::ImplicitFunDestruct { func, arg } => {
+            write_subterm(func, out);
+            out.push_str(" {");
+            write_term(arg, out);
+            out.push('}');
+        }
+        _ => write_subsubterm(term, out),
+    }
+}
+
+/// Corresponds to the `subsubterm` rule of the grammar
+fn write_subsubterm(term: &Term, out: &mut String) {
+    match term {
+        Term::Type { .. } => out.push_str("Type"),
+        Term::Universe { level, .. } => {
+            out.push_str("Type ");
+            write_level_atom(level, out);
+        }
+        Term::Var { name } => out.push_str(name),
+        Term::Hole { name } => {
+            out.push('?');
+            out.push_str(name);
+        }
+        Term::Instantiate { name, levels } => {
+            out.push_str(name);
+            out.push_str(".{");
+            for (i, level) in levels.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_level(level, out);
+            }
+            out.push('}');
+        }
+        _ => write_parenthesized(term, out),
+    }
+}
+
+/// Corresponds to the `level` rule of the grammar
+fn write_level(level: &LevelTerm, out: &mut String) {
+    match level {
+        LevelTerm::Max { left, right } => {
+            out.push_str("max ");
+            write_level_atom(left, out);
+            out.push(' ');
+            write_level_atom(right, out);
+        }
+        LevelTerm::Succ { level, offset } => {
+            write_level_atom(level, out);
+            out.push_str(" + ");
+            out.push_str(offset);
+        }
+        _ => write_level_atom(level, out),
+    }
+}
+
+/// Corresponds to the `level_atom` rule of the grammar
+fn write_level_atom(level: &LevelTerm, out: &mut String) {
+    match level {
+        LevelTerm::Const { value } => out.push_str(value),
+        LevelTerm::LevelVar { name } => out.push_str(name),
+        _ => {
+            out.push('(');
+            write_level(level, out);
+            out.push(')');
+        }
+    }
+}
+
+fn write_parenthesized(term: &Term, out: &mut String) {
+    out.push('(');
+    write_term(term, out);
+    out.push(')');
+}
+
+/// Formats a core term as Jonla source, in a context where the local variables have the given `names`.
+pub fn pretty_core(globals: &Globals, names: &[Name], tm: &Tm) -> String {
+    let arena = Arena::new();
+    let mut scope = names.iter().map(|n| &**n).collect();
+    pretty_term(&core_to_surface(&arena, globals, &mut scope, tm))
+}
+
+/// Converts a core term back to the surface syntax, renaming binders that would shadow a variable in `scope`
+fn core_to_surface<'a>(
+    arena: &'a Arena<String>,
+    globals: &'a Globals,
+    scope: &mut Vec<&'a str>,
+    tm: &'a Tm,
+) -> Term<'a> {
+    let under = |scope: &mut Vec<&'a str>, name: &'a str, body: &'a Tm| {
+        let name = fresh_name(arena, scope, name, body.uses_var(0));
+        scope.push(name);
+        let body = core_to_surface(arena, globals, scope, body);
+        scope.pop();
+        (name, Box::new(body))
+    };
+
+    match tm {
+        Tm::Type(level) if *level == Level::zero() => Term::Type { keyword: "Type" },
+        Tm::Type(level) => Term::Universe {
+            keyword: "Type",
+            level: Box::new(level_to_surface(arena, level)),
+        },
+        Tm::Var(ix) => Term::Var {
+            name: scope[scope.len() - 1 - ix],
+        },
+        //Universe levels of globals are left out, as they can be inferred
+        Tm::Global(id, _) => Term::Var {
+            name: &globals.defs[*id].name,
+        },
+        Tm::Let {
+            name,
+            typ,
+            value,
+            body,
+        } => {
+            let arg_type = Box::new(core_to_surface(arena, globals, scope, typ));
+            let arg_value = Box::new(core_to_surface(arena, globals, scope, value));
+            let (name, body) = under(scope, name, body);
+            Term::Let {
+                name,
+                arg_type,
+                arg_value,
+                body,
+            }
+        }
+        Tm::Pi {
+            name,
+            icit: Icit::Explicit,
+            dom,
+            cod,
+        } => {
+            let arg_type = Box::new(core_to_surface(arena, globals, scope, dom));
+            let (name, body_type) = if cod.uses_var(0) {
+                under(scope, name, cod)
+            } else {
+                //Print a non-dependent function type as an arrow
+                under(scope, "_", cod)
+            };
+            Term::FunType {
+                name,
+                arg_type,
+                body_type,
+            }
+        }
+        Tm::Pi {
+            name,
+            icit: Icit::Implicit,
+            dom,
+            cod,
+        } => {
+            let arg_type = Box::new(core_to_surface(arena, globals, scope, dom));
+            let (name, body_type) = under(scope, name, cod);
+            Term::ImplicitFunType {
+                name,
+                arg_type,
+                body_type,
+            }
+        }
+        Tm::Lam {
+            name,
+            icit,
+            typ,
+            body,
+        } => {
+            let arg_type = Box::new(core_to_surface(arena, globals, scope, typ));
+            let (name, body) = under(scope, name, body);
+            match icit {
+                Icit::Explicit => Term::FunConstruct {
+                    name,
+                    arg_type,
+                    body,
+                },
+                Icit::Implicit => Term::ImplicitFunConstruct {
+                    name,
+                    arg_type,
+                    body,
+                },
+            }
+        }
+        //A hole is shown without the variables in scope where it was written, as every use of it has them
+        Tm::App(f, _, _) if matches!(f.spine(), (Tm::Hole(h), args) if args.len() < globals.holes[*h].context) => {
+            core_to_surface(arena, globals, scope, f)
+        }
+        Tm::App(f, a, icit) => {
+            let func = Box::new(core_to_surface(arena, globals, scope, f));
+            let arg = Box::new(core_to_surface(arena, globals, scope, a));
+            match icit {
+                Icit::Explicit => Term::FunDestruct { func, arg },
+                Icit::Implicit => Term::ImplicitFunDestruct { func, arg },
+            }
+        }
+        Tm::Data(d) => Term::Var {
+            name: &globals.datas[*d].name,
+        },
+        Tm::Ctor(d, c) => Term::Var {
+            name: &globals.datas[*d].ctors[*c].name,
+        },
+        Tm::Elim(d) => Term::Var {
+            name: &globals.datas[*d].elim_name,
+        },
+        //Metavariables that weren't solved are shown by their number, like level metavariables
+        Tm::Meta(m) => Term::Var {
+            name: arena.alloc(format!("?{}", m)),
+        },
+        Tm::Hole(h) => match &*globals.holes[*h].name {
+            "_" => Term::Var { name: "_" },
+            name => Term::Hole { name },
+        },
+    }
+}
+
+/// Converts a universe level back to the surface syntax, as a `max` of its parts
+fn level_to_surface<'a>(arena: &'a Arena<String>, level: &'a Level) -> LevelTerm<'a> {
+    let constant = (level.constant > 0 || level.vars.is_empty()).then(|| LevelTerm::Const {
+        value: arena.alloc(level.constant.to_string()),
+    });
+    let vars = level.vars.iter().map(|(var, offset)| {
+        let name = match var {
+            LevelVar::Param(name) => &**name,
+            LevelVar::Meta(m) => arena.alloc(format!("?{}", m)),
+        };
+        let var = LevelTerm::LevelVar { name };
+        match offset {
+            0 => var,
+            _ => LevelTerm::Succ {
+                level: Box::new(var),
+                offset: arena.alloc(offset.to_string()),
+            },
+        }
+    });
+    vars.chain(constant)
+        .reduce(|left, right| LevelTerm::Max {
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+        .unwrap()
+}
+
+/// Picks a name for a binder that doesn't hide a variable in scope, by adding a number to it
+fn fresh_name<'a>(
+    arena: &'a Arena<String>,
+    scope: &[&'a str],
+    name: &'a str,
+    used: bool,
+) -> &'a str {
+    if name == "_" && !used {
+        return name;
+    }
+    let base = if name == "_" { "x" } else { name };
+    if !scope.contains(&base) {
+        return base;
+    }
+    let candidate = (1..)
+        .map(|i| format!("{}{}", base, i))
+        .find(|candidate| !scope.contains(&candidate.as_str()))
+        .unwrap();
+    arena.alloc(candidate)
+}
@@ -0,0 +1,182 @@
+use crate::lang::core::{Globals, Name, Tm};
+
+/// A recursive call that does not make the definition terminate
+#[derive(Clone, Debug)]
+pub struct BadCall {
+    pub call: Tm,
+    /// The names of the variables in scope at the call
+    pub names: Vec<Name>,
+    /// The parameters that every call before this one made smaller, empty if this call makes none of them smaller
+    pub decreasing: Vec<usize>,
+}
+
+/// Checks that the value of global definition `id` is structurally recursive.
+/// The parameters of the definition are the lambdas its value starts with, and every recursive call has to pass
+/// a strictly smaller argument for the same parameter. A variable is smaller than a parameter if it is a field of it,
+/// bound by a method of an eliminator applied to it, or a field of such a field.
+pub fn check_structural(globals: &Globals, id: usize, value: &Tm) -> Result<(), BadCall> {
+    let mut walker = Walker {
+        globals,
+        id,
+        params: 0,
+        names: vec![],
+        below: vec![],
+        candidates: None,
+    };
+    let mut body = value;
+    while let Tm::Lam { name, body: b, .. } = body {
+        walker.params += 1;
+        walker.names.push(name.clone());
+        walker.below.push(vec![]);
+        body = b;
+    }
+    walker.walk(body)
+}
+
+struct Walker<'g> {
+    globals: &'g Globals,
+    id: usize,
+    params: usize,
+    names: Vec<Name>,
+    /// For every variable in scope, the parameters it is strictly smaller than
+    below: Vec<Vec<usize>>,
+    /// The parameters that every recursive call so far made smaller, `None` before the first call
+    candidates: Option<Vec<usize>>,
+}
+
+impl Walker<'_> {
+    /// The parameters that `tm` is strictly smaller than
+    fn smaller_than(&self, tm: &Tm) -> Vec<usize> {
+        match tm.spine().0 {
+            Tm::Var(ix) => self.below[self.names.len() - 1 - ix].clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Walks `tm` under a binder for a variable that is smaller than the parameters `below`
+    fn under(&mut self, name: &Name, below: Vec<usize>, tm: &Tm) -> Result<(), BadCall> {
+        self.names.push(name.clone());
+        self.below.push(below);
+        let result = self.walk(tm);
+        self.names.pop();
+        self.below.pop();
+        result
+    }
+
+    fn walk(&mut self, tm: &Tm) -> Result<(), BadCall> {
+        match tm {
+            Tm::Type(_)
+            | Tm::Var(_)
+            | Tm::Data(_)
+            | Tm::Ctor(..)
+            | Tm::Elim(_)
+            | Tm::Meta(_)
+            | Tm::Hole(_) => Ok(()),
+            Tm::Global(..) | Tm::App(..) => self.walk_spine(tm),
+            Tm::Let {
+                name,
+                typ,
+                value,
+                body,
+            } => {
+                self.walk(typ)?;
+                self.walk(value)?;
+                let below = self.smaller_than(value);
+                self.under(name, below, body)
+            }
+            Tm::Pi {
+                name,
+                dom: typ,
+                cod: body,
+                ..
+            }
+            | Tm::Lam {
+                name, typ, body, ..
+            } => {
+                self.walk(typ)?;
+                self.under(name, vec![], body)
+            }
+        }
+    }
+
+    fn walk_spine(&mut self, tm: &Tm) -> Result<(), BadCall> {
+        let (head, args) = tm.spine();
+        //Eliminators are referred to by the global definitions for them
+        let head = match head {
+            Tm::Global(id, _) => match self.globals.defs.get(*id) {
+                Some(def) if matches!(def.value, Tm::Elim(_)) => &def.value,
+                _ => head,
+            },
+            _ => head,
+        };
+        match head {
+            Tm::Global(id, _) if *id == self.id => {
+                let smaller: Vec<usize> = (0..args.len().min(self.params))
+                    .filter(|&i| self.smaller_than(args[i]).contains(&i))
+                    .collect();
+                let decreasing: Vec<usize> = match &self.candidates {
+                    Some(candidates) => smaller
+                        .iter()
+                        .copied()
+                        .filter(|i| candidates.contains(i))
+                        .collect(),
+                    None => smaller.clone(),
+                };
+                if decreasing.is_empty() {
+                    return Err(BadCall {
+                        call: tm.clone(),
+                        names: self.names.clone(),
+                        decreasing: if smaller.is_empty() {
+                            vec![]
+                        } else {
+                            self.candidates.clone().unwrap_or_default()
+                        },
+                    });
+                }
+                self.candidates = Some(decreasing);
+            }
+            Tm::Elim(d) if args.len() >= self.globals.datas[*d].elim_arity() => {
+                let data = &self.globals.datas[*d];
+                let target = args[data.elim_arity() - 1];
+                let mut below = self.smaller_than(target);
+                if let Tm::Var(ix) = target {
+                    let lvl = self.names.len() - 1 - ix;
+                    if lvl < self.params && !below.contains(&lvl) {
+                        below.push(lvl);
+                    }
+                }
+                let methods = data.params + 1..data.params + 1 + data.ctors.len();
+                for (i, arg) in args.iter().enumerate() {
+                    if !methods.contains(&i) {
+                        self.walk(arg)?;
+                        continue;
+                    }
+                    //The method binds the fields of the target first, which are smaller than it
+                    let fields = data.ctors[i - methods.start].fields;
+                    let depth = self.names.len();
+                    let mut body = *arg;
+                    while let Tm::Lam {
+                        name, typ, body: b, ..
+                    } = body
+                    {
+                        if self.names.len() - depth == fields {
+                            break;
+                        }
+                        self.walk(typ)?;
+                        self.names.push(name.clone());
+                        self.below.push(below.clone());
+                        body = b;
+                    }
+                    let result = self.walk(body);
+                    self.names.truncate(depth);
+                    self.below.truncate(depth);
+                    result?;
+                }
+                return Ok(());
+            }
+            Tm::Global(..) => {}
+            _ => self.walk(head)?,
+        }
+        args.into_iter().try_for_each(|arg| self.walk(arg))
+    }
+}
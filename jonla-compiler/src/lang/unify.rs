@@ -0,0 +1,422 @@
+use crate::lang::conv::Unfolding;
+use crate::lang::core::{Globals, Icit, Name, Tm};
+use crate::lang::eval::{apply, eval, quote, quote_head, unfold, whnf, Head, Spine, Val};
+use crate::lang::level::{Level, LevelMetas};
+use crate::lang::Span;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// A term the checker has to infer, such as an implicit argument
+#[derive(Clone, Debug)]
+pub struct MetaEntry {
+    /// The type of the metavariable, as a closed chain of `Pi`s over the variables in scope where it was made
+    pub typ: Tm,
+    /// The closed solution, as a term and as a value
+    pub solution: Option<(Tm, Val)>,
+    /// The part of the source the metavariable was made for
+    pub span: Option<Span>,
+    /// The error to report if the metavariable isn't solved
+    pub description: String,
+    /// The name of the hole the metavariable was made for, if it was written in the source.
+    /// A hole doesn't have to be solved, it becomes a hole in `Globals` if it isn't.
+    pub hole: Option<Name>,
+}
+
+/// The metavariables of a definition, for universe levels and for terms
+#[derive(Clone, Debug, Default)]
+pub struct Metas {
+    pub levels: LevelMetas,
+    pub terms: Vec<MetaEntry>,
+    /// The holes that were left unsolved by `freeze_holes`, in the order they are added to `Globals::holes`
+    pub frozen: Vec<usize>,
+    /// When `unify` unfolds global definitions
+    pub unfolding: Unfolding,
+}
+
+impl Metas {
+    pub fn fresh(&mut self, typ: Tm, span: Option<Span>, description: String) -> usize {
+        self.terms.push(MetaEntry {
+            typ,
+            solution: None,
+            span,
+            description,
+            hole: None,
+        });
+        self.terms.len() - 1
+    }
+
+    /// A metavariable for a hole named `name`, see `MetaEntry::hole`
+    pub fn fresh_hole(&mut self, typ: Tm, span: Option<Span>, name: Name) -> usize {
+        let m = self.fresh(typ, span, String::new());
+        self.terms[m].hole = Some(name);
+        m
+    }
+
+    /// The first metavariable that wasn't solved and isn't a hole, if there is one
+    pub fn unsolved(&self) -> Option<&MetaEntry> {
+        self.terms
+            .iter()
+            .find(|entry| entry.solution.is_none() && entry.hole.is_none())
+    }
+
+    /// Solves the holes that weren't solved by unification with new holes in `Globals`,
+    /// which get ids from `base`, the number of holes in `Globals`
+    pub fn freeze_holes(&mut self, base: usize) {
+        for m in 0..self.terms.len() {
+            let entry = &mut self.terms[m];
+            if entry.hole.is_some() && entry.solution.is_none() {
+                let h = base + self.frozen.len();
+                entry.solution = Some((Tm::Hole(h), Val::Neutral(Head::Hole(h), vec![])));
+                self.frozen.push(m);
+            }
+        }
+    }
+
+    /// Brings a value to weak head normal form: solved metavariables and global definitions at its head are unfolded
+    pub fn force(&self, globals: &Globals, val: &Val) -> Val {
+        let val = self.force_metas(globals, val);
+        match unfold(globals, &val) {
+            Some(unfolded) => self.force(globals, &unfolded),
+            None => val,
+        }
+    }
+
+    /// Replaces solved metavariables at the head of a value by their solution, keeping global definitions folded.
+    /// An eliminator that was stuck on a metavariable is reduced if the metavariable is now a constructor.
+    fn force_metas(&self, globals: &Globals, val: &Val) -> Val {
+        match val {
+            Val::Neutral(Head::Meta(m), spine) => match &self.terms[*m].solution {
+                Some((_, solution)) => {
+                    let applied = spine.iter().fold(solution.clone(), |f, (a, icit)| {
+                        apply(globals, f, a.clone(), *icit)
+                    });
+                    self.force_metas(globals, &applied)
+                }
+                None => val.clone(),
+            },
+            Val::Neutral(Head::Elim(d), spine) if spine.len() == globals.datas[*d].elim_arity() => {
+                let (target, icit) = spine.last().unwrap();
+                let target = self.force(globals, target);
+                if !matches!(target, Val::Neutral(Head::Ctor(..), _)) {
+                    return val.clone();
+                }
+                let args = spine[..spine.len() - 1].to_vec();
+                match apply(globals, Val::Neutral(Head::Elim(*d), args), target, *icit) {
+                    stuck @ Val::Neutral(Head::Elim(_), _) => stuck,
+                    reduced => self.force_metas(globals, &reduced),
+                }
+            }
+            _ => val.clone(),
+        }
+    }
+
+    /// Checks whether two values are definitionally equal, in a context with `lvl` variables,
+    /// solving metavariables to make them so. Equations between universe levels are added to `levels`.
+    /// If `cumulative` is set, `a` may also be in a universe that is contained in that of `b`.
+    /// Global definitions are unfolded according to `unfolding`, see `conv` for the same comparison without metavariables.
+    pub fn unify(
+        &mut self,
+        globals: &Globals,
+        lvl: usize,
+        a: &Val,
+        b: &Val,
+        cumulative: bool,
+    ) -> bool {
+        let (a, b) = match self.unfolding {
+            Unfolding::Lazy => (self.force_metas(globals, a), self.force_metas(globals, b)),
+            Unfolding::Eager => (self.force(globals, a), self.force(globals, b)),
+        };
+        match (&a, &b) {
+            (Val::Type(l1), Val::Type(l2)) if cumulative => self.levels.leq(l1, l2),
+            (Val::Type(l1), Val::Type(l2)) => self.levels.equal(l1, l2),
+            //Function types are covariant in their codomain
+            (Val::Pi(_, i1, d1, c1), Val::Pi(_, i2, d2, c2)) if i1 == i2 => {
+                self.unify(globals, lvl, d1, d2, false)
+                    && self.unify(
+                        globals,
+                        lvl + 1,
+                        &c1.apply(globals, Val::var(lvl)),
+                        &c2.apply(globals, Val::var(lvl)),
+                        cumulative,
+                    )
+            }
+            (Val::Lam(_, _, _, c1), Val::Lam(_, _, _, c2)) => self.unify(
+                globals,
+                lvl + 1,
+                &c1.apply(globals, Val::var(lvl)),
+                &c2.apply(globals, Val::var(lvl)),
+                false,
+            ),
+            //A function is equal to a lambda if applying it to a variable gives the body of the lambda
+            (Val::Lam(_, icit, _, body), f @ Val::Neutral(..))
+            | (f @ Val::Neutral(..), Val::Lam(_, icit, _, body)) => self.unify(
+                globals,
+                lvl + 1,
+                &body.apply(globals, Val::var(lvl)),
+                &apply(globals, f.clone(), Val::var(lvl), *icit),
+                false,
+            ),
+            //The same definition is equal if its arguments are, otherwise it is unfolded.
+            //Comparing the arguments may solve metavariables, which is undone if it fails.
+            (Val::Neutral(Head::Global(g1, l1), s1), Val::Neutral(Head::Global(g2, l2), s2))
+                if g1 == g2 && s1.len() == s2.len() =>
+            {
+                let saved = self.clone();
+                let equal = l1
+                    .iter()
+                    .zip(l2.iter())
+                    .all(|(l1, l2)| self.levels.equal(l1, l2))
+                    && s1.iter().zip(s2.iter()).all(|((a1, i1), (a2, i2))| {
+                        i1 == i2 && self.unify(globals, lvl, a1, a2, false)
+                    });
+                if !equal {
+                    *self = saved;
+                    return self.unify_unfolded(globals, lvl, &a, &b, cumulative);
+                }
+                true
+            }
+            (Val::Neutral(h1, s1), Val::Neutral(h2, s2)) if h1 == h2 => {
+                s1.len() == s2.len()
+                    && s1.iter().zip(s2.iter()).all(|((a1, i1), (a2, i2))| {
+                        i1 == i2 && self.unify(globals, lvl, a1, a2, false)
+                    })
+            }
+            (Val::Neutral(Head::Meta(m), spine), other)
+            | (other, Val::Neutral(Head::Meta(m), spine)) => {
+                self.solve(globals, lvl, *m, spine, other)
+            }
+            _ => self.unify_unfolded(globals, lvl, &a, &b, cumulative),
+        }
+    }
+
+    /// Unifies two values after unfolding the definitions at their heads, fails if neither can be unfolded
+    fn unify_unfolded(
+        &mut self,
+        globals: &Globals,
+        lvl: usize,
+        a: &Val,
+        b: &Val,
+        cumulative: bool,
+    ) -> bool {
+        match (unfold(globals, a), unfold(globals, b)) {
+            (None, None) => false,
+            (a1, b1) => self.unify(
+                globals,
+                lvl,
+                &a1.unwrap_or_else(|| a.clone()),
+                &b1.unwrap_or_else(|| b.clone()),
+                cumulative,
+            ),
+        }
+    }
+
+    /// Solves `?m spine = rhs`, if the spine consists of variables.
+    /// The solution is then `rhs` with those variables abstracted, which is the only one.
+    /// A variable that occurs more than once in the spine can't be used by `rhs`, as it's unclear which one it is.
+    fn solve(&mut self, globals: &Globals, lvl: usize, m: usize, spine: &Spine, rhs: &Val) -> bool {
+        //The level of every variable of the spine, in the context of the solution
+        let mut renaming = HashMap::new();
+        let mut repeated = HashSet::new();
+        for (i, (arg, _)) in spine.iter().enumerate() {
+            match self.force(globals, arg) {
+                Val::Neutral(Head::Var(var), args) if args.is_empty() => {
+                    if renaming.insert(var, i).is_some() {
+                        repeated.insert(var);
+                    }
+                }
+                _ => return false,
+            }
+        }
+        for var in repeated {
+            renaming.remove(&var);
+        }
+        let Some(body) = self.rename(globals, m, &renaming, spine.len(), lvl, rhs) else {
+            return false;
+        };
+
+        //The types of the variables come from the type of the metavariable
+        let mut typ = eval(globals, &vec![], &self.terms[m].typ);
+        let mut binders: Vec<(Name, Icit, Tm)> = vec![];
+        for (i, (_, icit)) in spine.iter().enumerate() {
+            let Val::Pi(name, _, dom, cod) = self.force(globals, &typ) else {
+                return false;
+            };
+            binders.push((name, *icit, quote(globals, i, &dom)));
+            typ = cod.apply(globals, Val::var(i));
+        }
+        //A metavariable in a universe can only stand for the types in that universe
+        if let (Val::Type(level), Some(rhs_level)) =
+            (self.force(globals, &typ), universe_of(globals, lvl, rhs))
+        {
+            if !self.levels.leq(&rhs_level, &level) {
+                return false;
+            }
+        }
+        let solution = binders
+            .into_iter()
+            .rev()
+            .fold(body, |body, (name, icit, typ)| Tm::Lam {
+                name,
+                icit,
+                typ: Rc::new(typ),
+                body: Rc::new(body),
+            });
+        let value = eval(globals, &vec![], &solution);
+        self.terms[m].solution = Some((solution, value));
+        true
+    }
+
+    /// Quotes `val` from a context with `cod` variables into one with `dom` variables, where the variable with level
+    /// `l` has level `renaming[l]`. Fails if `val` uses a variable that isn't renamed, or the metavariable `m` itself.
+    fn rename(
+        &self,
+        globals: &Globals,
+        m: usize,
+        renaming: &HashMap<usize, usize>,
+        dom: usize,
+        cod: usize,
+        val: &Val,
+    ) -> Option<Tm> {
+        //Under a binder, the new variable is renamed to the new variable
+        let under = |name: &Name| {
+            let mut renaming = renaming.clone();
+            renaming.insert(cod, dom);
+            (name.clone(), renaming)
+        };
+        match self.force_metas(globals, val) {
+            Val::Type(level) => Some(Tm::Type(level)),
+            Val::Neutral(head, spine) => {
+                let head_tm = match &head {
+                    Head::Meta(other) if *other == m => return None,
+                    Head::Var(var) => Tm::Var(dom - 1 - renaming.get(var)?),
+                    head => quote_head(dom, head),
+                };
+                let renamed = spine.iter().try_fold(head_tm, |f, (a, icit)| {
+                    let a = self.rename(globals, m, renaming, dom, cod, a)?;
+                    Some(Tm::App(Rc::new(f), Rc::new(a), *icit))
+                });
+                match renamed {
+                    //A definition is kept folded in the solution, unless its arguments can't be renamed
+                    None => {
+                        let unfolded = unfold(globals, &Val::Neutral(head, spine))?;
+                        self.rename(globals, m, renaming, dom, cod, &unfolded)
+                    }
+                    renamed => renamed,
+                }
+            }
+            Val::Pi(name, icit, typ, body) => {
+                let typ = self.rename(globals, m, renaming, dom, cod, &typ)?;
+                let (name, inner) = under(&name);
+                let body = body.apply(globals, Val::var(cod));
+                let body = self.rename(globals, m, &inner, dom + 1, cod + 1, &body)?;
+                Some(Tm::Pi {
+                    name,
+                    icit,
+                    dom: Rc::new(typ),
+                    cod: Rc::new(body),
+                })
+            }
+            Val::Lam(name, icit, typ, body) => {
+                let typ = self.rename(globals, m, renaming, dom, cod, &typ)?;
+                let (name, inner) = under(&name);
+                let body = body.apply(globals, Val::var(cod));
+                let body = self.rename(globals, m, &inner, dom + 1, cod + 1, &body)?;
+                Some(Tm::Lam {
+                    name,
+                    icit,
+                    typ: Rc::new(typ),
+                    body: Rc::new(body),
+                })
+            }
+        }
+    }
+
+    /// Replaces the solved metavariables in a term by their solution, for universe levels after `LevelMetas::solve`.
+    /// A solution that is applied to arguments is reduced, so no redexes are introduced.
+    pub fn zonk(&self, tm: &Tm) -> Tm {
+        let go = |tm: &Rc<Tm>| Rc::new(self.zonk(tm));
+        match tm {
+            Tm::Type(level) => Tm::Type(self.levels.zonk(level)),
+            Tm::Global(id, levels) => {
+                Tm::Global(*id, levels.iter().map(|l| self.levels.zonk(l)).collect())
+            }
+            Tm::Var(_) | Tm::Data(_) | Tm::Ctor(..) | Tm::Elim(_) | Tm::Hole(_) => tm.clone(),
+            Tm::Meta(m) => match &self.terms[*m].solution {
+                Some((solution, _)) => self.zonk(solution),
+                None => tm.clone(),
+            },
+            Tm::App(..) => {
+                let mut args = vec![];
+                let mut head = tm;
+                while let Tm::App(f, a, icit) = head {
+                    args.push((self.zonk(a), *icit));
+                    head = f;
+                }
+                args.reverse();
+
+                //Substitute the arguments for the lambdas of a solution
+                let mut head = self.zonk(head);
+                if matches!(tm.spine().0, Tm::Meta(_)) {
+                    let mut consumed = vec![];
+                    while let (Tm::Lam { body, .. }, Some((a, _))) =
+                        (&head, args.get(consumed.len()))
+                    {
+                        consumed.push(a.clone());
+                        head = (**body).clone();
+                    }
+                    head = head.instantiate_vars(&consumed);
+                    args.drain(..consumed.len());
+                }
+                args.into_iter()
+                    .fold(head, |f, (a, icit)| Tm::App(Rc::new(f), Rc::new(a), icit))
+            }
+            Tm::Let {
+                name,
+                typ,
+                value,
+                body,
+            } => Tm::Let {
+                name: name.clone(),
+                typ: go(typ),
+                value: go(value),
+                body: go(body),
+            },
+            Tm::Pi {
+                name,
+                icit,
+                dom,
+                cod,
+            } => Tm::Pi {
+                name: name.clone(),
+                icit: *icit,
+                dom: go(dom),
+                cod: go(cod),
+            },
+            Tm::Lam {
+                name,
+                icit,
+                typ,
+                body,
+            } => Tm::Lam {
+                name: name.clone(),
+                icit: *icit,
+                typ: go(typ),
+                body: go(body),
+            },
+        }
+    }
+}
+
+/// The universe that a type is in, if it is built from universes and function types only.
+/// Other types can't be checked without the types of the variables, so they give `None`.
+fn universe_of(globals: &Globals, lvl: usize, val: &Val) -> Option<Level> {
+    match &whnf(globals, val) {
+        Val::Type(level) => Some(level.succ()),
+        Val::Pi(_, _, dom, cod) => {
+            let dom = universe_of(globals, lvl, dom)?;
+            let cod = universe_of(globals, lvl + 1, &cod.apply(globals, Val::var(lvl)))?;
+            Some(dom.max(&cod))
+        }
+        Val::Neutral(..) | Val::Lam(..) => None,
+    }
+}
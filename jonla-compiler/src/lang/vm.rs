@@ -0,0 +1,174 @@
+use crate::lang::bytecode::{Instr, Program};
+use crate::lang::core::Globals;
+use std::rc::Rc;
+
+/// A value computed by the virtual machine
+#[derive(Clone, Debug)]
+pub enum Value {
+    Closure(Rc<Closure>),
+    /// A constructor, by the index of its data type and its own index, applied to its fields
+    Con(usize, usize, Rc<[Value]>),
+    /// A type or a proof, which has no value at run time
+    Erased,
+}
+
+#[derive(Clone, Debug)]
+pub struct Closure {
+    pub chunk: usize,
+    pub captures: Vec<Value>,
+    /// Whether the function gets itself as a local variable, after its captures
+    pub rec: bool,
+}
+
+/// The program reached a hole, by its index in `Globals::holes`, which has no value
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VmError {
+    pub hole: usize,
+}
+
+impl VmError {
+    pub fn render(&self, globals: &Globals) -> String {
+        match &*globals.holes[self.hole].name {
+            "_" => "Reached a hole, which has no value".to_string(),
+            name => format!("Reached the hole `?{}`, which has no value", name),
+        }
+    }
+}
+
+impl Value {
+    /// Shows a value like a term, with qualified constructor names.
+    /// The parameters of data types are erased, so they are left out.
+    pub fn render(&self, globals: &Globals) -> String {
+        match self {
+            Value::Closure(_) => "<function>".to_string(),
+            Value::Erased => "<erased>".to_string(),
+            Value::Con(d, c, fields) => {
+                let mut out = globals.datas[*d].ctors[*c].name.to_string();
+                for field in fields.iter() {
+                    match field {
+                        Value::Con(_, _, fields) if !fields.is_empty() => {
+                            out.push_str(&format!(" ({})", field.render(globals)))
+                        }
+                        _ => out.push_str(&format!(" {}", field.render(globals))),
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+/// A call that is running, with its own local variables
+struct Frame {
+    chunk: usize,
+    pc: usize,
+    locals: Vec<Value>,
+}
+
+/// Runs compiled programs, see `bytecode::compile`.
+/// Calls are kept on a stack of frames instead of the native stack, so deep recursion does not overflow it.
+pub struct Vm<'p> {
+    program: &'p Program,
+    /// The values of global definitions that were computed so far
+    globals: Vec<Option<Value>>,
+}
+
+impl<'p> Vm<'p> {
+    pub fn new(program: &'p Program) -> Self {
+        Vm {
+            program,
+            globals: vec![None; program.globals.len()],
+        }
+    }
+
+    /// The value of global definition `id`, which is computed when it is first needed
+    pub fn global(&mut self, id: usize) -> Result<Value, VmError> {
+        if let Some(value) = &self.globals[id] {
+            return Ok(value.clone());
+        }
+        let value = self.run(self.program.globals[id], vec![])?;
+        self.globals[id] = Some(value.clone());
+        Ok(value)
+    }
+
+    /// The frame for calling `f` with `arg`
+    fn call(&self, f: Value, arg: Value) -> Frame {
+        let Value::Closure(closure) = f else {
+            unreachable!("Applied a value that is not a function")
+        };
+        let mut locals = closure.captures.clone();
+        if closure.rec {
+            locals.push(Value::Closure(closure.clone()));
+        }
+        locals.push(arg);
+        Frame {
+            chunk: closure.chunk,
+            pc: 0,
+            locals,
+        }
+    }
+
+    fn run(&mut self, chunk: usize, locals: Vec<Value>) -> Result<Value, VmError> {
+        let program = self.program;
+        let mut stack = vec![];
+        let mut frames = vec![];
+        let mut frame = Frame {
+            chunk,
+            pc: 0,
+            locals,
+        };
+        loop {
+            let code = &program.chunks[frame.chunk].code;
+            let instr = &code[frame.pc];
+            frame.pc += 1;
+            match instr {
+                Instr::Local(slot) => stack.push(frame.locals[*slot].clone()),
+                Instr::Global(id) => stack.push(self.global(*id)?),
+                Instr::Closure {
+                    chunk,
+                    captures,
+                    rec,
+                } => stack.push(Value::Closure(Rc::new(Closure {
+                    chunk: *chunk,
+                    captures: captures.iter().map(|&s| frame.locals[s].clone()).collect(),
+                    rec: *rec,
+                }))),
+                Instr::Apply => {
+                    let arg = stack.pop().unwrap();
+                    let f = stack.pop().unwrap();
+                    //A type applied to something is still a type
+                    if let Value::Erased = f {
+                        stack.push(Value::Erased);
+                        continue;
+                    }
+                    let callee = self.call(f, arg);
+                    //A call in tail position replaces the frame of the caller
+                    if code[frame.pc] != Instr::Return {
+                        frames.push(frame);
+                    }
+                    frame = callee;
+                }
+                Instr::Bind => frame.locals.push(stack.pop().unwrap()),
+                Instr::Unbind(n) => frame.locals.truncate(frame.locals.len() - n),
+                Instr::Con { data, ctor, fields } => {
+                    let fields: Rc<[Value]> = stack.split_off(stack.len() - fields).into();
+                    stack.push(Value::Con(*data, *ctor, fields));
+                }
+                Instr::Match(arms) => {
+                    let Some(Value::Con(_, c, fields)) = stack.pop() else {
+                        unreachable!("Matched a value that is not constructed")
+                    };
+                    frame.locals.extend(fields.iter().cloned());
+                    frame.pc = arms[c];
+                }
+                Instr::Jump(pc) => frame.pc = *pc,
+                Instr::Hole(hole) => return Err(VmError { hole: *hole }),
+                Instr::Erased => stack.push(Value::Erased),
+                Instr::Return => match frames.pop() {
+                    Some(caller) => frame = caller,
+                    None => return Ok(stack.pop().unwrap()),
+                },
+            }
+        }
+    }
+}
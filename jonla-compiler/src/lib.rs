@@ -0,0 +1,8 @@
+#[allow(unused)]
+#[rustfmt::skip]
+pub mod autogen;
+
+pub mod driver;
+pub mod lang;
+pub mod lsp;
+pub mod repl;
@@ -0,0 +1,271 @@
+use crate::autogen::ast::{Constructor, Decl, Param, Term};
+use crate::autogen::parse::parse_program;
+use crate::lang::module::Loader;
+use crate::lang::pretty::pretty_term;
+use crate::lang::{parse_error_span, span_of, Span};
+use std::path::Path;
+
+/// Everything the language server knows about a single version of a document.
+#[derive(Default, Debug)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    /// Every named binder that occurs in the source
+    pub binders: Vec<Binder>,
+    /// Every variable, with the index of the binder it refers to
+    pub references: Vec<(Span, Option<usize>)>,
+    /// Indices of the binders of the declarations, in the order they are declared
+    pub symbols: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct Binder {
+    pub name: String,
+    pub span: Span,
+    /// The declared type of the binder, formatted as source
+    pub typ: String,
+}
+
+impl Analysis {
+    /// The binder at `offset`, either because its name is there or because a reference to it is
+    pub fn binder_at(&self, offset: usize) -> Option<&Binder> {
+        let contains = |(start, end): Span| start <= offset && offset <= end;
+        if let Some(binder) = self.binders.iter().find(|b| contains(b.span)) {
+            return Some(binder);
+        }
+        self.references
+            .iter()
+            .find(|(span, _)| contains(*span))
+            .and_then(|(_, binder)| binder.map(|b| &self.binders[b]))
+    }
+}
+
+/// Analyzes `text` as the contents of the file at `path`.
+/// The program is checked like `jonla check` would, loading its imports relative to `path`.
+pub fn analyze(path: &Path, text: &str) -> Analysis {
+    let mut analysis = Analysis::default();
+    let decls = match parse_program(text).inner {
+        Ok(ok) => ok.result,
+        Err(err) => {
+            analysis.diagnostics.push(Diagnostic {
+                span: parse_error_span(text, &err),
+                message: err
+                    .message()
+                    .unwrap_or_else(|| "Failed to parse".to_string()),
+            });
+            return analysis;
+        }
+    };
+    let mut resolver = Resolver {
+        text,
+        analysis: &mut analysis,
+        scope: vec![],
+    };
+    for decl in &decls {
+        resolver.resolve_decl(decl);
+    }
+
+    let errors = Loader::default()
+        .load_source(path, text.to_string())
+        .err()
+        .unwrap_or_default();
+    for err in errors {
+        //Errors in imported files, and errors about the file as a whole, are shown at its start
+        let diagnostic = if err.path == path {
+            Diagnostic {
+                span: err.span.unwrap_or((0, 0)),
+                message: err.message,
+            }
+        } else {
+            Diagnostic {
+                span: (0, 0),
+                message: format!("In `{}`: {}", err.path.display(), err.message),
+            }
+        };
+        analysis.diagnostics.push(diagnostic);
+    }
+    analysis
+}
+
+struct Resolver<'a, 'src> {
+    text: &'src str,
+    analysis: &'a mut Analysis,
+    /// The names in scope, with the index of their binder if it occurs in the source
+    scope: Vec<(&'src str, Option<usize>)>,
+}
+
+impl<'a, 'src> Resolver<'a, 'src> {
+    /// Resolves a declaration, and keeps the names it defines in scope for the declarations after it
+    fn resolve_decl(&mut self, decl: &Decl<'src>) {
+        match decl {
+            Decl::Def {
+                name,
+                arg_type,
+                arg_value,
+            }
+            | Decl::PartialDef {
+                name,
+                arg_type,
+                arg_value,
+            } => {
+                self.resolve(arg_type);
+                //A definition can refer to itself
+                self.declare(name, pretty_term(arg_type));
+                self.resolve(arg_value);
+            }
+            Decl::Data {
+                name,
+                params,
+                arg_type,
+                constructors,
+            } => {
+                //The parameters are arguments of the data type and of its constructors
+                let params_type = params
+                    .iter()
+                    .map(|Param::Param { name, arg_type }| {
+                        format!("({} : {}) -> ", name, pretty_term(arg_type))
+                    })
+                    .collect::<String>();
+                self.declare(name, format!("{}{}", params_type, pretty_term(arg_type)));
+
+                let depth = self.scope.len();
+                for Param::Param { name, arg_type } in params {
+                    self.resolve(arg_type);
+                    let binder = self.bind(name, arg_type);
+                    self.scope.push((name, binder));
+                }
+                self.resolve(arg_type);
+                for Constructor::Constructor { arg_type, .. } in constructors {
+                    self.resolve(arg_type);
+                }
+                self.scope.truncate(depth);
+
+                for Constructor::Constructor { name, arg_type } in constructors {
+                    self.declare(name, format!("{}{}", params_type, pretty_term(arg_type)));
+                }
+            }
+            Decl::Import { .. } | Decl::Universes { .. } => {}
+        }
+    }
+
+    /// Brings a declared name into scope, as one of the symbols of the document
+    fn declare(&mut self, name: &'src str, typ: String) {
+        let binder = span_of(self.text, name).map(|span| {
+            self.analysis.binders.push(Binder {
+                name: name.to_string(),
+                span,
+                typ,
+            });
+            self.analysis.symbols.push(self.analysis.binders.len() - 1);
+            self.analysis.binders.len() - 1
+        });
+        self.scope.push((name, binder));
+    }
+
+    fn resolve(&mut self, term: &Term<'src>) {
+        match term {
+            Term::Type { .. } | Term::Universe { .. } | Term::Hole { .. } => {}
+            Term::Var { name } | Term::Instantiate { name, .. } => {
+                let binder = self
+                    .scope
+                    .iter()
+                    .rev()
+                    .find(|(n, _)| n == name)
+                    .and_then(|(_, b)| *b);
+                if let Some(span) = span_of(self.text, name) {
+                    self.analysis.references.push((span, binder));
+                }
+            }
+            Term::Let {
+                name,
+                arg_type,
+                arg_value,
+                body,
+            } => {
+                self.resolve(arg_type);
+                self.resolve(arg_value);
+                self.resolve_under(name, arg_type, body);
+            }
+            Term::FunType {
+                name,
+                arg_type,
+                body_type: body,
+            }
+            | Term::ImplicitFunType {
+                name,
+                arg_type,
+                body_type: body,
+            }
+            | Term::FunConstruct {
+                name,
+                arg_type,
+                body,
+            }
+            | Term::ImplicitFunConstruct {
+                name,
+                arg_type,
+                body,
+            } => {
+                self.resolve(arg_type);
+                self.resolve_under(name, arg_type, body);
+            }
+            Term::FunDestruct { func, arg } | Term::ImplicitFunDestruct { func, arg } => {
+                self.resolve(func);
+                self.resolve(arg);
+            }
+        }
+    }
+
+    /// Resolves `body` with `name` in scope
+    fn resolve_under(&mut self, name: &'src str, typ: &Term<'src>, body: &Term<'src>) {
+        let binder = self.bind(name, typ);
+        self.scope.push((name, binder));
+        self.resolve(body);
+        self.scope.pop();
+    }
+
+    /// Adds a binder of a local variable, if its name occurs in the source
+    fn bind(&mut self, name: &'src str, typ: &Term<'src>) -> Option<usize> {
+        let span = span_of(self.text, name)?;
+        self.analysis.binders.push(Binder {
+            name: name.to_string(),
+            span,
+            typ: pretty_term(typ),
+        });
+        Some(self.analysis.binders.len() - 1)
+    }
+}
+
+/// Converts a byte offset to an LSP position, which counts UTF-16 code units
+pub fn offset_to_position(text: &str, offset: usize) -> (u32, u32) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let character = before[line_start..].encode_utf16().count();
+    (line as u32, character as u32)
+}
+
+/// Converts an LSP position to a byte offset, clamping it to the document
+pub fn position_to_offset(text: &str, line: u32, character: u32) -> usize {
+    let mut line_start = 0;
+    for _ in 0..line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character as usize || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
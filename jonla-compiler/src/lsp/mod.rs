@@ -0,0 +1,243 @@
+use crate::lang::Span;
+use crate::lsp::analysis::{analyze, offset_to_position, position_to_offset, Analysis};
+use crate::lsp::transport::{read_message, write_message};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+pub mod analysis;
+pub mod transport;
+
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// An open document, together with the analysis of its current contents
+struct Document {
+    text: String,
+    analysis: Analysis,
+}
+
+/// A language server for Jonla, communicating with a single client.
+pub struct Server<W: Write> {
+    output: W,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+/// Runs the language server until the client sends `exit` or closes the input
+pub fn run(mut input: impl BufRead, output: impl Write) -> io::Result<()> {
+    let mut server = Server::new(output);
+    while let Some(message) = read_message(&mut input)? {
+        if !server.handle(message)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(output: W) -> Self {
+        Server {
+            output,
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Handles a single request or notification.
+    /// Returns false if the server should stop.
+    pub fn handle(&mut self, message: Value) -> io::Result<bool> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        let Some(id) = message.get("id").cloned() else {
+            return self.handle_notification(method, params);
+        };
+        let result = match method {
+            _ if self.shutdown => Err((INVALID_REQUEST, "Server is shutting down".to_string())),
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "jonla-lsp" },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        write_message(&mut self.output, &response)?;
+        Ok(true)
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> io::Result<bool> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.update(uri, text.to_string())?;
+            }
+            "textDocument/didChange" => {
+                //Documents are synchronized in full, so the last change contains the entire text
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|c| c.last()?["text"].as_str()) {
+                    self.update(uri, text.to_string())?;
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.publish_diagnostics(uri, vec![])?;
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn update(&mut self, uri: &str, text: String) -> io::Result<()> {
+        let analysis = analyze(&uri_to_path(uri), &text);
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|d| {
+                json!({
+                    "range": range(&text, d.span),
+                    "severity": 1,
+                    "source": "jonla",
+                    "message": d.message,
+                })
+            })
+            .collect();
+        self.documents
+            .insert(uri.to_string(), Document { text, analysis });
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) -> io::Result<()> {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        });
+        write_message(&mut self.output, &notification)
+    }
+
+    /// Finds the document and byte offset that `params` point to
+    fn locate(&self, params: &Value) -> Result<(&Document, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document: {}", uri)))?;
+        let line = params["position"]["line"].as_u64().unwrap_or_default();
+        let character = params["position"]["character"].as_u64().unwrap_or_default();
+        let offset = position_to_offset(&document.text, line as u32, character as u32);
+        Ok((document, offset))
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, offset) = self.locate(params)?;
+        Ok(match document.analysis.binder_at(offset) {
+            Some(binder) => json!({
+                "contents": {
+                    "kind": "markdown",
+                    "value": format!("```jonla\n{} : {}\n```", binder.name, binder.typ),
+                },
+            }),
+            None => Value::Null,
+        })
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, offset) = self.locate(params)?;
+        Ok(match document.analysis.binder_at(offset) {
+            Some(binder) => json!({
+                "uri": params["textDocument"]["uri"],
+                "range": range(&document.text, binder.span),
+            }),
+            None => Value::Null,
+        })
+    }
+
+    fn document_symbols(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document: {}", uri)))?;
+        let symbols = document
+            .analysis
+            .symbols
+            .iter()
+            .map(|&i| {
+                let binder = &document.analysis.binders[i];
+                let range = range(&document.text, binder.span);
+                json!({
+                    "name": binder.name,
+                    "detail": binder.typ,
+                    // SymbolKind.Variable
+                    "kind": 13,
+                    "range": range,
+                    "selectionRange": range,
+                })
+            })
+            .collect();
+        Ok(Value::Array(symbols))
+    }
+}
+
+/// The path of the file a document is about, which its imports are relative to.
+/// Documents that aren't files are treated as a file of the same name in the working directory.
+fn uri_to_path(uri: &str) -> PathBuf {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return PathBuf::from(uri.rsplit([':', '/']).next().unwrap_or_default());
+    };
+    //Characters that can't occur in a URI are percent-encoded
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = (byte == b'%')
+            .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn range(text: &str, (start, end): Span) -> Value {
+    let (start_line, start_character) = offset_to_position(text, start);
+    let (end_line, end_character) = offset_to_position(text, end);
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character },
+    })
+}
@@ -1,18 +1,13 @@
-#[allow(unused)]
-#[rustfmt::skip]
-mod autogen;
-
-use crate::autogen::parse::parse_term;
-
 fn main() {
-    let input = include_str!("../resources/program.jl");
-    let result = parse_term(input);
-    match result.inner {
-        Ok(ok) => {
-            println!("{:?}", ok);
-        }
-        Err(err) => {
-            err.display(input);
-        }
-    }
+    let args = std::env::args().skip(1);
+    let exit_code = jonla_compiler::driver::run(
+        args,
+        &mut std::io::stdout().lock(),
+        &mut std::io::stderr().lock(),
+    )
+    .unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        jonla_compiler::driver::EXIT_USAGE
+    });
+    std::process::exit(exit_code);
 }
@@ -0,0 +1,238 @@
+use crate::autogen::ast::Entry;
+use crate::autogen::parse::{parse_entries, parse_term};
+use crate::lang::check::{check_entry, Checked, TypeError};
+use crate::lang::core::Namespace;
+use crate::lang::module::Loader;
+use crate::lang::pretty::pretty_core;
+use jonla_macros::parser::parser_result::ParseResult;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const HELP: &str = "\
+<term>              Show the normal form and type of a term, and the goals of its holes `?name` and `_`
+let x : T = v       Add a definition
+:type <term>        Show the type of a term
+:normalize <term>   Show the normal form of a term
+:load <file>        Add the definitions in a file, with and without its module name
+:reload             Forget all definitions and load the loaded files again
+:quit               Exit the REPL
+";
+
+/// The state of the REPL, which is kept between entries
+#[derive(Default)]
+pub struct Repl {
+    loader: Loader,
+    /// The definitions that can be used in entries
+    names: Namespace,
+    loaded: Vec<PathBuf>,
+}
+
+/// Reads entries from `input` until it is closed or `:quit` is entered.
+/// An entry that is incomplete, such as a lambda without a body, continues on the next line.
+pub fn run(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut repl = Repl::default();
+    let mut lines = input.lines();
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let mut entry = line?;
+        while is_incomplete(&entry) {
+            write!(output, ".. ")?;
+            output.flush()?;
+            match lines.next() {
+                //An empty line forces the entry to end, so the error is shown
+                Some(line) if !line.as_ref().is_ok_and(|l| l.trim().is_empty()) => {
+                    entry = join_lines(&entry, &line?);
+                }
+                _ => break,
+            }
+        }
+        if !repl.handle(&entry, &mut output)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Splits an entry into its command (if any) and the rest of the entry
+fn split_command(entry: &str) -> (Option<&str>, &str) {
+    let entry = entry.trim();
+    match entry.strip_prefix(':') {
+        Some(command) => {
+            let (command, rest) = command
+                .split_once(char::is_whitespace)
+                .unwrap_or((command, ""));
+            (Some(command), rest.trim())
+        }
+        None => (None, entry),
+    }
+}
+
+/// The position of the parse error in the entry, or `None` if it parses
+fn error_pos(entry: &str) -> Option<usize> {
+    fn pos<T: Clone>(result: ParseResult<T>) -> Option<usize> {
+        result.inner.err().map(|err| err.pos)
+    }
+    match split_command(entry) {
+        (None, "") => None,
+        (None, rest) => pos(parse_entries(rest)),
+        (Some("t" | "type" | "n" | "normalize"), rest) if !rest.is_empty() => pos(parse_term(rest)),
+        (Some(_), _) => None,
+    }
+}
+
+/// Whether the parser ran out of input, so the entry may continue on the next line
+fn is_incomplete(entry: &str) -> bool {
+    let rest = split_command(entry).1;
+    error_pos(entry).is_some_and(|pos| pos >= rest.len())
+}
+
+/// Adds a continuation line to an entry.
+/// Only a `let` may be followed by a newline, so other terms are continued on the same line.
+fn join_lines(entry: &str, line: &str) -> String {
+    let with_newline = format!("{}\n{}", entry, line);
+    let with_space = format!("{} {}", entry, line);
+    match (error_pos(&with_newline), error_pos(&with_space)) {
+        (Some(newline), Some(space)) if space > newline => with_space,
+        (Some(_), None) => with_space,
+        _ => with_newline,
+    }
+}
+
+impl Repl {
+    /// Handles a single entry, returns false if the REPL should stop
+    pub fn handle(&mut self, entry: &str, out: &mut impl Write) -> io::Result<bool> {
+        match split_command(entry) {
+            (None, "") => {}
+            (None, rest) => {
+                if let Err(err) = self.run_entries(rest, out) {
+                    write!(out, "{}", err)?;
+                }
+            }
+            (Some("q" | "quit"), _) => return Ok(false),
+            (Some("h" | "help"), _) => write!(out, "{}", HELP)?,
+            (Some("t" | "type"), rest) => match self.infer(rest) {
+                Ok((_, typ)) => writeln!(out, "{}", typ)?,
+                Err(err) => write!(out, "{}", err)?,
+            },
+            (Some("n" | "normalize"), rest) => match self.infer(rest) {
+                Ok((value, _)) => writeln!(out, "{}", value)?,
+                Err(err) => write!(out, "{}", err)?,
+            },
+            (Some("l" | "load"), rest) => {
+                let path = PathBuf::from(rest);
+                if let Err(err) = self.load(&path, out) {
+                    write!(out, "{}", err)?;
+                } else if !self.loaded.contains(&path) {
+                    self.loaded.push(path);
+                }
+            }
+            (Some("r" | "reload"), _) => {
+                self.loader = Loader::default();
+                self.names = Namespace::default();
+                for path in self.loaded.clone() {
+                    if let Err(err) = self.load(&path, out) {
+                        write!(out, "{}", err)?;
+                    }
+                }
+            }
+            (Some(command), _) => writeln!(out, "Unknown command `:{}`, see `:help`", command)?,
+        }
+        Ok(true)
+    }
+
+    fn load(&mut self, path: &Path, out: &mut impl Write) -> Result<(), String> {
+        let known_goals = self.loader.goals.len();
+        let loaded = self.loader.load(path);
+        for goal in &self.loader.goals[known_goals..] {
+            writeln!(out, "{}", goal.render()).map_err(|err| err.to_string())?;
+        }
+        let id = loaded.map_err(|errs| {
+            errs.iter()
+                .map(|err| err.render() + "\n")
+                .collect::<String>()
+        })?;
+        let module = &self.loader.modules[id];
+        let globals = &self.loader.globals;
+        for (name, id) in &module.defs {
+            self.names.insert(name.clone(), *id);
+            self.names
+                .insert(format!("{}.{}", module.name, name).into(), *id);
+            let typ = pretty_core(globals, &[], &globals.defs[*id].typ);
+            writeln!(out, "{} : {}", name, typ).map_err(|err| err.to_string())?;
+        }
+        writeln!(out, "Loaded {}", path.display()).map_err(|err| err.to_string())
+    }
+
+    /// Runs all entries in `src`, a `let` followed by a term runs as a declaration followed by an expression
+    fn run_entries(&mut self, src: &str, out: &mut impl Write) -> Result<(), String> {
+        let entries = match parse_entries(src).inner {
+            Ok(ok) => ok.result,
+            Err(err) => return Err(err.render(src)),
+        };
+        for entry in entries {
+            let line = self.run_entry(src, &entry)?;
+            writeln!(out, "{}", line).map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Runs a single entry, returning the lines to show for it: the goals of its holes, then its type
+    fn run_entry(&mut self, src: &str, entry: &Entry) -> Result<String, String> {
+        let (checked, goals) = check_entry(&mut self.loader.globals, &mut self.names, src, entry)
+            .map_err(|err| err.render(src) + "\n")?;
+        let goals: String = goals
+            .iter()
+            .map(|goal| {
+                let message = goal.render(&self.loader.globals);
+                TypeError {
+                    span: goal.span,
+                    message,
+                }
+                .render(src)
+                    + "\n"
+            })
+            .collect();
+        let line = match checked {
+            Checked::Declared(id) => {
+                let def = &self.loader.globals.defs[id];
+                format!(
+                    "{} : {}",
+                    def.name,
+                    pretty_core(&self.loader.globals, &[], &def.typ)
+                )
+            }
+            Checked::Evaluated { value, typ } => format!(
+                "{} : {}",
+                pretty_core(&self.loader.globals, &[], &value),
+                pretty_core(&self.loader.globals, &[], &typ)
+            ),
+        };
+        Ok(goals + &line)
+    }
+
+    /// Parses and infers the type of a term, returning its normal form and type
+    fn infer(&mut self, src: &str) -> Result<(String, String), String> {
+        let term = match parse_term(src).inner {
+            Ok(ok) => ok.result,
+            Err(err) => return Err(err.render(src)),
+        };
+        let entry = Entry::Eval {
+            term: Box::new(term),
+        };
+        match check_entry(&mut self.loader.globals, &mut self.names, src, &entry)
+            .map_err(|err| err.render(src) + "\n")?
+            .0
+        {
+            Checked::Evaluated { value, typ } => Ok((
+                pretty_core(&self.loader.globals, &[], &value),
+                pretty_core(&self.loader.globals, &[], &typ),
+            )),
+            Checked::Declared(_) => unreachable!(),
+        }
+    }
+}
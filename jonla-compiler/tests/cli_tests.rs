@@ -0,0 +1,154 @@
+mod common;
+
+use common::{jonla, source_file};
+use serde_json::Value;
+use std::path::PathBuf;
+
+const ID: &str = "universe u\ndef id : (a : Type u) -> a -> a = / a : Type u, x : a.   x\ndef idt : Type -> Type =  id   Type\n";
+
+#[test]
+fn check_and_eval() {
+    let file = source_file("cli_id.jl", ID);
+    let file = file.to_str().unwrap();
+    assert_eq!(jonla(&["check", file]), (0, String::new(), String::new()));
+
+    let (code, stdout, _) = jonla(&["eval", file]);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "id : (a : Type u) -> a -> a = / a : Type u, x : a. x\nidt : Type -> Type = / x : Type. x\n"
+    );
+}
+
+#[test]
+fn fmt_and_parse_tree() {
+    let file = source_file("cli_fmt.jl", ID);
+    let file = file.to_str().unwrap();
+    let (code, stdout, _) = jonla(&["fmt", file]);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "universe u\ndef id : (a : Type u) -> a -> a = / a : Type u, x : a. x\ndef idt : Type -> Type = id Type\n"
+    );
+
+    let (code, stdout, _) = jonla(&["parse-tree", file]);
+    assert_eq!(code, 0);
+    assert!(
+        stdout.starts_with("program@0..106\n  decl@0..11\n    \"universe\"@0..8 \"universe\"\n")
+    );
+}
+
+#[test]
+fn human_errors() {
+    let type_error = source_file(
+        "cli_type_error.jl",
+        "def x : Type 1 = Type\ndef y : x = foo\n",
+    );
+    let parse_error = source_file("cli_parse_error.jl", "def x : Type =\n");
+    let (code, stdout, stderr) = jonla(&[
+        "check",
+        type_error.to_str().unwrap(),
+        parse_error.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "");
+    assert!(stderr.contains(&format!(
+        "error: Unknown variable `foo`\n --> {}:2:13\ndef y : x = foo\n            ^^^\n",
+        type_error.display()
+    )));
+    assert!(stderr.contains(&format!("{}:1:15\n", parse_error.display())));
+}
+
+#[test]
+fn json_errors() {
+    let file = source_file("cli_json.jl", "def x : Type 1 = Type\ndef y : x = foo\n");
+    let (code, _, stderr) = jonla(&["check", "--error-format=json", file.to_str().unwrap()]);
+    assert_eq!(code, 1);
+    let diagnostic: Value = serde_json::from_str(stderr.trim()).unwrap();
+    assert_eq!(diagnostic["message"], "Unknown variable `foo`");
+    assert_eq!(diagnostic["start"], 34);
+    assert_eq!(diagnostic["end"], 37);
+    assert_eq!(diagnostic["line"], 2);
+    assert_eq!(diagnostic["column"], 13);
+}
+
+#[test]
+fn usage_errors() {
+    assert_eq!(jonla(&[]).0, 2);
+    assert_eq!(jonla(&["check"]).0, 2);
+    assert_eq!(jonla(&["frobnicate", "x.jl"]).0, 2);
+    assert_eq!(jonla(&["check", "--error-format=xml", "x.jl"]).0, 2);
+    assert_eq!(jonla(&["check", "does/not/exist.jl"]).0, 2);
+    assert_eq!(jonla(&["--help"]).0, 0);
+}
+
+#[test]
+fn goals() {
+    let file = source_file("cli_goal.jl", "def f : Type -> Type = / a : Type. ?goal\n");
+    let (code, stdout, stderr) = jonla(&["check", file.to_str().unwrap()]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        format!(
+            "goal: Hole `?goal` should have type `Type`\n --> {}:1:36\ndef f : Type -> Type = / a : Type. ?goal\n                                   ^^^^^\n  a : Type\n",
+            file.display()
+        )
+    );
+}
+
+#[test]
+fn run() {
+    let file = source_file(
+        "cli_run.jl",
+        "data Nat : Type {\n    zero : Nat\n    succ : Nat -> Nat\n}\ndef two : Nat = succ (succ zero)\ndef f : Nat -> Nat = succ\n",
+    );
+    let (code, stdout, _) = jonla(&["run", file.to_str().unwrap()]);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "Nat = <erased>\nzero = cli_run.zero\nsucc = <function>\nNat.elim = <function>\ntwo = cli_run.succ (cli_run.succ cli_run.zero)\nf = <function>\n"
+    );
+}
+
+#[test]
+fn export_and_verify() {
+    let file = source_file("cli_export.jl", ID);
+    let (code, exported, _) = jonla(&["export", file.to_str().unwrap()]);
+    assert_eq!(code, 0);
+    let exported_file = source_file("cli_export.json", &exported);
+    let (code, stdout, stderr) = jonla(&["verify", exported_file.to_str().unwrap()]);
+    assert_eq!((code, stdout, stderr), (0, String::new(), String::new()));
+
+    //`id` is used on `Type` at the universe `Type` is in, instead of the one above it
+    let tampered = exported.replace(
+        r#"["global",0,[{"constant":1,"vars":{}}]]"#,
+        r#"["global",0,[{"constant":0,"vars":{}}]]"#,
+    );
+    assert_ne!(tampered, exported);
+    let tampered_file = source_file("cli_tampered.json", &tampered);
+    let (code, _, stderr) = jonla(&["verify", tampered_file.to_str().unwrap()]);
+    assert_eq!(code, 1);
+    assert!(
+        stderr.contains("`cli_export.idt`: Type mismatch"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn check_resources() {
+    //The sample programs that are shipped keep checking
+    let resources = concat!(env!("CARGO_MANIFEST_DIR"), "/resources");
+    let mut files: Vec<PathBuf> = std::fs::read_dir(resources)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jl"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty());
+    for file in files {
+        let (code, _, stderr) = jonla(&["check", file.to_str().unwrap()]);
+        assert_eq!(code, 0, "{}: {}", file.display(), stderr);
+    }
+}
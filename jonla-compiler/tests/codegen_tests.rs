@@ -0,0 +1,92 @@
+mod common;
+
+use common::{jonla, source_file};
+use jonla_compiler::lang::eval::normalize;
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::pretty_core;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Compiles `src` to Rust, and checks that the compiled program shows the normal forms of the definitions in `names`
+fn agrees(name: &str, src: &str, names: &[&str]) {
+    let path = source_file(&format!("{}.jl", name), src);
+    let (code, rust, stderr) = jonla(&["rust", path.to_str().unwrap()]);
+    assert_eq!(code, 0, "{}", stderr);
+    let rust = source_file(&format!("{}.rs", name), &rust);
+    let binary = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let status = Command::new(rustc)
+        .args(["--edition", "2021", "-o"])
+        .arg(&binary)
+        .arg(&rust)
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "Generated code for `{}` didn't compile",
+        name
+    );
+    let output = Command::new(&binary).output().unwrap();
+    assert!(output.status.success());
+    let shown = String::from_utf8(output.stdout).unwrap();
+
+    let mut loader = Loader::default();
+    let id = loader.load(&path).unwrap();
+    let globals = &loader.globals;
+    for name in names {
+        let (_, id) = loader.modules[id]
+            .defs
+            .iter()
+            .find(|(n, _)| &**n == *name)
+            .unwrap();
+        let expected = pretty_core(
+            globals,
+            &[],
+            &normalize(globals, &vec![], &globals.defs[*id].value),
+        );
+        let line = format!("{} = {}", name, expected);
+        assert!(shown.lines().any(|l| l == line), "{}\n{}", line, shown);
+    }
+}
+
+#[test]
+fn data_and_recursion() {
+    agrees(
+        "codegen_nat",
+        concat!(
+            "data Nat : Type {\n    zero : Nat\n    succ : Nat -> Nat\n}\n",
+            "data Vec (a : Type) : Nat -> Type {\n    nil : Vec a zero\n    cons : (n : Nat) -> a -> Vec a n -> Vec a (succ n)\n}\n",
+            "def one : Nat = succ zero\n",
+            "def plus : Nat -> Nat -> Nat = / m : Nat, n : Nat. ",
+            "Nat.elim (/ _ : Nat. Nat) n (/ k : Nat, _ : Nat. succ (plus k n)) m\n",
+            "def length : (a : Type) -> (n : Nat) -> Vec a n -> Nat = / a : Type. ",
+            "Vec.elim a (/ n : Nat, _ : Vec a n. Nat) zero (/ n : Nat, _ : a, _ : Vec a n, ih : Nat. succ ih)\n",
+            "def three : Nat = plus one (plus one one)\n",
+            "def xs : Vec Nat (succ one) = cons Nat one three (cons Nat zero one (nil Nat))\n",
+            "def len : Nat = length Nat (succ one) xs\n",
+        ),
+        &["one", "three", "len"],
+    );
+}
+
+#[test]
+fn closures_and_partial_application() {
+    let church = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/church_and.jl");
+    agrees(
+        "codegen_church",
+        &format!(
+            "import {}\n\
+             data Bool : Type {{\n    true : Bool\n    false : Bool\n}}\n\
+             def pair : church_and.and Bool Bool = church_and.conj true false\n\
+             def first : Bool = church_and.proj1 (church_and.swap pair)\n\
+             def twice : (Bool -> Bool) -> Bool -> Bool = / f : Bool -> Bool, x : Bool. f (f x)\n\
+             def not : Bool -> Bool = / b : Bool. Bool.elim (/ _ : Bool. Bool) false true b\n\
+             def flip : Bool -> Bool = twice not\n\
+             def second : Bool = twice (twice not) (flip false)\n\
+             def choose : Bool -> Bool -> Bool = / x : Bool. let y : Bool = not x; / z : Bool. Bool.elim (/ _ : Bool. Bool) y z x\n\
+             def chosen : Bool = choose true false\n",
+            church
+        ),
+        &["first", "second", "chosen"],
+    );
+}
@@ -0,0 +1,67 @@
+//! Fixtures shared by the integration tests, each of which only uses some of them
+#![allow(dead_code)]
+
+use jonla_compiler::lang::module::{LoadError, Loader};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The natural numbers
+pub const NAT: &str = "data Nat : Type {\n    zero : Nat\n    succ : Nat -> Nat\n}\n";
+
+/// Equality, which only has a proof for equal values
+pub const EQ: &str = "data Eq (a : Type) (x : a) : a -> Type {\n    refl : Eq a x x\n}\n";
+
+/// `two`, and addition of natural numbers, which recurses on its first argument
+pub const PLUS: &str = "def two : Nat = succ (succ zero)\n\
+                        def plus : Nat -> Nat -> Nat = / m : Nat, n : Nat. \
+                        Nat.elim (/ _ : Nat. Nat) n (/ k : Nat, _ : Nat. succ (plus k n)) m\n";
+
+/// Writes `src` to a file named `name` in a scratch directory, returning its path
+pub fn source_file(name: &str, src: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, src).unwrap();
+    path
+}
+
+/// Writes the given files to a fresh scratch directory named `dir`, returning its path
+pub fn files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    for (name, src) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, src).unwrap();
+    }
+    dir
+}
+
+/// Loads the file at `path`, which should fail with one error
+pub fn load_path_err(path: &Path) -> LoadError {
+    let mut errors = Loader::default().load(path).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    errors.remove(0)
+}
+
+/// Loads `src` from a file named `name`, which should fail with one error
+pub fn load_err(name: &str, src: &str) -> LoadError {
+    load_path_err(&source_file(name, src))
+}
+
+/// The part of the source that `err` is about
+pub fn spanned(err: &LoadError) -> &str {
+    let (start, end) = err.span.unwrap();
+    &err.src[start..end]
+}
+
+/// Runs `jonla` with the given arguments, returning the exit code, stdout and stderr
+pub fn jonla(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_jonla"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
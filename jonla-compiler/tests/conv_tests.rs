@@ -0,0 +1,92 @@
+use jonla_compiler::autogen::ast::Entry;
+use jonla_compiler::autogen::parse::{parse_entries, parse_term};
+use jonla_compiler::lang::check::{Checker, Ctx};
+use jonla_compiler::lang::conv::{conv, Unfolding};
+use jonla_compiler::lang::core::Namespace;
+use jonla_compiler::lang::eval::{eval, Val};
+use jonla_compiler::lang::module::Loader;
+
+/// Loads the Church encoding of `and`, returning the loader and a namespace with its definitions
+fn church_and() -> (Loader, Namespace) {
+    let mut loader = Loader::default();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/church_and.jl");
+    let id = loader.load(path.as_ref()).unwrap();
+    let mut names = Namespace::default();
+    for (name, def) in &loader.modules[id].defs {
+        names.insert(name.clone(), *def);
+    }
+    (loader, names)
+}
+
+/// Checks the closed term `src` and evaluates it
+fn value(loader: &Loader, names: &Namespace, src: &str) -> Val {
+    let term = parse_term(src).inner.unwrap().result;
+    let checker = Checker::new(&loader.globals, names, src);
+    let (tm, _) = checker.infer(&Ctx::default(), &term).unwrap();
+    checker.solve_metas().unwrap();
+    eval(&loader.globals, &vec![], &checker.zonk(&tm))
+}
+
+/// Whether `a` and `b` are convertible, checking that both strategies agree
+fn convertible(a: &str, b: &str) -> bool {
+    let (loader, names) = church_and();
+    let a = value(&loader, &names, a);
+    let b = value(&loader, &names, b);
+    let lazy = conv(&loader.globals, 0, &a, &b, Unfolding::Lazy);
+    assert_eq!(lazy, conv(&loader.globals, 0, &a, &b, Unfolding::Eager));
+    lazy
+}
+
+#[test]
+fn beta_delta_eta() {
+    assert!(convertible("(/ x : Type 1. x) Type", "Type"));
+    assert!(convertible("let t : Type 1 = Type\nt -> t", "Type -> Type"));
+    assert!(convertible(
+        "and Type (Type -> Type)",
+        "(c : Type 1) -> (Type -> (Type -> Type) -> c) -> c"
+    ));
+    assert!(convertible("/ p : Type, q : Type. and p q", "and"));
+    assert!(convertible(
+        "/ a : and Type (Type -> Type). proj1 a",
+        "/ b : and Type (Type -> Type). b Type (/ x : Type, y : Type -> Type. x)"
+    ));
+    assert!(!convertible(
+        "and Type (Type -> Type)",
+        "and (Type -> Type) Type"
+    ));
+    assert!(!convertible("Type", "Type 1"));
+}
+
+/// Checks `let name : typ = value`, returning the error
+fn check_error(unfolding: Unfolding, src: &str) -> String {
+    let (loader, names) = church_and();
+    let entries = parse_entries(src).inner.unwrap().result;
+    let Entry::Declare {
+        name,
+        arg_type,
+        arg_value,
+    } = &entries[0]
+    else {
+        panic!("Expected a declaration")
+    };
+    Checker::new(&loader.globals, &names, src)
+        .with_unfolding(unfolding)
+        .check_definition(name, arg_type, arg_value, false)
+        .unwrap_err()
+        .message
+}
+
+#[test]
+fn errors_show_folded_names() {
+    let src =
+        "let bad : (p : Type) -> (q : Type) -> and p q -> p = / p : Type, q : Type, a : and p q. a";
+    assert_eq!(
+        check_error(Unfolding::Lazy, src),
+        "Type mismatch: expected `p`, but found `church_and.and p q`"
+    );
+    //Errors show the types from before they were compared, so eager unfolding keeps the names as well
+    assert_eq!(
+        check_error(Unfolding::Eager, src),
+        "Type mismatch: expected `p`, but found `church_and.and p q`"
+    );
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{load_err, source_file, spanned, EQ, NAT};
+use jonla_compiler::lang::eval::normalize;
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::pretty_core;
+
+#[test]
+fn eliminators_compute() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/nat.jl");
+    let mut loader = Loader::default();
+    let id = loader.load(path.as_ref()).unwrap();
+    let globals = &loader.globals;
+    let show = |name: &str| {
+        let (_, id) = loader.modules[id]
+            .defs
+            .iter()
+            .find(|(n, _)| &**n == name)
+            .unwrap();
+        let def = &globals.defs[*id];
+        (
+            pretty_core(globals, &[], &def.typ),
+            pretty_core(globals, &[], &normalize(globals, &vec![], &def.value)),
+        )
+    };
+    assert_eq!(
+        show("Nat.elim").0,
+        "(P : nat.Nat -> Type u) -> P nat.zero -> ((x : nat.Nat) -> P x -> P (nat.succ x)) -> (t : nat.Nat) -> P t"
+    );
+    assert_eq!(
+        show("four").1,
+        "nat.succ (nat.succ (nat.succ (nat.succ nat.zero)))"
+    );
+    assert_eq!(
+        show("Vec.elim").0,
+        "(a : Type) -> (P : (x : nat.Nat) -> nat.Vec a x -> Type u) -> P nat.zero (nat.nil a) -> \
+         ((n : nat.Nat) -> (x : a) -> (x1 : nat.Vec a n) -> P n x1 -> P (nat.succ n) (nat.cons a n x x1)) -> \
+         (x : nat.Nat) -> (t : nat.Vec a x) -> P x t"
+    );
+}
+
+#[test]
+fn elimination_is_checked() {
+    let err = load_err(
+        "data_wrong_proof.jl",
+        &format!(
+            "{}{}def wrong : Eq Nat zero (succ zero) = refl Nat zero\n",
+            NAT, EQ
+        ),
+    );
+    assert!(err.message.starts_with("Type mismatch"), "{}", err.message);
+    assert_eq!(spanned(&err), "refl");
+}
+
+#[test]
+fn positivity() {
+    let err = load_err(
+        "data_negative.jl",
+        "data Bad : Type {\n    bad : (Bad -> Bad) -> Bad\n}\n",
+    );
+    assert_eq!(
+        err.message,
+        "`data_negative.Bad` occurs in a non-positive position, to the left of an arrow"
+    );
+    assert_eq!(spanned(&err), "bad");
+
+    //Functions returning the data type are fine
+    let src = "data Tree : Type 1 {\n    leaf : Tree\n    node : (Type -> Tree) -> Tree\n}\n";
+    Loader::default()
+        .load(&source_file("data_positive.jl", src))
+        .unwrap();
+}
+
+#[test]
+fn constructor_results() {
+    let err = load_err(
+        "data_ctor_result.jl",
+        &format!("{}data Wrong : Type {{\n    wrong : Nat\n}}\n", NAT),
+    );
+    assert_eq!(
+        err.message,
+        "A constructor of `data_ctor_result.Wrong` should result in `data_ctor_result.Wrong` applied to 0 argument(s)"
+    );
+    assert_eq!(spanned(&err), "wrong");
+
+    let err = load_err(
+        "data_ctor_params.jl",
+        &format!(
+            "{}data List (a : Type) : Type {{\n    nil : List Nat\n}}\n",
+            NAT
+        ),
+    );
+    assert_eq!(
+        err.message,
+        "`data_ctor_params.List` should be applied to its parameters, in the same order"
+    );
+
+    let err = load_err(
+        "data_duplicate.jl",
+        &format!("{}def succ : Type = Type\n", NAT),
+    );
+    assert_eq!(err.message, "`succ` is already defined in this module");
+    assert_eq!(spanned(&err), "succ");
+}
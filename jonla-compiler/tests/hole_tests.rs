@@ -0,0 +1,144 @@
+mod common;
+
+use common::{load_err, source_file, spanned, NAT};
+use jonla_compiler::autogen::parse::parse_term;
+use jonla_compiler::lang::eval::normalize;
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::{pretty_core, pretty_term};
+
+/// Loads `src`, returning the messages of its goals with the part of the source each is about
+fn goals(name: &str, src: &str) -> Vec<(String, String)> {
+    let mut loader = Loader::default();
+    loader.load(&source_file(name, src)).unwrap();
+    loader
+        .goals
+        .iter()
+        .map(|goal| {
+            let (start, end) = goal.span.unwrap();
+            (goal.message.clone(), goal.src[start..end].to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn goals_show_type_and_context() {
+    let goals = goals(
+        "hole_context.jl",
+        &format!(
+            "{}def f : Nat -> Nat -> (Nat -> Nat) -> Nat -> Nat = / n : Nat, _ : Nat, m : Nat -> Nat. ?goal\n",
+            NAT
+        ),
+    );
+    assert_eq!(
+        goals,
+        vec![(
+            "Hole `?goal` should have type `hole_context.Nat -> hole_context.Nat`\n  \
+             n : hole_context.Nat\n  \
+             m : hole_context.Nat -> hole_context.Nat"
+                .to_string(),
+            "?goal".to_string()
+        )]
+    );
+}
+
+#[test]
+fn checking_continues_after_holes() {
+    let mut loader = Loader::default();
+    let id = loader
+        .load(&source_file(
+            "hole_continue.jl",
+            &format!(
+                "{}def two : Nat = succ ?x\ndef three : Nat = succ two\ndef f : Nat -> Nat = / n : Nat. succ (?y n)\n",
+                NAT
+            ),
+        ))
+        .unwrap();
+    assert_eq!(loader.goals.len(), 2);
+    assert!(loader.goals[1]
+        .message
+        .starts_with("Hole `?y` should have type `hole_continue.Nat -> hole_continue.Nat`"));
+
+    //The holes stay in the normal forms of the definitions that use them
+    let globals = &loader.globals;
+    let value = |i: usize| {
+        let def = &globals.defs[loader.modules[id].defs[i].1];
+        pretty_core(globals, &[], &normalize(globals, &vec![], &def.value))
+    };
+    assert_eq!(value(5), "hole_continue.succ (hole_continue.succ ?x)");
+    assert_eq!(
+        value(6),
+        "/ n : hole_continue.Nat. hole_continue.succ (?y n)"
+    );
+}
+
+#[test]
+fn checking_continues_after_errors() {
+    //The definitions after an error are checked, so their errors and goals are reported as well
+    let mut loader = Loader::default();
+    let errors = loader
+        .load(&source_file(
+            "hole_after_error.jl",
+            "def a : Type = Type\ndef b : Type 1 = ?g\ndef c : Type 1 = Type 1\ndef d : Type 1 = b\n",
+        ))
+        .unwrap_err();
+    let errors: Vec<(&str, &str)> = errors
+        .iter()
+        .map(|err| (err.message.as_str(), spanned(err)))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            ("Type mismatch: expected `Type`, but found `Type 1`", "Type"),
+            (
+                "Type mismatch: expected `Type 1`, but found `Type 2`",
+                "Type"
+            ),
+        ]
+    );
+    assert_eq!(loader.goals.len(), 1);
+    assert!(loader.goals[0]
+        .message
+        .starts_with("Hole `?g` should have type `Type 1`"));
+}
+
+#[test]
+fn solved_holes() {
+    //An anonymous hole that unification solves is not a goal, a named one is shown with its solution
+    let goals = goals(
+        "hole_solved.jl",
+        &format!(
+            "{}def id : {{a : Type}} -> a -> a = / x : a. x\ndef one : _ = id {{_}} (succ zero)\ndef two : ?t = succ one\n",
+            NAT
+        ),
+    );
+    assert_eq!(
+        goals,
+        vec![(
+            "Hole `?t` should have type `Type`, and was solved as `hole_solved.Nat`".to_string(),
+            "?t".to_string()
+        )]
+    );
+}
+
+#[test]
+fn holes_in_data_declarations() {
+    let err = load_err(
+        "hole_data.jl",
+        "data Box : Type {\n    box : ?field -> Box\n}\n",
+    );
+    assert_eq!(
+        err.message,
+        "Holes can't be left in a data declaration, `Box` has a hole"
+    );
+    assert_eq!(
+        err.span.map(|(start, end)| &err.src[start..end]),
+        Some("?field")
+    );
+}
+
+#[test]
+fn hole_syntax_round_trips() {
+    let src = "/ x : Type. f ?goal _ (?g x)";
+    let term = parse_term(src).inner.unwrap().result;
+    assert_eq!(pretty_term(&term), src);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::{load_err, source_file, spanned};
+use jonla_compiler::autogen::parse::parse_term;
+use jonla_compiler::lang::eval::normalize;
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::{pretty_core, pretty_term};
+
+/// Loads `src`, returning the types and the normalized values of its definitions
+fn defs(name: &str, src: &str) -> Vec<String> {
+    let mut loader = Loader::default();
+    let id = loader.load(&source_file(name, src)).unwrap();
+    let globals = &loader.globals;
+    loader.modules[id]
+        .defs
+        .iter()
+        .map(|(name, id)| {
+            let def = &globals.defs[*id];
+            let value = normalize(globals, &vec![], &def.value);
+            format!(
+                "{} : {} = {}",
+                name,
+                pretty_core(globals, &[], &def.typ),
+                pretty_core(globals, &[], &value)
+            )
+        })
+        .collect()
+}
+
+const ID: &str = "def id : {a : Type} -> a -> a = / x : a. x\n";
+
+#[test]
+fn implicit_arguments_are_inferred() {
+    let defs = defs(
+        "implicit_inferred.jl",
+        &format!(
+            "{}def t : (b : Type) -> b -> b = / b : Type, y : b. id y\n\
+             def explicit : (b : Type) -> b -> b = / b : Type. id {{b}}\n\
+             def lambda : {{a : Type}} -> a -> a = / {{a : Type}}, x : a. id {{a}} x\n",
+            ID
+        ),
+    );
+    assert_eq!(
+        defs,
+        vec![
+            "id : {a : Type} -> a -> a = / {a : Type}, x : a. x",
+            "t : (b : Type) -> b -> b = / b : Type, y : b. y",
+            "explicit : (b : Type) -> b -> b = / b : Type, x : b. x",
+            "lambda : {a : Type} -> a -> a = / {a : Type}, x : a. x",
+        ]
+    );
+}
+
+#[test]
+fn higher_order_unification() {
+    let defs = defs(
+        "implicit_pattern.jl",
+        "def app : {a : Type 1} -> {b : a -> Type 1} -> ((x : a) -> b x) -> (x : a) -> b x = / f : (x : a) -> b x, x : a. f x\n\
+         def poly : (c : Type) -> c -> c = / c : Type, x : c. x\n\
+         def t : (c : Type) -> c -> c = app poly\n",
+    );
+    assert_eq!(defs[2], "t : (c : Type) -> c -> c = / x : Type, x1 : x. x1");
+}
+
+#[test]
+fn unsolved_metas() {
+    let err = load_err(
+        "implicit_unsolved.jl",
+        "def const : {a : Type} -> Type 1 = / {a : Type}. Type\n\
+         def t : Type 1 = const\n",
+    );
+    assert_eq!(
+        err.message,
+        "Could not infer the implicit argument `a` of `const`"
+    );
+    assert_eq!(spanned(&err), "const");
+
+    //`?b c Type = Type` has no unique solution, as `Type` is not a variable
+    let err = load_err(
+        "implicit_not_pattern.jl",
+        "def pick : {b : Type 1 -> Type} -> b Type -> Type 1 = / {b : Type 1 -> Type}, x : b Type. Type\n\
+         def t : Type -> Type 1 = / c : Type. pick c\n",
+    );
+    assert_eq!(
+        err.message,
+        "Type mismatch: expected `?0 c Type`, but found `Type`"
+    );
+    assert_eq!(spanned(&err), "c");
+
+    //The implicit argument would have to be `Type 1`, which is not in `Type`
+    let err = load_err(
+        "implicit_universe.jl",
+        &format!("{}def big : Type 2 = Type 1\ndef t : Type 1 = id big\n", ID),
+    );
+    assert_eq!(
+        err.message,
+        "Type mismatch: expected `?0`, but found `Type 2`"
+    );
+    assert_eq!(spanned(&err), "big");
+}
+
+#[test]
+fn implicit_syntax_round_trips() {
+    let src = "/ {a : Type}, x : a. f {a -> a} (g {a} x) ({b : Type} -> b)";
+    let term = parse_term(src).inner.unwrap().result;
+    assert_eq!(pretty_term(&term), src);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::{source_file, EQ, NAT, PLUS};
+use jonla_compiler::lang::core::{Globals, Tm};
+use jonla_compiler::lang::export::{export, import, Decl};
+use jonla_compiler::lang::kernel::{check_decls, KernelError};
+use jonla_compiler::lang::level::{Level, LevelVar};
+use jonla_compiler::lang::module::Loader;
+
+/// Checks `src` with the type checker, and exports it, also returning the globals it was exported from
+fn exported(name: &str, src: &str) -> (String, Globals) {
+    let mut loader = Loader::default();
+    loader.load(&source_file(name, src)).unwrap();
+    (export(&loader.globals).unwrap(), loader.globals)
+}
+
+/// The declaration named `name` in `decls`
+fn decl<'d>(decls: &'d mut [Decl], name: &str) -> &'d mut Decl {
+    decls
+        .iter_mut()
+        .find(|decl| match decl {
+            Decl::Data { name: n, .. } | Decl::Def { name: n, .. } => &**n == name,
+        })
+        .unwrap()
+}
+
+/// The natural numbers, vectors and equality, with a proof that needs `plus` to compute
+fn prelude() -> String {
+    format!(
+        "{}data Vec (a : Type) : Nat -> Type {{\n    nil : Vec a zero\n    cons : (n : Nat) -> a -> Vec a n -> Vec a (succ n)\n}}\n\
+         {}{}def four : Eq Nat (plus two two) (succ (succ two)) = refl Nat (succ (succ two))\n",
+        NAT, EQ, PLUS
+    )
+}
+
+#[test]
+fn exports_verify() {
+    let src = format!(
+        "{}universe u\n\
+         def id : {{a : Type u}} -> a -> a = / {{a : Type u}}, x : a. x\n\
+         def xs : Vec Nat (succ zero) = cons Nat zero (id two) (nil Nat)\n\
+         def length : (a : Type) -> (n : Nat) -> Vec a n -> Nat = / a : Type. \
+         Vec.elim a (/ n : Nat, _ : Vec a n. Nat) zero (/ n : Nat, _ : a, _ : Vec a n, ih : Nat. succ ih)\n\
+         def six : Nat = let n : Nat = plus two (length Nat (succ zero) xs) ; plus n (plus n n)\n\
+         partial def loop : Nat -> Nat = / n : Nat. loop (succ n)\n",
+        prelude()
+    );
+    let (exported, checked) = exported("kernel_nat.jl", &src);
+    let globals = check_decls(&import(&exported).unwrap()).unwrap();
+    //The kernel defines the same globals as the type checker, with the same ids
+    assert_eq!(globals.defs.len(), checked.defs.len());
+    for (def, checked) in globals.defs.iter().zip(&checked.defs) {
+        assert_eq!(def.name, checked.name);
+        assert_eq!(format!("{:?}", def.typ), format!("{:?}", checked.typ));
+        assert_eq!(format!("{:?}", def.value), format!("{:?}", checked.value));
+    }
+}
+
+#[test]
+fn tampered_exports() {
+    let src = format!(
+        "{}def three : Eq Nat (succ two) (succ two) = refl Nat (succ two)\n\
+         partial def loop : Nat -> Nat = / n : Nat. loop n\n\
+         partial def start : Nat = loop zero\n",
+        prelude()
+    );
+    let (exported, _) = exported("kernel_tampered.jl", &src);
+    let check = |tamper: &dyn Fn(&mut [Decl])| -> KernelError {
+        let mut decls = import(&exported).unwrap();
+        tamper(&mut decls);
+        check_decls(&decls).unwrap_err()
+    };
+    let set_value = |decls: &mut [Decl], name: &str, new: Tm| {
+        if let Decl::Def { value, .. } = decl(decls, name) {
+            *value = new;
+        }
+    };
+    let set_partial = |decls: &mut [Decl], name: &str, new: bool| {
+        if let Decl::Def { partial, .. } = decl(decls, name) {
+            *partial = new;
+        }
+    };
+
+    //A proof of another equation
+    let err = check(&|decls| {
+        let Decl::Def { value, .. } = decl(decls, "kernel_tampered.three") else {
+            unreachable!()
+        };
+        let proof = value.clone();
+        set_value(decls, "kernel_tampered.four", proof);
+    });
+    assert_eq!(&*err.name, "kernel_tampered.four");
+    assert!(err.message.starts_with("Type mismatch"), "{}", err.message);
+
+    //Definitions that may not terminate
+    let err = check(&|decls| set_partial(decls, "kernel_tampered.loop", false));
+    assert_eq!(
+        err.message,
+        "`kernel_tampered.loop` is not structurally recursive"
+    );
+    let err = check(&|decls| set_partial(decls, "kernel_tampered.start", false));
+    assert_eq!(
+        err.message,
+        "`kernel_tampered.start` uses the partial definition `kernel_tampered.loop`, but isn't partial"
+    );
+
+    //A universe that contains itself
+    let err = check(&|decls| {
+        if let Decl::Def { typ, .. } = decl(decls, "kernel_tampered.two") {
+            *typ = Tm::Type(Level::zero());
+        }
+        set_value(decls, "kernel_tampered.two", Tm::Type(Level::zero()));
+    });
+    assert_eq!(&*err.name, "kernel_tampered.two");
+    assert!(err.message.starts_with("Type mismatch"), "{}", err.message);
+
+    //A universe that isn't a parameter of the definition
+    let err = check(&|decls| {
+        let u = Level::var(LevelVar::Param("u".into()));
+        if let Decl::Def { typ, .. } = decl(decls, "kernel_tampered.two") {
+            *typ = Tm::Type(u.succ());
+        }
+        set_value(decls, "kernel_tampered.two", Tm::Type(u));
+    });
+    assert_eq!(err.message, "Unknown universe `u`");
+}
+
+#[test]
+fn invalid_exports() {
+    let mut loader = Loader::default();
+    //A hole is reported as a goal, but it has no term to export
+    loader
+        .load(&source_file("kernel_hole.jl", "def t : Type = ?t\n"))
+        .unwrap();
+    assert_eq!(
+        export(&loader.globals),
+        Err("`kernel_hole.t`: Holes can't be exported".to_string())
+    );
+
+    assert!(import("def t : Type = Type\n").is_err());
+    let err = import(&format!(
+        "{}{{\"def\": 1}}\n",
+        exported("kernel_empty.jl", "").0
+    ));
+    assert_eq!(
+        err.map(|_| ()),
+        Err("Line 2: Invalid declaration".to_string())
+    );
+}
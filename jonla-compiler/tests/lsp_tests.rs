@@ -0,0 +1,232 @@
+use jonla_compiler::lsp::transport::{read_message, write_message};
+use serde_json::{json, Value};
+use std::io::BufReader;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Talks to the `jonla-lsp` binary the way an editor would
+struct ScriptedClient {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    notifications: Vec<Value>,
+}
+
+impl ScriptedClient {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_jonla-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = ScriptedClient {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+            notifications: vec![],
+        };
+        let result = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(result["capabilities"]["hoverProvider"], true);
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut self.stdin, &message).unwrap();
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        write_message(&mut self.stdin, &message).unwrap();
+        loop {
+            let message = read_message(&mut self.stdout).unwrap().unwrap();
+            if message["id"] == id {
+                return message["result"].clone();
+            }
+            self.notifications.push(message);
+        }
+    }
+
+    /// The diagnostics published most recently, which arrive before the response to any later request
+    fn last_diagnostics(&mut self) -> Value {
+        self.request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": URI } }),
+        );
+        self.notifications
+            .iter()
+            .rev()
+            .find(|n| n["method"] == "textDocument/publishDiagnostics")
+            .unwrap()["params"]["diagnostics"]
+            .clone()
+    }
+
+    fn open(&mut self, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "languageId": "jonla", "version": 1, "text": text } }),
+        );
+    }
+
+    fn change(&mut self, text: &str) {
+        self.notify(
+            "textDocument/didChange",
+            json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": text }] }),
+        );
+    }
+
+    fn at(&mut self, method: &str, line: u32, character: u32) -> Value {
+        self.request(
+            method,
+            json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } }),
+        )
+    }
+
+    fn stop(mut self) {
+        assert_eq!(self.request("shutdown", Value::Null), Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+const URI: &str = "file:///test.jl";
+
+const PROGRAM: &str = "data Nat : Type {
+    zero : Nat
+    succ : Nat -> Nat
+}
+def id : (p : Type) -> p -> p = / p : Type, x : p. x
+def k : Type -> Type -> Type = / a : Type, b : Type. a
+def two : k Nat Nat = id Nat (succ (succ zero))";
+
+#[test]
+fn diagnostics() {
+    let mut client = ScriptedClient::start();
+    client.open(PROGRAM);
+    assert_eq!(client.last_diagnostics(), json!([]));
+
+    client.change("def x : Type = \ndef y : Type = x");
+    let diagnostics = client.last_diagnostics();
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 0);
+    assert!(diagnostics[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Expected"));
+
+    // Type errors point at the term they are about
+    client.change(&format!("{}\ndef wrong : Nat = id Type", PROGRAM));
+    let diagnostics = client.last_diagnostics();
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({ "start": { "line": 7, "character": 21 }, "end": { "line": 7, "character": 25 } })
+    );
+    assert_eq!(
+        diagnostics[0]["message"],
+        "Type mismatch: expected `Type`, but found `Type 1`"
+    );
+
+    // So do definitions that may not terminate
+    client.change(&format!(
+        "{}\ndef loop : Nat -> Nat = / n : Nat. loop (succ n)",
+        PROGRAM
+    ));
+    let diagnostics = client.last_diagnostics();
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({ "start": { "line": 7, "character": 4 }, "end": { "line": 7, "character": 8 } })
+    );
+    assert!(diagnostics[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("`loop` may not terminate"));
+
+    client.change(PROGRAM);
+    assert_eq!(client.last_diagnostics(), json!([]));
+    client.stop();
+}
+
+#[test]
+fn hover() {
+    let mut client = ScriptedClient::start();
+    client.open(PROGRAM);
+
+    // The name of a definition
+    let hover = client.at("textDocument/hover", 5, 4);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```jonla\nk : Type -> Type -> Type\n```"
+    );
+
+    // A reference to a definition
+    let hover = client.at("textDocument/hover", 6, 22);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```jonla\nid : (p : Type) -> p -> p\n```"
+    );
+
+    // A reference to a constructor
+    let hover = client.at("textDocument/hover", 6, 30);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```jonla\nsucc : Nat -> Nat\n```"
+    );
+
+    // Nothing to show on a keyword
+    assert_eq!(client.at("textDocument/hover", 0, 1), Value::Null);
+    client.stop();
+}
+
+#[test]
+fn definition() {
+    let mut client = ScriptedClient::start();
+    client.open(PROGRAM);
+
+    let location = client.at("textDocument/definition", 6, 10);
+    assert_eq!(location["uri"], URI);
+    assert_eq!(
+        location["range"],
+        json!({ "start": { "line": 5, "character": 4 }, "end": { "line": 5, "character": 5 } })
+    );
+
+    // The `x` in the body of `id` refers to the lambda binder
+    let location = client.at("textDocument/definition", 4, 51);
+    assert_eq!(
+        location["range"],
+        json!({ "start": { "line": 4, "character": 44 }, "end": { "line": 4, "character": 45 } })
+    );
+
+    // The data type in the type of a constructor
+    let location = client.at("textDocument/definition", 2, 11);
+    assert_eq!(
+        location["range"],
+        json!({ "start": { "line": 0, "character": 5 }, "end": { "line": 0, "character": 8 } })
+    );
+    client.stop();
+}
+
+#[test]
+fn document_symbols() {
+    let mut client = ScriptedClient::start();
+    client.open(PROGRAM);
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let names = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Nat", "zero", "succ", "id", "k", "two"]);
+    assert_eq!(symbols[3]["detail"], "(p : Type) -> p -> p");
+    client.stop();
+}
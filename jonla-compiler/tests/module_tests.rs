@@ -0,0 +1,104 @@
+mod common;
+
+use common::{files, load_path_err};
+use jonla_compiler::lang::module::Loader;
+
+#[test]
+fn qualified_names() {
+    let dir = files(
+        "modules_qualified",
+        &[
+            (
+                "main.jl",
+                "import lib/id.jl\ndef idt : Type -> Type = id.id Type\n",
+            ),
+            (
+                "lib/id.jl",
+                "universe u\ndef id : (a : Type u) -> a -> a = / a : Type u, x : a. x\n",
+            ),
+        ],
+    );
+    let mut loader = Loader::default();
+    let main = loader.load(&dir.join("main.jl")).unwrap();
+    assert_eq!(loader.modules.len(), 2);
+    assert_eq!(&*loader.modules[main].name, "main");
+    let names = loader.modules[main]
+        .defs
+        .iter()
+        .map(|(name, id)| (&**name, &*loader.globals.defs[*id].name))
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![("idt", "main.idt")]);
+}
+
+#[test]
+fn names_are_per_file() {
+    let dir = files(
+        "modules_namespaces",
+        &[
+            ("a.jl", "import b.jl\ndef y : Type = x\n"),
+            ("b.jl", "def x : Type 1 = Type\n"),
+        ],
+    );
+    let err = load_path_err(&dir.join("a.jl"));
+    assert_eq!(err.message, "Unknown variable `x`");
+    assert_eq!(err.path, dir.join("a.jl"));
+}
+
+#[test]
+fn shared_imports_are_loaded_once() {
+    let dir = files(
+        "modules_diamond",
+        &[
+            (
+                "main.jl",
+                "import left.jl\nimport right.jl\ndef t : Type 1 = left.t\n",
+            ),
+            ("left.jl", "import base.jl\ndef t : Type 1 = base.t\n"),
+            ("right.jl", "import base.jl\ndef t : Type 1 = base.t\n"),
+            ("base.jl", "def t : Type 1 = Type\n"),
+        ],
+    );
+    let mut loader = Loader::default();
+    loader.load(&dir.join("main.jl")).unwrap();
+    assert_eq!(loader.modules.len(), 4);
+    assert_eq!(loader.globals.defs.len(), 4);
+}
+
+#[test]
+fn import_cycle() {
+    let dir = files(
+        "modules_cycle",
+        &[
+            ("a.jl", "import b.jl\n"),
+            ("b.jl", "import c.jl\n"),
+            ("c.jl", "def t : Type 1 = Type\nimport a.jl\n"),
+        ],
+    );
+    let err = load_path_err(&dir.join("a.jl"));
+    assert_eq!(err.message, "Import cycle: a.jl -> b.jl -> c.jl -> a.jl");
+    assert_eq!(err.path, dir.join("c.jl"));
+    assert_eq!(err.span, Some((29, 33)));
+}
+
+#[test]
+fn errors_in_imports() {
+    let dir = files(
+        "modules_errors",
+        &[
+            ("missing.jl", "import nope.jl\n"),
+            ("broken.jl", "import parse-error.jl\n"),
+            ("parse-error.jl", "def x : Type =\n"),
+            ("twice.jl", "def x : Type 1 = Type\ndef x : Type 1 = Type\n"),
+        ],
+    );
+    let err = load_path_err(&dir.join("missing.jl"));
+    assert!(err.message.starts_with("Could not import `nope.jl`"));
+    assert_eq!(err.span, Some((7, 14)));
+
+    let err = load_path_err(&dir.join("broken.jl"));
+    assert!(err.message.contains("can't be used as a module name"));
+
+    let err = load_path_err(&dir.join("twice.jl"));
+    assert_eq!(err.message, "`x` is already defined in this module");
+    assert_eq!(err.span, Some((26, 27)));
+}
@@ -0,0 +1,106 @@
+use jonla_compiler::repl;
+
+/// Runs the REPL on `input`, returning everything it printed
+fn session(input: &str) -> String {
+    let mut output = vec![];
+    repl::run(input.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn declarations_and_expressions() {
+    let output = session(
+        "let id : (a : Type 1) -> a -> a = / a : Type 1, x : a. x\n\
+         id (Type -> Type) (id Type)\n",
+    );
+    assert_eq!(
+        output,
+        "> id : (a : Type 1) -> a -> a\n\
+         > / x : Type. x : Type -> Type\n\
+         > "
+    );
+}
+
+#[test]
+fn commands() {
+    let output = session(
+        "let id : (a : Type 1) -> a -> a = / a : Type 1, x : a. x\n\
+         :type id Type\n\
+         :normalize id Type\n\
+         :bogus\n\
+         :quit\n\
+         id\n",
+    );
+    assert_eq!(
+        output,
+        "> id : (a : Type 1) -> a -> a\n\
+         > Type -> Type\n\
+         > / x : Type. x\n\
+         > Unknown command `:bogus`, see `:help`\n\
+         > "
+    );
+}
+
+#[test]
+fn multi_line_input() {
+    let output = session("(/ x : Type,\n y : x. y)\n");
+    assert_eq!(
+        output,
+        "> .. / x : Type, y : x. y : (x : Type) -> x -> x\n> "
+    );
+
+    //An empty line ends an incomplete entry
+    let output = session("/ x : Type.\n\nType\n");
+    assert!(output.starts_with("> .. / x : Type.\n"));
+    assert!(output.ends_with("> Type : Type 1\n> "));
+}
+
+#[test]
+fn errors_keep_context() {
+    let output = session(
+        "let a : Type 1 = Type\n\
+         a a\n\
+         a\n",
+    );
+    assert!(output.contains("Expected a function, but found a term of type `Type 1`"));
+    assert!(output.ends_with("> Type : Type 1\n> "));
+}
+
+#[test]
+fn goals() {
+    let output = session(
+        "let f : Type -> Type = / x : Type. ?b\n\
+         f _\n",
+    );
+    assert_eq!(
+        output,
+        "> let f : Type -> Type = / x : Type. ?b\n\
+         \x20                                  ^^\n\
+         Hole `?b` should have type `Type`\n  x : Type\n\
+         f : Type -> Type\n\
+         > f _\n\
+         \x20 ^\n\
+         Hole `_` should have type `Type`\n\
+         ?b : Type\n\
+         > "
+    );
+}
+
+#[test]
+fn load_and_reload() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/church_and.jl");
+    let output = session(&format!(
+        ":load {}\n\
+         :type proj1 (conj (Type -> Type) Type)\n\
+         :type church_and.proj2\n\
+         :reload\n",
+        path
+    ));
+    assert!(
+        output.contains("conj : {p : Type u} -> {q : Type u} -> p -> q -> church_and.and p q\n")
+    );
+    assert!(output.contains("> Type 1\n"));
+    //The type is shown with `and` folded
+    assert!(output.contains("> {p : Type} -> {q : Type} -> church_and.and p q -> q\n"));
+    assert_eq!(output.matches(&format!("Loaded {}\n", path)).count(), 2);
+}
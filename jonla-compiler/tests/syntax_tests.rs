@@ -0,0 +1,62 @@
+mod common;
+
+use common::{load_err, source_file, spanned};
+use jonla_compiler::autogen::parse::parse_term;
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::pretty_term;
+
+/// Parses the term `src`, and shows it with one binding per `let` and ASCII symbols
+fn pretty(src: &str) -> String {
+    pretty_term(&parse_term(src).inner.unwrap().result)
+}
+
+#[test]
+fn let_blocks() {
+    //The bindings of a `let` are a block, of which every binding starts at the column of the first one
+    assert_eq!(
+        pretty("let a : Type 1 = Type\n    b : Type 1 = a -> a\nb"),
+        "let a : Type 1 = Type\nlet b : Type 1 = a -> a\nb"
+    );
+    assert_eq!(
+        pretty("let a : Type 1 = Type\n    b : Type 1 = let c : Type 1 = a\n                     c -> c\n    c : Type 1 = b -> a\nc"),
+        "let a : Type 1 = Type\nlet b : Type 1 = (let c : Type 1 = a\nc -> c)\nlet c : Type 1 = b -> a\nc"
+    );
+    assert!(parse_term("let a : Type 1 = Type\n   b : Type 1 = a\nb")
+        .inner
+        .is_err());
+}
+
+#[test]
+fn unicode_syntax() {
+    //Identifiers can be in any script, and `→` and `λ` can be written for `->` and `/`
+    assert_eq!(
+        pretty("λ α : Type, β : Type → Type. β α → α"),
+        "/ α : Type, β : Type -> Type. β α -> α"
+    );
+    assert_eq!(
+        pretty("let 型 : Type 1 = Type → Type\nλ f : 型. f"),
+        "let 型 : Type 1 = Type -> Type\n/ f : 型. f"
+    );
+}
+
+#[test]
+fn names_with_lambda() {
+    //`λ` starts a lambda, so it can't start a name, which would be read as a lambda where it is used
+    let err = load_err(
+        "syntax_lambda.jl",
+        "def λa : Type 1 = Type\ndef b : Type 1 = λa\n",
+    );
+    assert_eq!(
+        (err.message.as_str(), spanned(&err)),
+        ("Expected: ' ', Identifier", "λ")
+    );
+    assert_eq!(pretty("λa : Type. a"), "/ a : Type. a");
+    //It can be in a name after the first character
+    let mut loader = Loader::default();
+    loader
+        .load(&source_file(
+            "syntax_lambda_inside.jl",
+            "def aλ : Type 1 = Type\ndef b : Type 1 = aλ\n",
+        ))
+        .unwrap();
+}
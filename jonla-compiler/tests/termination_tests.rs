@@ -0,0 +1,128 @@
+mod common;
+
+use common::{load_err, source_file, spanned, EQ, NAT, PLUS};
+use jonla_compiler::lang::eval::normalize;
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::pretty_core;
+
+/// The natural numbers with `two`, `plus` and equality
+fn prelude() -> String {
+    format!("{}{}{}", NAT, EQ, PLUS)
+}
+
+#[test]
+fn structural_recursion() {
+    let src = format!(
+        "{}def four : Eq Nat (plus two two) (succ (succ two)) = refl Nat (succ (succ two))\n",
+        prelude()
+    );
+    let mut loader = Loader::default();
+    let id = loader
+        .load(&source_file("termination_plus.jl", &src))
+        .unwrap();
+    let globals = &loader.globals;
+    let (_, plus) = loader.modules[id]
+        .defs
+        .iter()
+        .find(|(n, _)| &**n == "plus")
+        .unwrap();
+    assert!(globals.defs[*plus].recursive);
+
+    //A recursive definition is only unfolded when it reduces, so its normal form mentions itself
+    let normal = pretty_core(
+        globals,
+        &[],
+        &normalize(globals, &vec![], &globals.defs[*plus].value),
+    );
+    assert_eq!(
+        normal,
+        "/ m : termination_plus.Nat, n : termination_plus.Nat. termination_plus.Nat.elim (/ _ : termination_plus.Nat. termination_plus.Nat) n \
+         (/ k : termination_plus.Nat, _ : termination_plus.Nat. termination_plus.succ (termination_plus.plus k n)) m"
+    );
+}
+
+#[test]
+fn non_terminating_definitions() {
+    let err = load_err(
+        "termination_loop.jl",
+        &format!(
+            "{}def loop : Nat -> Nat = / n : Nat. loop (succ n)\n",
+            prelude()
+        ),
+    );
+    assert_eq!(spanned(&err), "loop");
+    assert_eq!(
+        err.message,
+        "`loop` may not terminate, as no argument of the recursive call `loop (termination_loop.succ n)` is smaller than the \
+         parameter it is passed for. A parameter gets smaller by taking one of its fields with an eliminator, \
+         definitions that don't have to terminate can be declared `partial`"
+    );
+
+    //Every call has to make the same argument smaller
+    let err = load_err(
+        "termination_swap.jl",
+        &format!(
+            "{}def swap : Nat -> Nat -> Nat = / m : Nat, n : Nat. \
+             Nat.elim (/ _ : Nat. Nat) zero (/ k : Nat, _ : Nat. swap k (Nat.elim (/ _ : Nat. Nat) zero \
+             (/ j : Nat, _ : Nat. swap m j) n)) m\n",
+            prelude()
+        ),
+    );
+    assert_eq!(
+        err.message,
+        "`swap` may not terminate, as the recursive call `swap m j` doesn't make the same argument smaller \
+         as the calls before it"
+    );
+}
+
+#[test]
+fn partial_definitions() {
+    let src = format!(
+        "{}partial def loop : Nat -> Nat = / n : Nat. loop (succ n)\n\
+         partial def start : Nat = loop zero\n",
+        prelude()
+    );
+    let mut loader = Loader::default();
+    let id = loader
+        .load(&source_file("termination_partial.jl", &src))
+        .unwrap();
+    let (_, start) = loader.modules[id]
+        .defs
+        .iter()
+        .find(|(n, _)| &**n == "start")
+        .unwrap();
+    //A partial definition is never unfolded
+    let globals = &loader.globals;
+    let def = &globals.defs[*start];
+    assert!(def.partial);
+    assert_eq!(
+        pretty_core(globals, &[], &normalize(globals, &vec![], &def.value)),
+        "termination_partial.loop termination_partial.zero"
+    );
+
+    let err = load_err(
+        "termination_total.jl",
+        &format!(
+            "{}partial def loop : Nat -> Nat = / n : Nat. loop (succ n)\ndef start : Nat = loop zero\n",
+            prelude()
+        ),
+    );
+    assert_eq!(spanned(&err), "start");
+    assert_eq!(
+        err.message,
+        "`start` uses the partial definition `termination_total.loop`, so it should be declared `partial` as well"
+    );
+
+    let err = load_err(
+        "termination_type.jl",
+        &format!(
+            "{}partial def loop : Nat -> Nat = / n : Nat. loop (succ n)\n\
+             partial def proof : Eq Nat (loop zero) two = proof\n",
+            prelude()
+        ),
+    );
+    assert_eq!(
+        err.message,
+        "The type of `proof` uses the partial definition `termination_type.loop`, which can't be used in types"
+    );
+}
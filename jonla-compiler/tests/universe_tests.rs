@@ -0,0 +1,136 @@
+mod common;
+
+use common::{load_err, source_file};
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::pretty_core;
+
+/// Loads `src`, returning the types of its definitions
+fn types(name: &str, src: &str) -> Vec<String> {
+    let mut loader = Loader::default();
+    let id = loader.load(&source_file(name, src)).unwrap();
+    loader.modules[id]
+        .defs
+        .iter()
+        .map(|(name, id)| {
+            let typ = pretty_core(&loader.globals, &[], &loader.globals.defs[*id].typ);
+            format!("{} : {}", name, typ)
+        })
+        .collect()
+}
+
+#[test]
+fn type_is_not_in_type() {
+    let err = load_err("universe_girard.jl", "def t : Type = Type\n");
+    assert_eq!(
+        err.message,
+        "Type mismatch: expected `Type`, but found `Type 1`"
+    );
+    assert_eq!(err.span, Some((15, 19)));
+
+    //Errors about a universe point at its `Type`
+    let err = load_err("universe_level.jl", "def t : Type 1 = Type 1\n");
+    assert_eq!(
+        err.message,
+        "Type mismatch: expected `Type 1`, but found `Type 2`"
+    );
+    assert_eq!(err.span, Some((17, 21)));
+    assert!(err
+        .render()
+        .contains("def t : Type 1 = Type 1\n                 ^^^^\n"));
+
+    let err = load_err("universe_pi.jl", "def t : Type = (a : Type) -> a\n");
+    assert_eq!(
+        err.message,
+        "Type mismatch: expected `Type`, but found `Type 1`"
+    );
+}
+
+#[test]
+fn cumulativity() {
+    let types = types(
+        "universe_cumulative.jl",
+        "def t : Type 2 = Type\n\
+         def f : Type 1 -> Type 3 = / a : Type 1. Type -> a\n\
+         def g : Type 1 -> Type 4 = f\n",
+    );
+    assert_eq!(
+        types,
+        vec!["t : Type 2", "f : Type 1 -> Type 3", "g : Type 1 -> Type 4"]
+    );
+}
+
+#[test]
+fn polymorphic_definitions() {
+    let types = types(
+        "universe_polymorphic.jl",
+        "universe u v\n\
+         def id : (a : Type u) -> a -> a = / a : Type u, x : a. x\n\
+         def pair : Type u -> Type v -> Type (max (u + 1) (v + 1)) = / a : Type u, b : Type v. (c : Type (max u v)) -> (a -> b -> c) -> c\n\
+         def small : Type -> Type = id (Type -> Type) (/ a : Type. a)\n\
+         def large : Type 1 = id.{2} (Type 1) Type\n\
+         def both : Type 3 = pair Type (Type 1)\n",
+    );
+    assert_eq!(
+        types,
+        vec![
+            "id : (a : Type u) -> a -> a",
+            "pair : Type u -> Type v -> Type (max (u + 1) (v + 1))",
+            "small : Type -> Type",
+            "large : Type 1",
+            "both : Type 3",
+        ]
+    );
+}
+
+#[test]
+fn level_errors() {
+    let err = load_err("universe_unknown.jl", "def t : Type u = Type u\n");
+    assert_eq!(
+        err.message,
+        "Unknown universe `u`, universes are declared with `universe u`"
+    );
+    assert_eq!(err.span, Some((13, 14)));
+
+    let err = load_err(
+        "universe_count.jl",
+        "universe u\ndef id : (a : Type u) -> a -> a = / a : Type u, x : a. x\ndef t : Type 1 = id.{1, 2} Type Type\n",
+    );
+    assert_eq!(
+        err.message,
+        "`id` is polymorphic over 1 universe(s), but 2 level(s) were given"
+    );
+
+    //`pair` would have to be used at a level that contains `Type`, so its result is in `Type 2`
+    let err = load_err(
+        "universe_inconsistent.jl",
+        "universe u\ndef pair : Type u -> Type (u + 1) = / a : Type u. (c : Type u) -> (a -> c) -> c\ndef t : Type 1 = pair Type\n",
+    );
+    assert_eq!(
+        err.message,
+        "Universe inconsistency: `Type 2` should be contained in `Type 1`"
+    );
+
+    let err = load_err("universe_twice.jl", "universe u u\n");
+    assert_eq!(err.message, "The universe `u` is already declared");
+}
+
+#[test]
+fn data_universes() {
+    let err = load_err(
+        "universe_data_field.jl",
+        "data Box : Type {\n    box : Type -> Box\n}\n",
+    );
+    assert_eq!(
+        err.message,
+        "The fields of `box` should be in the universe of `Box`, which is `Type`"
+    );
+
+    let err = load_err(
+        "universe_data_polymorphic.jl",
+        "universe u\ndata List (a : Type u) : Type u {\n    nil : List a\n}\n",
+    );
+    assert_eq!(
+        err.message,
+        "Data types can't be universe polymorphic, but `List` uses `u`"
+    );
+}
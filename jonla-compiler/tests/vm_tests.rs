@@ -0,0 +1,116 @@
+mod common;
+
+use common::{source_file, EQ, NAT, PLUS};
+use jonla_compiler::lang::bytecode::compile;
+use jonla_compiler::lang::eval::normalize;
+use jonla_compiler::lang::module::Loader;
+use jonla_compiler::lang::pretty::pretty_core;
+use jonla_compiler::lang::vm::Vm;
+
+/// Loads `path`, and checks that the VM computes the same value as the evaluator for the definitions in `names`
+fn agrees(path: &std::path::Path, names: &[&str]) {
+    let mut loader = Loader::default();
+    let id = loader.load(path).unwrap();
+    let globals = &loader.globals;
+    let program = compile(globals);
+    let mut vm = Vm::new(&program);
+    for name in names {
+        let (_, id) = loader.modules[id]
+            .defs
+            .iter()
+            .find(|(n, _)| &**n == *name)
+            .unwrap();
+        let expected = pretty_core(
+            globals,
+            &[],
+            &normalize(globals, &vec![], &globals.defs[*id].value),
+        );
+        assert_eq!(
+            vm.global(*id).unwrap().render(globals),
+            expected,
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn data_and_eliminators() {
+    let src = concat!(
+        "data Nat : Type {\n    zero : Nat\n    succ : Nat -> Nat\n}\n",
+        "data Vec (a : Type) : Nat -> Type {\n    nil : Vec a zero\n    cons : (n : Nat) -> a -> Vec a n -> Vec a (succ n)\n}\n",
+        "def one : Nat = succ zero\n",
+        "def plus : Nat -> Nat -> Nat = / m : Nat, n : Nat. ",
+        "Nat.elim (/ _ : Nat. Nat) n (/ k : Nat, _ : Nat. succ (plus k n)) m\n",
+        "def length : (a : Type) -> (n : Nat) -> Vec a n -> Nat = / a : Type. ",
+        "Vec.elim a (/ n : Nat, _ : Vec a n. Nat) zero (/ n : Nat, _ : a, _ : Vec a n, ih : Nat. succ ih)\n",
+        "def three : Nat = plus one (plus one one)\n",
+        "def len : Nat = length Nat (succ one) (cons Nat one three (cons Nat zero one (nil Nat)))\n",
+    );
+    agrees(&source_file("vm_data.jl", src), &["one", "three", "len"]);
+}
+
+#[test]
+fn church_encodings() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/church_and.jl");
+    let src = format!(
+        "import {}\n\
+         data Bool : Type {{\n    true : Bool\n    false : Bool\n}}\n\
+         def pair : church_and.and Bool Bool = church_and.conj true false\n\
+         def first : Bool = church_and.proj1 pair\n\
+         def second : Bool = church_and.proj1 (church_and.swap pair)\n",
+        path
+    );
+    agrees(&source_file("vm_church.jl", &src), &["first", "second"]);
+}
+
+#[test]
+fn larger_numbers() {
+    let src = concat!(
+        "data Nat : Type {\n    zero : Nat\n    succ : Nat -> Nat\n}\n",
+        "data Bool : Type {\n    true : Bool\n    false : Bool\n}\n",
+        "def double : Nat -> Nat = / n : Nat. Nat.elim (/ _ : Nat. Nat) zero (/ _ : Nat, ih : Nat. succ (succ ih)) n\n",
+        "def even : Nat -> Bool = / n : Nat. Nat.elim (/ _ : Nat. Bool) true ",
+        "(/ _ : Nat, ih : Bool. Bool.elim (/ _ : Bool. Bool) false true ih) n\n",
+        "def big : Nat = double (double (double (double (double (double (double (succ zero)))))))\n",
+        "def result : Bool = even (double big)\n",
+    );
+    let mut loader = Loader::default();
+    let id = loader.load(&source_file("vm_numbers.jl", src)).unwrap();
+    let program = compile(&loader.globals);
+    let mut vm = Vm::new(&program);
+    let (_, result) = loader.modules[id].defs.last().unwrap();
+    assert_eq!(
+        vm.global(*result).unwrap().render(&loader.globals),
+        "vm_numbers.true"
+    );
+}
+
+#[test]
+fn proofs_are_erased() {
+    //A proof of an equation holds no information, so it isn't kept, and eliminating it doesn't look at it
+    let src = format!(
+        "{}{}{}def four : Eq Nat (plus two two) (succ (succ two)) = refl Nat (succ (succ two))\n\
+         def cast : (n : Nat) -> Eq Nat two n -> Nat = / n : Nat, p : Eq Nat two n. \
+         Eq.elim Nat two (/ m : Nat, _ : Eq Nat two m. Nat) two n p\n\
+         def cast_two : Nat = cast two (refl Nat two)\n",
+        NAT, EQ, PLUS
+    );
+    let mut loader = Loader::default();
+    let id = loader.load(&source_file("vm_proofs.jl", &src)).unwrap();
+    let program = compile(&loader.globals);
+    let mut vm = Vm::new(&program);
+    let mut value = |name: &str| {
+        let (_, id) = loader.modules[id]
+            .defs
+            .iter()
+            .find(|(n, _)| &**n == name)
+            .unwrap();
+        vm.global(*id).unwrap().render(&loader.globals)
+    };
+    assert_eq!(value("four"), "<erased>");
+    assert_eq!(
+        value("cast_two"),
+        "vm_proofs.succ (vm_proofs.succ vm_proofs.zero)"
+    );
+}
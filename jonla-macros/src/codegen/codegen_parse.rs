@@ -1,9 +1,9 @@
 use crate::codegen::codegen_ast::process_type;
+use crate::codegen::codegen_from_tuples::write_from_tuple_arg;
 use crate::formatting_file::FormattingFile;
-use crate::grammar::{Rule};
+use crate::grammar::{Rule, RuleKind};
 use quote::{format_ident, quote};
 use std::io::Write;
-use crate::codegen::codegen_from_tuples::write_from_tuple_arg;
 
 pub fn write_parsers(mut file: FormattingFile, rules: &Vec<Rule>) {
     write!(
@@ -13,6 +13,7 @@ pub fn write_parsers(mut file: FormattingFile, rules: &Vec<Rule>) {
             use super::ast::*;
             use super::from_tuples::*;
             use jonla_macros::parser::parser_core::*;
+            use jonla_macros::parser::parser_cst::*;
             use jonla_macros::parser::parser_result::*;
             use jonla_macros::parser::parser_rule::*;
             use std::collections::HashMap;
@@ -27,12 +28,13 @@ pub fn write_parsers(mut file: FormattingFile, rules: &Vec<Rule>) {
 }
 
 fn write_parser(file: &mut FormattingFile, rule: &Rule) {
-    if rule.name.starts_with("_") {
+    if rule.name.starts_with("_") || rule.kind != RuleKind::Rule {
         return;
     }
 
     let name_str = rule.name;
     let name = format_ident!("parse_{}", rule.name);
+    let name_cst = format_ident!("parse_{}_cst", rule.name);
     let rtrn = process_type(&rule.rtrn, false);
     let from_action_result = write_from_tuple_arg(&rule.rtrn, quote!(&pr.1), false);
 
@@ -41,11 +43,20 @@ fn write_parser(file: &mut FormattingFile, rule: &Rule) {
         "{}",
         quote! {
             pub fn #name<'input>(input: &'input str) -> ParseResult<'static, #rtrn> {
-                let rules: HashMap<&'static str, RuleBody<'static>> = jonla_macros::read_rules_json(RULES_STR).unwrap();
+                let rules: Vec<Rule<'static>> = jonla_macros::read_rules_json(RULES_STR).unwrap();
+                let rules = Rules::from_grammar(&rules);
                 let mut state: ParserState<'static, 'input, PR<'static>> = ParserState::new(input);
                 let result: ParseResult<'static, PR<'static>> = state.parse_full_input(|s, p| s.parse_rule(p, &rules, #name_str));
                 result.map(|pr| #from_action_result)
             }
+
+            pub fn #name_cst<'input>(input: &'input str) -> ParseResult<'static, SyntaxNode<'static>> {
+                let rules: Vec<Rule<'static>> = jonla_macros::read_rules_json(RULES_STR).unwrap();
+                let rules = Rules::from_grammar(&rules);
+                let mut state: ParserState<'static, 'input, PR<'static>> = ParserState::new_with_cst(input);
+                let result: ParseResult<'static, PR<'static>> = state.parse_full_input(|s, p| s.parse_rule(p, &rules, #name_str));
+                result.map(|pr| SyntaxNode::new_root(GreenNode::new(CstKind::Rule(#name_str), pr.2)))
+            }
         }
     ).unwrap()
 }
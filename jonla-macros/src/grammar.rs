@@ -1,4 +1,6 @@
+use itertools::{Either, Itertools};
 use serde::{Deserialize, Serialize};
+use unicode_general_category::get_general_category;
 
 #[derive(Debug, Clone)]
 pub struct GrammarFile<'input> {
@@ -30,24 +32,240 @@ pub struct Rule<'input> {
     pub name: &'input str,
     pub rtrn: AstType<'input>,
     pub body: RuleBody<'input>,
+    #[serde(default)]
+    pub memo: Memo,
+    #[serde(default)]
+    pub kind: RuleKind,
+    #[serde(default)]
+    pub skip: Skip,
 }
 
+/// Where the skip rule of a grammar is parsed, set with a `#[skip]` or `#[no_skip]` attribute on a rule.
+/// The skip rule is parsed between the elements of the sequences in the other rules, and between repetitions of anything but characters,
+/// so rules don't have to say where whitespace goes.
+/// It isn't parsed next to rules whose name starts with `_` though, which say themselves what goes there.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Skip {
+    #[default]
+    Auto,
+    /// The skip rule of the grammar, of which there is at most one
+    Rule,
+    /// Don't parse the skip rule in this rule, such as in a lexical rule like an identifier
+    Never,
+}
+
+/// An attribute of a rule, see `Memo` and `Skip`
+enum RuleAttr {
+    Memo(Memo),
+    Skip(Skip),
+}
+
+/// What a rule is used for. The token and skip rules are defined in the `lexer` block of a grammar file, which is optional.
+/// With a lexer, the input is split into tokens before it is parsed, see `Rules::has_lexer`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RuleKind {
+    #[default]
+    Rule,
+    /// A kind of token: rules that refer to it match a token of this kind.
+    /// Where more than one kind of token matches, the longest token is taken, or the one that was defined first.
+    Token,
+    /// Matches what is skipped before every token, such as whitespace and comments
+    Skip,
+}
+
+/// Whether the results of a rule are cached, set with a `#[memo]` or `#[no_memo]` attribute on the rule
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Memo {
+    /// Cache the rule, unless it is lexical: it doesn't refer to other rules, so it is cheap to parse again
+    #[default]
+    Auto,
+    Always,
+    /// Never cache the rule, which can't be left-recursive then
+    Never,
+}
+
+/// The characters in `ranges` or with one of `properties` but not in `excluded`, or with `negated`, all other characters.
+/// Written `['a'-'z' | \p{L} | '_']`, `[\p{L} -- 'λ' | 'ƛ']` with excluded characters, or `[^ ...]` when negated.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CharClass {
     pub ranges: Vec<(char, char)>,
+    #[serde(default)]
+    pub properties: Vec<CharProperty>,
+    #[serde(default)]
+    pub excluded: Vec<(char, char)>,
+    #[serde(default)]
+    pub negated: bool,
 }
 
 impl CharClass {
     pub fn contains(&self, c: char) -> bool {
-        self.ranges.iter().any(|range| range.0 <= c && c <= range.1)
+        let in_range =
+            |ranges: &[(char, char)]| ranges.iter().any(|range| range.0 <= c && c <= range.1);
+        let contains = (in_range(&self.ranges)
+            || self.properties.iter().any(|property| property.contains(c)))
+            && !in_range(&self.excluded);
+        contains != self.negated
+    }
+}
+
+/// A Unicode property of characters, written `\p{name}`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CharProperty {
+    /// A general category such as `Lu`, or all categories of a kind such as `L` for letters
+    Category(String),
+    /// The characters that can start an identifier, see Unicode Standard Annex #31
+    XidStart,
+    /// The characters that can continue an identifier
+    XidContinue,
+}
+
+impl CharProperty {
+    /// The property called `name`, if there is one
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "XID_Start" => Some(CharProperty::XidStart),
+            "XID_Continue" => Some(CharProperty::XidContinue),
+            "L" | "Lu" | "Ll" | "Lt" | "Lm" | "Lo" | "M" | "Mn" | "Mc" | "Me" | "N" | "Nd"
+            | "Nl" | "No" | "P" | "Pc" | "Pd" | "Ps" | "Pe" | "Pi" | "Pf" | "Po" | "S" | "Sm"
+            | "Sc" | "Sk" | "So" | "Z" | "Zs" | "Zl" | "Zp" | "C" | "Cc" | "Cf" | "Cs" | "Co"
+            | "Cn" => Some(CharProperty::Category(name.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            CharProperty::Category(category) => category,
+            CharProperty::XidStart => "XID_Start",
+            CharProperty::XidContinue => "XID_Continue",
+        }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            CharProperty::Category(category) => get_general_category(c)
+                .abbreviation()
+                .starts_with(category.as_str()),
+            CharProperty::XidStart => unicode_ident::is_xid_start(c),
+            CharProperty::XidContinue => unicode_ident::is_xid_continue(c),
+        }
+    }
+}
+
+/// Literals of which the longest one at a position is matched, with a trie so they are matched at once.
+/// Written `["->" | "→" | "-"]`, and with an `i` after it, such as `["let" | "in"]i` or `"let"i`, it matches the literals regardless of case.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiteralSet<'input> {
+    #[serde(borrow)]
+    pub literals: Vec<&'input str>,
+    pub case_insensitive: bool,
+    /// The nodes of the trie, of which the root is the first
+    trie: Vec<TrieNode>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TrieNode {
+    /// The nodes after this one by their character, sorted by character
+    children: Vec<(char, usize)>,
+    /// The index of the literal that ends at this node, the first one if it is in the set more than once
+    literal: Option<usize>,
+}
+
+impl<'input> LiteralSet<'input> {
+    pub fn new(literals: Vec<&'input str>, case_insensitive: bool) -> Self {
+        let mut trie = vec![TrieNode::default()];
+        for (i, literal) in literals.iter().enumerate() {
+            let mut node = 0;
+            for c in literal_chars(literal) {
+                let c = if case_insensitive { fold_case(c) } else { c };
+                node = match trie[node].children.binary_search_by_key(&c, |&(c, _)| c) {
+                    Ok(child) => trie[node].children[child].1,
+                    Err(child) => {
+                        trie.push(TrieNode::default());
+                        let next = trie.len() - 1;
+                        trie[node].children.insert(child, (c, next));
+                        next
+                    }
+                };
+            }
+            trie[node].literal.get_or_insert(i);
+        }
+        LiteralSet {
+            literals,
+            case_insensitive,
+            trie,
+        }
+    }
+
+    /// The node after `node` if its next character is `c`, starting from the root `0`
+    pub fn next(&self, node: usize, c: char) -> Option<usize> {
+        let c = if self.case_insensitive {
+            fold_case(c)
+        } else {
+            c
+        };
+        let children = &self.trie[node].children;
+        let child = children.binary_search_by_key(&c, |&(c, _)| c).ok()?;
+        Some(children[child].1)
+    }
+
+    /// The literal that ends at `node`
+    pub fn literal(&self, node: usize) -> Option<&'input str> {
+        self.trie[node].literal.map(|i| self.literals[i])
+    }
+
+    /// The literals that go through `node`, in the order of the set
+    pub fn literals_after(&self, node: usize) -> Vec<&'input str> {
+        let mut found = vec![];
+        let mut todo = vec![node];
+        while let Some(node) = todo.pop() {
+            found.extend(self.trie[node].literal);
+            todo.extend(self.trie[node].children.iter().map(|&(_, child)| child));
+        }
+        found.sort_unstable();
+        found.into_iter().map(|i| self.literals[i]).collect()
+    }
+}
+
+/// The lowercase character of `c`, or `c` itself if that is more than one character
+fn fold_case(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
     }
 }
 
+/// The characters of a literal as it is written in a grammar, with its escapes replaced, see `str_char`
+pub fn literal_chars(literal: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = literal.chars();
+    std::iter::from_fn(move || match chars.next()? {
+        '\\' => Some(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'x' => {
+                let n: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&n, 16).unwrap() as char
+            }
+            'u' => {
+                let n: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                char::from_u32(u32::from_str_radix(&n, 16).unwrap()).unwrap()
+            }
+            c => c,
+        }),
+        c => Some(c),
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RuleBody<'input> {
     Rule(&'input str),
+    /// A rule by its id, which `Rule` is resolved to when the rules are loaded, see `Rules`
+    RuleId(usize),
     CharClass(CharClass),
     Literal(&'input str),
+    LiteralSet(LiteralSet<'input>),
     Repeat {
         expr: Box<RuleBody<'input>>,
         min: u64,
@@ -60,6 +278,14 @@ pub enum RuleBody<'input> {
     Action(Box<RuleBody<'input>>, RuleAction<'input>),
     SliceInput(Box<RuleBody<'input>>),
     Error(Box<RuleBody<'input>>, &'input str),
+    /// Written `~`: if the alternative it is in fails after it, the choice doesn't try its other alternatives
+    Cut,
+    /// Written `@block(e)`: parses `e` as a block of layout, of which the column is where `e` starts
+    Block(Box<RuleBody<'input>>),
+    /// Written `@aligned`: matches nothing, at the column of the innermost block
+    Aligned,
+    /// Written `@indented`: matches nothing, after the column of the innermost block
+    Indented,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,7 +303,9 @@ peg::parser! {
         rule identifier() -> &'input str
             = x: quiet!{$([ 'a'..='z' | 'A'..='Z' | '_' ]['a'..='z' | 'A'..='Z' | '0'..='9' | '_' ]*)} / expected!("identifier")
 
-        pub rule toplevel() -> GrammarFile<'input> = asts:(__ a:ast() __ {a})* __ rules:(__ r:prule() __ {r})* { GrammarFile{ asts, rules } }
+        pub rule toplevel() -> GrammarFile<'input> = asts:(__ a:ast() __ {a})* __ lexer:lexer()? __ rules:(__ r:prule() __ {r})* {
+            GrammarFile{ asts, rules: lexer.unwrap_or_default().into_iter().chain(rules).collect() }
+        }
 
         rule ast() -> Ast<'input> = "ast" _ name:identifier() _ "{" constructors:(__ c:ast_constructor() {c})* __ "}" { Ast { name, constructors } }
         rule ast_constructor() -> AstConstructor<'input> = name:identifier() _ "(" _ args:ast_constructor_arg()**"," _ ")" _ "\n" { AstConstructor{ name, args } }
@@ -88,8 +316,31 @@ peg::parser! {
             r:identifier() { AstType::Ast(r) }
 
         rule prule() -> Rule<'input> =
-            "rule" _ name:identifier() _ "->" _ rtrn:ast_constructor_type() _ "{" __ body:prule_body() __ "}" { Rule{name, rtrn, body } } /
-            "rule" _ name:identifier() _ "->" _ rtrn:ast_constructor_type() _ "=" _ body:prule_body() { Rule{name, rtrn, body } }
+            attrs:prule_attrs() "rule" _ name:identifier() _ "->" _ rtrn:ast_constructor_type() _ "{" __ body:prule_body() __ "}" { Rule{name, rtrn, body, memo: attrs.0, kind: RuleKind::Rule, skip: attrs.1 } } /
+            attrs:prule_attrs() "rule" _ name:identifier() _ "->" _ rtrn:ast_constructor_type() _ "=" _ body:prule_body() { Rule{name, rtrn, body, memo: attrs.0, kind: RuleKind::Rule, skip: attrs.1 } }
+
+        rule lexer() -> Vec<Rule<'input>> = "lexer" _ "{" rs:(__ r:lexer_rule() {r})* __ "}" { rs }
+
+        rule lexer_rule() -> Rule<'input> =
+            kind:lexer_rule_kind() _ name:identifier() _ "{" __ body:prule_body() __ "}" { Rule{name, rtrn: AstType::Input, body, memo: Memo::Never, kind, skip: Skip::Never } } /
+            kind:lexer_rule_kind() _ name:identifier() _ "=" _ body:prule_body() { Rule{name, rtrn: AstType::Input, body, memo: Memo::Never, kind, skip: Skip::Never } }
+
+        rule lexer_rule_kind() -> RuleKind =
+            "token" { RuleKind::Token } /
+            "skip" { RuleKind::Skip }
+
+        rule prule_attrs() -> (Memo, Skip) = attrs:prule_attr()* {
+            attrs.into_iter().fold((Memo::Auto, Skip::Auto), |(memo, skip), attr| match attr {
+                RuleAttr::Memo(memo) => (memo, skip),
+                RuleAttr::Skip(skip) => (memo, skip),
+            })
+        }
+
+        rule prule_attr() -> RuleAttr =
+            "#[memo]" __ { RuleAttr::Memo(Memo::Always) } /
+            "#[no_memo]" __ { RuleAttr::Memo(Memo::Never) } /
+            "#[skip]" __ { RuleAttr::Skip(Skip::Rule) } /
+            "#[no_skip]" __ { RuleAttr::Skip(Skip::Never) }
 
         rule prule_body() -> RuleBody<'input> =
             rs:(r:prule_body_1a())**<2,>(__ "/" __) { RuleBody::Choice(rs) } /
@@ -113,19 +364,44 @@ peg::parser! {
             r:prule_body_3() { r }
         rule prule_body_3() -> RuleBody<'input> =
             name:identifier() { RuleBody::Rule(name) } /
-            "\"" n:$(str_char()*) "\"" { RuleBody::Literal(n) } /
+            "\"" n:$(str_char()*) "\"" i:case_insensitive() {
+                if i { RuleBody::LiteralSet(LiteralSet::new(vec![n], true)) } else { RuleBody::Literal(n) }
+            } /
+            "[" ls:(_ "\"" n:$(str_char()*) "\"" _ {n})++"|" "]" i:case_insensitive() { RuleBody::LiteralSet(LiteralSet::new(ls, i)) } /
             "[" c:charclass() "]" { RuleBody::CharClass(c) } /
             "$" _ "(" _ r:prule_body() _ ")" { RuleBody::SliceInput(box r) } /
+            "~" { RuleBody::Cut } /
+            "@block" _ "(" _ r:prule_body() _ ")" { RuleBody::Block(box r) } /
+            "@aligned" { RuleBody::Aligned } /
+            "@indented" { RuleBody::Indented } /
             "(" _ r:prule_body() _ ")" { r }
 
+        //The `i` right after a literal, which isn't the start of a name that is bound, such as in `"x"i:name`
+        rule case_insensitive() -> bool = "i" !['a'..='z' | 'A'..='Z' | '0'..='9' | '_'] !(_ ":") { true } / { false }
+
         rule prule_action() -> RuleAction<'input> =
             n:identifier() _ "(" args:(prule_action()**(_ "," _)) ")" { RuleAction::Construct(n, args) } /
             "\"" n:$(str_char()*) "\"" { RuleAction::InputLiteral(n) } /
             n:identifier() { RuleAction::Name(n) }
 
-        rule charclass() -> CharClass = rs:(_ r:charclass_part() _ {r})++"|" { CharClass { ranges: rs } }
+        rule charclass() -> CharClass =
+            "^" _ c:charclass_parts() { CharClass { negated: true, ..c } } /
+            charclass_parts()
+
+        rule charclass_parts() -> CharClass = ps:(_ p:charclass_part() _ {p})++"|" excluded:charclass_excluded() {
+            let (ranges, properties) = ps.into_iter().partition_map(|p| p);
+            CharClass { ranges, properties, excluded, negated: false }
+        }
+
+        rule charclass_excluded() -> Vec<(char, char)> =
+            "--" rs:(_ r:charclass_range() _ {r})++"|" { rs } /
+            { vec![] }
+
+        rule charclass_part() -> Either<(char, char), CharProperty> =
+            r:charclass_range() { Either::Left(r) } /
+            "\\p{" n:$(['a'..='z' | 'A'..='Z' | '_']+) "}" {? CharProperty::from_name(n).map(Either::Right).ok_or("Unicode property") }
 
-        rule charclass_part() -> (char, char) =
+        rule charclass_range() -> (char, char) =
             "'" c1:str_char() "'" _ "-" _ "'" c2:str_char() "'"  { (c1, c2) } /
             "'" c:str_char() "'" { (c, c) }
 
@@ -134,6 +410,10 @@ peg::parser! {
             "\\n" { '\n' } /
             "\\r" { '\r' } /
             "\\\"" { '"' } /
-            "\\\'" { '\'' }
+            "\\\'" { '\'' } /
+            "\\t" { '\t' } /
+            "\\\\" { '\\' } /
+            "\\x" n:$(['0'..='9' | 'a'..='f' | 'A'..='F']*<2>) { u8::from_str_radix(n, 16).unwrap() as char } /
+            "\\u{" n:$(['0'..='9' | 'a'..='f' | 'A'..='F']*<1,6>) "}" {? char::from_u32(u32::from_str_radix(n, 16).unwrap()).ok_or("Unicode scalar value") }
     }
 }
@@ -1,3 +1,5 @@
 pub mod parser_core;
+pub mod parser_cst;
+pub mod parser_input;
 pub mod parser_result;
 pub mod parser_rule;
@@ -1,36 +1,359 @@
-use crate::grammar::CharClass;
+use crate::grammar::{literal_chars, CharClass, LiteralSet};
+use crate::parser::parser_input::Input;
 use crate::parser::parser_result::ParseErrorLabel::RemainingInputNotParsed;
-use crate::parser::parser_result::{ParseError, ParseErrorLabel, ParseResult};
-use std::collections::HashMap;
+use crate::parser::parser_result::{ParseError, ParseErrorLabel, ParseResult, Relocate};
+use std::collections::VecDeque;
+use std::io;
+use std::io::Read;
 
 pub struct ParserState<'grm, 'src, CT: Clone> {
-    input: &'src str,
+    input: Input<'src>,
 
-    cache: HashMap<(usize, &'grm str), ParserCacheEntry<'grm, CT>>,
-    cache_stack: Vec<(usize, &'grm str)>,
+    /// The memoized rule results, by position from `cache_offset` on and then by the id of the rule.
+    /// A column is only allocated once a rule is memoized at its position.
+    cache: VecDeque<Vec<Option<ParserCacheEntry<'grm, CT>>>>,
+    /// The position of the first column of the cache, the columns before it were evicted
+    cache_offset: usize,
+    /// The number of entries that were put in the cache so far, which orders them
+    cache_stamp: usize,
+    /// The number of entries in the cache
+    cache_len: usize,
+    /// The largest number of entries that were in the cache at once
+    cache_peak: usize,
+    /// The number of times a rule was parsed because its result wasn't in the cache
+    rules_parsed: usize,
+
+    /// Whether a lossless concrete syntax tree should be built while parsing
+    pub(crate) cst: bool,
+    /// Whether the lexer is running, so rules match characters instead of tokens, see `Rules::has_lexer`
+    pub(crate) lexing: bool,
+
+    /// The position after the furthest character that was looked at so far
+    examined: usize,
+
+    /// Whether cache entries that can't be used anymore are removed, see `with_eviction`
+    evict: bool,
+    /// The positions the parser can go back to when what it is parsing fails, such as choices that have alternatives left.
+    /// A position is pushed while parsing something after it, so they are in increasing order.
+    backtrack: Vec<Backtrack>,
+    /// The number of backtrack points when the rule that is being parsed started, a cut only commits the points after it
+    cut_base: usize,
+    /// The rules that are being parsed, by position and id
+    active: Vec<(usize, usize)>,
+    /// The start positions of the slices of the input that are being parsed, see `keep_push`
+    keep: Vec<usize>,
+
+    /// The columns of the blocks that are being parsed, innermost last, see `parse_block`
+    blocks: Vec<usize>,
+    /// The fewest blocks that were open where the rule that is being parsed looked at the column of a block, see `parse_column`.
+    /// If that block was open before the rule started, its cache entry depends on the column of that block.
+    block_used: usize,
+    /// Whether the parser looked at the column of a position, which changes with an edit before it on its line, see `apply_edit`
+    layout: bool,
 }
 
 pub struct ParserCacheEntry<'grm, CT: Clone> {
     read: bool,
     value: ParseResult<'grm, CT>,
+    /// The position after the furthest character that was looked at while parsing this entry
+    examined: usize,
+    /// When the entry was put in the cache, see `ParserState::cache_revert`
+    stamp: usize,
+    /// The column of the innermost block around the entry if it depends on it, so it is only used in blocks with that column
+    block: Option<usize>,
+}
+
+/// A position the parser can go back to, see `ParserState::backtrack_push`
+struct Backtrack {
+    pos: usize,
+    /// Whether the position is the start of a choice, rather than the end of a repetition
+    choice: bool,
+    /// Whether a cut was passed since, so the parser won't go back to it
+    cut: bool,
+}
+
+/// Describes that the input in `start..old_end` was replaced by `new_len` bytes of new input.
+#[derive(Clone, Copy, Debug)]
+pub struct TextEdit {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_len: usize,
 }
 
 impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
     pub fn new(input: &'src str) -> Self {
+        Self::with_input(Input::Str(input))
+    }
+
+    /// Create a parser for binary input, which matches every byte as the character with the same value, see `Input`
+    pub fn new_bytes(input: &'src [u8]) -> Self {
+        Self::with_input(Input::Bytes(input))
+    }
+
+    /// Create a parser that reads its input from `reader` while parsing.
+    /// Only the part of the input that the parser can still go back to is kept in memory, see `with_eviction`,
+    /// so the input can be much larger than the memory. The values of the parse hold their own text, see `ActionResult::Text`.
+    ///
+    /// A memory-mapped file doesn't need to be read, it can be parsed with `new` and `with_eviction`.
+    /// If reading fails, the input ends there, and the error is available from `read_error`.
+    pub fn from_reader(reader: impl Read + 'src) -> Self {
+        Self::with_input(Input::stream(reader)).with_eviction()
+    }
+
+    fn with_input(input: Input<'src>) -> Self {
         ParserState {
             input,
-            cache: HashMap::new(),
-            cache_stack: Vec::new(),
+            cache: VecDeque::new(),
+            cache_offset: 0,
+            cache_stamp: 0,
+            cache_len: 0,
+            cache_peak: 0,
+            rules_parsed: 0,
+            cst: false,
+            lexing: false,
+            examined: 0,
+            evict: false,
+            backtrack: vec![],
+            cut_base: 0,
+            active: vec![],
+            keep: vec![],
+            blocks: vec![],
+            block_used: usize::MAX,
+            layout: false,
+        }
+    }
+
+    /// Create a parser that also records every matched token, see `parser_cst`
+    pub fn new_with_cst(input: &'src str) -> Self {
+        ParserState {
+            cst: true,
+            ..Self::new(input)
+        }
+    }
+
+    /// Makes the parser remove cache entries before the positions it can still go back to,
+    /// so the cache only holds entries for the part of the input it is working on.
+    /// Memory use is then bounded by how far the parser can backtrack, instead of by the size of the input.
+    pub fn with_eviction(mut self) -> Self {
+        self.evict = true;
+        self
+    }
+
+    /// The number of rule results in the cache
+    pub fn cache_entries(&self) -> usize {
+        self.cache_len
+    }
+
+    /// The largest number of rule results that were in the cache at once
+    pub fn cache_peak(&self) -> usize {
+        self.cache_peak
+    }
+
+    /// The number of times a rule was parsed so far because its result wasn't in the cache.
+    /// After `apply_edit`, only the rules of which the results were removed are parsed again.
+    pub fn rules_parsed(&self) -> usize {
+        self.rules_parsed
+    }
+
+    /// The largest number of bytes of input that were in memory at once
+    pub fn input_peak(&self) -> usize {
+        self.input.peak_in_memory()
+    }
+
+    /// The error that ended the input early, if it was read from a stream that failed
+    pub fn read_error(&mut self) -> Option<io::Error> {
+        self.input.take_error()
+    }
+
+    /// Whether the input is read from a stream, see `from_reader`
+    pub fn is_stream(&self) -> bool {
+        matches!(self.input, Input::Stream(_))
+    }
+
+    /// The input in `start..end`, which the parser should still be able to go back to
+    pub fn input_slice(&self, start: usize, end: usize) -> &[u8] {
+        self.input.slice(start, end)
+    }
+
+    /// Whether the input in `start..end` is `literal`, which is written as in a grammar, see `literal_chars`
+    pub fn input_is_literal(&mut self, start: usize, end: usize, literal: &str) -> bool {
+        let mut pos = start;
+        for c in literal_chars(literal) {
+            match self.input.symbol_at(pos) {
+                Some((s, len)) if s == c && pos + len <= end => pos += len,
+                _ => return false,
+            }
+        }
+        pos == end
+    }
+
+    /// Whether the input in `start..end` is one of the literals of `set`
+    pub fn input_is_literal_set(
+        &mut self,
+        start: usize,
+        end: usize,
+        set: &LiteralSet<'grm>,
+    ) -> bool {
+        let (literal, _, _) = self.walk_literal_set(start, set, end);
+        literal.is_some_and(|(_, literal_end)| literal_end == end)
+    }
+
+    /// Keeps the input from `pos` on in memory, until `keep_pop` is called
+    pub fn keep_push(&mut self, pos: usize) {
+        self.keep.push(pos);
+    }
+
+    pub fn keep_pop(&mut self) {
+        self.keep.pop();
+    }
+
+    /// Marks `pos` as a position that the parser can go back to, until `backtrack_pop` is called.
+    /// `choice` tells whether the position is the start of a choice, which is what a cut commits to, see `cut`.
+    pub fn backtrack_push(&mut self, pos: usize, choice: bool) {
+        self.backtrack.push(Backtrack {
+            pos,
+            choice,
+            cut: false,
+        });
+    }
+
+    pub fn backtrack_pop(&mut self) {
+        self.backtrack.pop();
+    }
+
+    /// Parses a rule with `sub`, so the cuts in it only commit to the choices in the rule itself
+    pub fn parse_rule_scope<T>(&mut self, sub: impl FnOnce(&mut Self) -> T) -> T {
+        let cut_base = std::mem::replace(&mut self.cut_base, self.backtrack.len());
+        let res = sub(self);
+        self.cut_base = cut_base;
+        res
+    }
+
+    /// Passes a cut at `pos`: the parser won't go back to the innermost choice in the current rule,
+    /// nor to the repetitions inside that choice, so the cache entries before `pos` might not be needed anymore.
+    /// If what comes after the cut fails, so does that choice, see `parse_choice`.
+    pub fn cut(&mut self, pos: usize) {
+        for point in self.backtrack[self.cut_base..].iter_mut().rev() {
+            point.cut = true;
+            if point.choice {
+                break;
+            }
+        }
+        if self.evict {
+            self.evict_before(self.evict_boundary(pos));
+        }
+    }
+
+    /// Whether a cut was passed since the innermost backtrack point of the current rule was pushed
+    pub fn committed(&self) -> bool {
+        self.backtrack.len() > self.cut_base && self.backtrack.last().is_some_and(|point| point.cut)
+    }
+
+    /// The position before which the parser won't look at the cache or the input anymore, when it is at `pos`
+    fn evict_boundary(&self, pos: usize) -> usize {
+        let mut boundary = self
+            .backtrack
+            .iter()
+            .find(|point| !point.cut)
+            .map_or(pos, |point| point.pos.min(pos));
+        if let Some(&keep) = self.keep.first() {
+            boundary = boundary.min(keep);
+        }
+        //A left-recursive rule goes back to its position to grow its seed, see `parse_cache_recurse`
+        for &(p, rule) in &self.active {
+            if p >= boundary {
+                break;
+            }
+            if self.cache_is_read((p, rule)) {
+                return p;
+            }
         }
+        boundary
+    }
+
+    /// Removes the cache columns and the input before `pos`.
+    /// The rules that are being parsed before `pos` aren't read, so they don't need their entries anymore.
+    fn evict_before(&mut self, pos: usize) {
+        while self.cache_offset < pos {
+            let Some(column) = self.cache.pop_front() else {
+                self.cache_offset = pos;
+                break;
+            };
+            self.cache_len -= column.into_iter().flatten().count();
+            self.cache_offset += 1;
+        }
+        self.input.discard_before(pos);
     }
 
     pub fn parse_charclass(&mut self, pos: usize, cc: &CharClass) -> ParseResult<'grm, ()> {
-        match self.input[pos..].chars().next() {
-            Some(c) if cc.contains(c) => ParseResult::new_ok((), pos + c.len_utf8()),
+        self.examined = self.examined.max(pos + 1);
+        match self.input.symbol_at(pos) {
+            Some((c, len)) if cc.contains(c) => ParseResult::new_ok((), pos + len),
             _ => ParseResult::new_err(pos, vec![ParseErrorLabel::CharClass(cc.clone())]),
         }
     }
 
+    /// Matches the characters of `literal` at `pos`, failing where one doesn't match
+    pub fn parse_literal(&mut self, pos: usize, literal: &'grm str) -> ParseResult<'grm, ()> {
+        let mut end = pos;
+        for c in literal_chars(literal) {
+            self.examined = self.examined.max(end + 1);
+            match self.input.symbol_at(end) {
+                Some((s, len)) if s == c => end += len,
+                _ => return ParseResult::new_err(end, vec![ParseErrorLabel::Error(literal)]),
+            }
+        }
+        ParseResult::new_ok((), end)
+    }
+
+    /// Matches the longest literal of `set` at `pos`, returning it.
+    /// Where none matches, the error expects the literals that matched the most input.
+    pub fn parse_literal_set(
+        &mut self,
+        pos: usize,
+        set: &LiteralSet<'grm>,
+    ) -> ParseResult<'grm, &'grm str> {
+        match self.walk_literal_set(pos, set, usize::MAX) {
+            (Some((literal, end)), _, _) => ParseResult::new_ok(literal, end),
+            (None, end, node) => ParseResult::new_err(
+                end,
+                set.literals_after(node)
+                    .into_iter()
+                    .map(ParseErrorLabel::Error)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Follows the trie of `set` through the input from `pos`, not past `limit`.
+    /// Returns the longest literal that matched and its end, and the position and node where it stopped.
+    fn walk_literal_set(
+        &mut self,
+        pos: usize,
+        set: &LiteralSet<'grm>,
+        limit: usize,
+    ) -> (Option<(&'grm str, usize)>, usize, usize) {
+        let (mut end, mut node) = (pos, 0);
+        let mut longest = None;
+        loop {
+            if let Some(literal) = set.literal(node) {
+                longest = Some((literal, end));
+            }
+            self.examined = self.examined.max(end + 1);
+            match self.input.symbol_at(end) {
+                Some((c, len)) if end + len <= limit => match set.next(node, c) {
+                    Some(next) => {
+                        node = next;
+                        end += len;
+                    }
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        (longest, end, node)
+    }
+
     ///
     /// Recovery:
     /// - If left matched zero input, don't bother continuing, we'll succeed higher up
@@ -47,7 +370,10 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
                     Ok(ok_right) => ParseResult::new_ok_with_err(
                         (ok_left.result, ok_right.result),
                         ok_right.pos,
-                        ok_right.best_error,
+                        ParseError::combine_option_parse_error(
+                            ok_left.best_error,
+                            ok_right.best_error,
+                        ),
                     ),
                     Err(err_right) => ParseResult::from_err(
                         ParseError::combine_option_parse_error(ok_left.best_error, Some(err_right))
@@ -70,6 +396,8 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
                 //TODO in case left terminated early, we should run right side and add to best error
                 ParseResult::from_ok(ok_left)
             }
+            //A cut was passed, so the parser commits to the left side
+            Err(err_left) if self.committed() => ParseResult::from_err(err_left),
             Err(err_left) => {
                 let res_right: ParseResult<T> = right(self, pos);
                 match res_right.inner {
@@ -86,51 +414,106 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
         }
     }
 
-    fn cache_is_read(&self, key: (usize, &'grm str)) -> Option<bool> {
-        self.cache.get(&key).map(|v| v.read)
+    fn cache_entry(
+        &mut self,
+        (pos, rule): (usize, usize),
+    ) -> Option<&mut ParserCacheEntry<'grm, CT>> {
+        self.cache
+            .get_mut(pos.checked_sub(self.cache_offset)?)?
+            .get_mut(rule)?
+            .as_mut()
     }
 
-    fn cache_get(&mut self, key: (usize, &'grm str)) -> Option<&ParseResult<'grm, CT>> {
-        if let Some(v) = self.cache.get_mut(&key) {
-            v.read = true;
-            Some(&v.value)
-        } else {
-            None
-        }
+    /// Whether the entry was used since it was put in the cache.
+    /// Evicted entries weren't, as the parser won't come back to their position.
+    fn cache_is_read(&self, (pos, rule): (usize, usize)) -> bool {
+        pos.checked_sub(self.cache_offset)
+            .and_then(|i| self.cache.get(i)?.get(rule)?.as_ref())
+            .is_some_and(|v| v.read)
     }
 
-    fn cache_insert(&mut self, key: (usize, &'grm str), value: ParseResult<'grm, CT>) {
-        self.cache
-            .insert(key, ParserCacheEntry { read: false, value });
-        self.cache_stack.push(key);
+    fn cache_get(&mut self, (pos, rule): (usize, usize)) -> Option<&ParseResult<'grm, CT>> {
+        let column = self.block_column();
+        let v = self
+            .cache
+            .get_mut(pos.checked_sub(self.cache_offset)?)?
+            .get_mut(rule)?
+            .as_mut()
+            .filter(|v| v.block.is_none_or(|block| block == column))?;
+        v.read = true;
+        self.examined = self.examined.max(v.examined);
+        if v.block.is_some() {
+            self.block_used = self.block_used.min(self.blocks.len());
+        }
+        Some(&v.value)
     }
 
-    fn cache_state_get(&self) -> usize {
-        self.cache_stack.len()
+    /// Puts `value` in the cache, unless its position was evicted already
+    fn cache_insert(&mut self, (pos, rule): (usize, usize), value: ParseResult<'grm, CT>) {
+        let Some(i) = pos.checked_sub(self.cache_offset) else {
+            return;
+        };
+        if self.cache.len() <= i {
+            self.cache.resize_with(i + 1, Vec::new);
+        }
+        let column = &mut self.cache[i];
+        if column.len() <= rule {
+            column.resize_with(rule + 1, || None);
+        }
+        if column[rule].is_none() {
+            self.cache_len += 1;
+            self.cache_peak = self.cache_peak.max(self.cache_len);
+        }
+        column[rule] = Some(ParserCacheEntry {
+            read: false,
+            value,
+            examined: self.examined,
+            stamp: self.cache_stamp,
+            block: None,
+        });
+        self.cache_stamp += 1;
     }
 
-    fn cache_state_revert(&mut self, state: usize) {
-        self.cache_stack.drain(state..).for_each(|key| {
-            self.cache.remove(&key);
-        })
+    /// Removes the entries at `pos` that were put in the cache since `stamp`.
+    /// Only entries at the position of a left-recursive rule can depend on its seed, as parsing never moves backwards,
+    /// so the entries at other positions stay valid while the seed grows.
+    fn cache_revert(&mut self, pos: usize, stamp: usize) {
+        let column = pos
+            .checked_sub(self.cache_offset)
+            .and_then(|i| self.cache.get_mut(i));
+        for entry in column.into_iter().flatten() {
+            if matches!(entry, Some(e) if e.stamp >= stamp) {
+                *entry = None;
+                self.cache_len -= 1;
+            }
+        }
     }
 
     pub fn parse_cache_recurse(
         &mut self,
         pos: usize,
         sub: impl Fn(&mut ParserState<'grm, 'src, CT>, usize) -> ParseResult<'grm, CT>,
-        id: &'grm str,
+        id: usize,
     ) -> ParseResult<'grm, CT> {
         //Check if this result is cached
         let key = (pos, id);
         if let Some(cached) = self.cache_get(key) {
             return cached.clone();
         }
+        self.rules_parsed += 1;
+
+        //Keep track of how far this rule looks, separately from the rules around it
+        let examined_outer = self.examined;
+        self.examined = pos;
+        //Likewise for the blocks it looks at
+        let blocks = self.blocks.len();
+        let block_used_outer = std::mem::replace(&mut self.block_used, usize::MAX);
 
         //Before executing, put a value for the current position in the cache.
         //This value is used if the rule is left-recursive
-        let cache_state = self.cache_state_get();
+        let cache_stamp = self.cache_stamp;
         self.cache_insert(key, ParseResult::new_err_leftrec(pos));
+        self.active.push(key);
 
         //Now execute the actual rule, taking into account left recursion
         //The way this is done is heavily inspired by http://web.cs.ucla.edu/~todd/research/pepm08.pdf
@@ -140,10 +523,10 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
         //- Put the new seed in the cache, and rerun on the current (rule, position). Make sure to revert the cache to the previous state.
         //- At some point, the above will fail. Either because no new input is parsed, or because the entire parse now failed. At this point, we have reached the maximum size.
         let res = sub(self, pos);
-        match res.inner {
+        let res = match res.inner {
             Ok(mut ok) => {
-                //Did our rule left-recurse? (Safety: We just inserted it)
-                if !self.cache_is_read(key).unwrap() {
+                //Did our rule left-recurse?
+                if !self.cache_is_read(key) {
                     //No leftrec, cache and return
                     let res = ParseResult::from_ok(ok);
                     self.cache_insert(key, res.clone());
@@ -152,8 +535,10 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
                     //There was leftrec, we need to grow the seed
                     loop {
                         //Insert the current seed into the cache
-                        self.cache_state_revert(cache_state);
+                        self.cache_revert(pos, cache_stamp);
                         self.cache_insert(key, ParseResult::from_ok(ok.clone()));
+                        //While growing, the parser comes back here, so this position is kept, see `evict_boundary`
+                        self.cache_entry(key).unwrap().read = true;
 
                         //Grow the seed
                         let new_res = sub(self, pos);
@@ -168,15 +553,21 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
                     }
 
                     //The seed is at its maximum size
-                    //It should still be in the cache,
+                    //It should still be in the cache, but it may have looked further while growing
+                    self.cache_entry(key).unwrap().examined = self.examined;
                     ParseResult::from_ok(ok)
                 }
             }
             Err(err) => {
                 // Left recursion value was used, but did not make a seed.
-                // This is an illegal grammar!
-                if self.cache_is_read(key).unwrap() {
-                    return ParseResult::new_err_leftrec(pos);
+                // Either the grammar is illegal, or the input doesn't match any alternative.
+                // Keep the error of the alternatives, so it points to where the input went wrong.
+                if self.cache_is_read(key) {
+                    let mut err = err;
+                    err.left_recursion_warning = true;
+                    let res = ParseResult::from_err(err);
+                    self.cache_insert(key, res.clone());
+                    res
                 } else {
                     //Not ok, but seed was not used. This is just normal error.
                     //Insert into cache then return
@@ -185,16 +576,85 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
                     res
                 }
             }
+        };
+
+        self.examined = self.examined.max(examined_outer);
+        if self.block_used <= blocks {
+            let column = self.block_column();
+            if let Some(entry) = self.cache_entry(key) {
+                entry.block = Some(column);
+            }
+        }
+        self.block_used = self.block_used.min(block_used_outer);
+        self.active.pop();
+        //The parser continues after this rule, unless it goes back
+        if self.evict && res.is_ok() {
+            self.evict_before(self.evict_boundary(res.pos()));
+        }
+        res
+    }
+
+    /// Parses `sub` as a block of layout, of which the column is the column of `pos`.
+    /// In the block, `parse_aligned` and `parse_indented` compare columns to it, such as to find where the items of the block start.
+    /// Outside of blocks, the column is 0.
+    pub fn parse_block<T: Clone>(
+        &mut self,
+        pos: usize,
+        sub: impl FnOnce(&mut ParserState<'grm, 'src, CT>, usize) -> ParseResult<'grm, T>,
+    ) -> ParseResult<'grm, T> {
+        self.layout = true;
+        let column = self.input.column(pos);
+        self.blocks.push(column);
+        let result = sub(self, pos);
+        self.blocks.pop();
+        result
+    }
+
+    /// Matches nothing at `pos`, but only at the column of the innermost block, where its next item starts
+    pub fn parse_aligned(&mut self, pos: usize) -> ParseResult<'grm, ()> {
+        self.parse_column(
+            pos,
+            |column, block| column == block,
+            ParseErrorLabel::Aligned,
+        )
+    }
+
+    /// Matches nothing at `pos`, but only after the column of the innermost block, where its current item continues
+    pub fn parse_indented(&mut self, pos: usize) -> ParseResult<'grm, ()> {
+        self.parse_column(
+            pos,
+            |column, block| column > block,
+            ParseErrorLabel::Indented,
+        )
+    }
+
+    fn parse_column(
+        &mut self,
+        pos: usize,
+        accept: impl FnOnce(usize, usize) -> bool,
+        label: fn(usize) -> ParseErrorLabel<'grm>,
+    ) -> ParseResult<'grm, ()> {
+        self.layout = true;
+        self.block_used = self.block_used.min(self.blocks.len());
+        let block = self.block_column();
+        if accept(self.input.column(pos), block) {
+            ParseResult::new_ok((), pos)
+        } else {
+            ParseResult::new_err(pos, vec![label(block)])
         }
     }
 
+    fn block_column(&self) -> usize {
+        self.blocks.last().copied().unwrap_or(0)
+    }
+
     pub fn parse_full_input<T: Clone>(
         &mut self,
         sub: impl Fn(&mut ParserState<'grm, 'src, CT>, usize) -> ParseResult<'grm, T>,
     ) -> ParseResult<'grm, T> {
         let res = sub(self, 0);
         match res.inner {
-            Ok(ok) if res.pos() == self.input.len() => ParseResult::from_ok(ok),
+            Ok(ok) if self.input.is_end(res.pos()) => ParseResult::from_ok(ok),
             Ok(ok) => ok
                 .best_error
                 .map(ParseResult::from_err)
@@ -203,3 +663,54 @@ impl<'grm, 'src, CT: Clone> ParserState<'grm, 'src, CT> {
         }
     }
 }
+
+impl<'grm, 'src, CT: Clone + Relocate> ParserState<'grm, 'src, CT> {
+    /// Replace the input by an edited version of it, so the next parse can reuse the work of the previous one.
+    /// The new input should be parsed with the same rules as before.
+    ///
+    /// Cache entries that only looked at input before the edit are kept as is,
+    /// entries that only looked at input after the edit are kept and moved to their new position,
+    /// all other entries are removed.
+    /// If the parser looked at columns, the entries after the edit on its line are removed as well, as their columns changed.
+    pub fn apply_edit(&mut self, input: &'src str, edit: TextEdit) {
+        let delta = edit.new_len as isize - (edit.old_end - edit.start) as isize;
+        //The end of the line of the edit, up to which the columns changed
+        let edit_end = edit.start + edit.new_len;
+        let line_end = self.layout.then(|| {
+            input[edit_end..]
+                .find('\n')
+                .map_or(input.len(), |i| edit_end + i)
+        });
+        let mut cache: VecDeque<Vec<_>> = VecDeque::new();
+        for (i, mut column) in std::mem::take(&mut self.cache).into_iter().enumerate() {
+            let pos = self.cache_offset + i;
+            let new_pos = if pos >= edit.old_end {
+                (pos as isize + delta) as usize
+            } else {
+                pos
+            };
+            if cache.len() <= new_pos {
+                cache.resize_with(new_pos + 1, Vec::new);
+            }
+            if pos >= edit.old_end && line_end.is_some_and(|end| new_pos <= end) {
+                //Its column changed
+            } else if pos >= edit.old_end {
+                for entry in column.iter_mut().flatten() {
+                    entry.value.relocate(delta);
+                    entry.examined = (entry.examined as isize + delta) as usize;
+                }
+                cache[new_pos] = column;
+            } else if pos <= edit.start {
+                cache[pos] = column
+                    .into_iter()
+                    .map(|entry| entry.filter(|entry| entry.examined <= edit.start))
+                    .collect();
+            }
+        }
+        self.cache_len = cache.iter().flatten().flatten().count();
+        self.cache = cache;
+        self.cache_offset = 0;
+        self.input = Input::Str(input);
+        self.examined = 0;
+    }
+}
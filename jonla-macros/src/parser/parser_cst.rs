@@ -0,0 +1,234 @@
+use itertools::Itertools;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// The kind of a node or token in the concrete syntax tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CstKind<'grm> {
+    /// A node for an invocation of the rule with this name
+    Rule(&'grm str),
+    /// A token matched by a literal in the grammar
+    Literal(&'grm str),
+    /// A token matched by one or more consecutive character classes
+    Text,
+    /// A token matched by a rule whose name starts with `_`, such as whitespace or comments, or by a skip rule of the lexer
+    Trivia(&'grm str),
+    /// A token of the lexer of this kind, see `RuleKind::Token`
+    Token(&'grm str),
+}
+
+/// A position independent node, only storing its length.
+/// Green nodes can be shared between trees, see `SyntaxNode` for a node with a position.
+#[derive(Clone, Debug)]
+pub struct GreenNode<'grm> {
+    pub kind: CstKind<'grm>,
+    pub len: usize,
+    pub children: Vec<GreenElement<'grm>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GreenToken<'grm> {
+    pub kind: CstKind<'grm>,
+    pub len: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum GreenElement<'grm> {
+    Node(Rc<GreenNode<'grm>>),
+    Token(GreenToken<'grm>),
+}
+
+impl<'grm> GreenNode<'grm> {
+    pub fn new(kind: CstKind<'grm>, children: Vec<GreenElement<'grm>>) -> Rc<Self> {
+        let len = children.iter().map(|c| c.len()).sum();
+        Rc::new(GreenNode {
+            kind,
+            len,
+            children,
+        })
+    }
+}
+
+impl<'grm> GreenElement<'grm> {
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(n) => n.len,
+            GreenElement::Token(t) => t.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Appends `elements` to `into`.
+/// Empty tokens are dropped, and adjacent `Text` tokens are merged into a single token.
+pub fn push_green<'grm>(into: &mut Vec<GreenElement<'grm>>, elements: Vec<GreenElement<'grm>>) {
+    for element in elements {
+        match (into.last_mut(), element) {
+            (_, GreenElement::Token(t)) if t.len == 0 => {}
+            (
+                Some(GreenElement::Token(GreenToken {
+                    kind: CstKind::Text,
+                    len,
+                })),
+                GreenElement::Token(GreenToken {
+                    kind: CstKind::Text,
+                    len: new_len,
+                }),
+            ) => *len += new_len,
+            (_, element) => into.push(element),
+        }
+    }
+}
+
+/// A node in the concrete syntax tree, together with its absolute position in the source.
+#[derive(Clone, Debug)]
+pub struct SyntaxNode<'grm> {
+    green: Rc<GreenNode<'grm>>,
+    offset: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxToken<'grm> {
+    green: GreenToken<'grm>,
+    offset: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement<'grm> {
+    Node(SyntaxNode<'grm>),
+    Token(SyntaxToken<'grm>),
+}
+
+impl<'grm> SyntaxNode<'grm> {
+    pub fn new_root(green: Rc<GreenNode<'grm>>) -> Self {
+        SyntaxNode { green, offset: 0 }
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode<'grm>> {
+        &self.green
+    }
+
+    pub fn kind(&self) -> &CstKind<'grm> {
+        &self.green.kind
+    }
+
+    /// The span `(start, end)` of this node in the source
+    pub fn text_range(&self) -> (usize, usize) {
+        (self.offset, self.offset + self.green.len)
+    }
+
+    pub fn text<'src>(&self, src: &'src str) -> &'src str {
+        &src[self.offset..self.offset + self.green.len]
+    }
+
+    pub fn children(&self) -> Vec<SyntaxElement<'grm>> {
+        let mut offset = self.offset;
+        self.green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(n) => SyntaxElement::Node(SyntaxNode {
+                        green: n.clone(),
+                        offset,
+                    }),
+                    GreenElement::Token(t) => SyntaxElement::Token(SyntaxToken {
+                        green: t.clone(),
+                        offset,
+                    }),
+                };
+                offset += child.len();
+                element
+            })
+            .collect()
+    }
+
+    /// All tokens in this node, in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken<'grm>> {
+        let mut tokens = vec![];
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(n) => tokens.append(&mut n.tokens()),
+                SyntaxElement::Token(t) => tokens.push(t),
+            }
+        }
+        tokens
+    }
+
+    /// Shows the tree, one element per line, in the style of `Kind@start..end "text"`
+    pub fn debug_dump(&self, src: &str) -> String {
+        let mut out = String::new();
+        self.debug_dump_indent(src, 0, &mut out);
+        out
+    }
+
+    fn debug_dump_indent(&self, src: &str, indent: usize, out: &mut String) {
+        let (s, e) = self.text_range();
+        writeln!(
+            out,
+            "{: <4$}{}@{}..{}",
+            "",
+            show_kind(self.kind()),
+            s,
+            e,
+            indent
+        )
+        .unwrap();
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(n) => n.debug_dump_indent(src, indent + 2, out),
+                SyntaxElement::Token(t) => {
+                    let (s, e) = t.text_range();
+                    writeln!(
+                        out,
+                        "{: <5$}{}@{}..{} {:?}",
+                        "",
+                        show_kind(t.kind()),
+                        s,
+                        e,
+                        t.text(src),
+                        indent + 2
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+}
+
+impl<'grm> SyntaxToken<'grm> {
+    pub fn kind(&self) -> &CstKind<'grm> {
+        &self.green.kind
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.green.kind, CstKind::Trivia(_))
+    }
+
+    /// The span `(start, end)` of this token in the source
+    pub fn text_range(&self) -> (usize, usize) {
+        (self.offset, self.offset + self.green.len)
+    }
+
+    pub fn text<'src>(&self, src: &'src str) -> &'src str {
+        &src[self.offset..self.offset + self.green.len]
+    }
+}
+
+fn show_kind(kind: &CstKind) -> String {
+    match kind {
+        CstKind::Rule(name) => name.to_string(),
+        CstKind::Literal(lit) => format!("{:?}", lit),
+        CstKind::Text => "Text".to_string(),
+        CstKind::Trivia(name) => format!("Trivia({})", name),
+        CstKind::Token(kind) => format!("Token({})", kind),
+    }
+}
+
+/// Reconstructs the source text covered by the given tokens.
+pub fn tokens_to_string(tokens: &[SyntaxToken], src: &str) -> String {
+    tokens.iter().map(|t| t.text(src)).join("")
+}
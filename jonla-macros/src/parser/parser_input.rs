@@ -0,0 +1,194 @@
+use std::io;
+use std::io::Read;
+
+/// The number of bytes that are read from a stream at once
+const READ_SIZE: usize = 8 * 1024;
+
+/// The input of a parser, which is either entirely in memory or read from a stream while parsing.
+///
+/// The parser matches the input a symbol at a time, see `symbol_at`:
+/// text is split into characters, and bytes into bytes, which match the character with the same value.
+/// So a grammar for bytes uses the characters up to `\xff` for them.
+pub enum Input<'src> {
+    Str(&'src str),
+    Bytes(&'src [u8]),
+    Stream(Stream<'src>),
+}
+
+/// Text that is read while parsing, of which only a window is kept in memory
+pub struct Stream<'src> {
+    reader: Box<dyn Read + 'src>,
+    /// The input from `offset` on that was read so far
+    window: String,
+    offset: usize,
+    /// The column of `offset`, as the start of its line may be discarded
+    offset_column: usize,
+    /// Bytes that were read after the window, which don't form a whole character yet
+    pending: Vec<u8>,
+    /// Whether the whole stream was read
+    eof: bool,
+    /// The error that ended the stream early
+    error: Option<io::Error>,
+    /// The largest size the window had
+    peak: usize,
+}
+
+impl<'src> Input<'src> {
+    pub fn stream(reader: impl Read + 'src) -> Self {
+        Input::Stream(Stream {
+            reader: Box::new(reader),
+            window: String::new(),
+            offset: 0,
+            offset_column: 0,
+            pending: vec![],
+            eof: false,
+            error: None,
+            peak: 0,
+        })
+    }
+
+    /// The symbol at `pos` and its length, which should not be discarded yet
+    #[inline]
+    pub fn symbol_at(&mut self, pos: usize) -> Option<(char, usize)> {
+        let c = match self {
+            Input::Str(input) => input[pos..].chars().next(),
+            Input::Bytes(input) => return input.get(pos).map(|&b| (b as char, 1)),
+            Input::Stream(stream) => {
+                while pos - stream.offset >= stream.window.len() && stream.read() {}
+                stream.window[pos - stream.offset..].chars().next()
+            }
+        };
+        c.map(|c| (c, c.len_utf8()))
+    }
+
+    /// Whether `pos` is the end of the input
+    pub fn is_end(&mut self, pos: usize) -> bool {
+        self.symbol_at(pos).is_none()
+    }
+
+    /// The number of symbols between the start of the line of `pos` and `pos`, of which `pos` should not be discarded yet
+    pub fn column(&self, pos: usize) -> usize {
+        match self {
+            Input::Str(input) => {
+                let line = &input[..pos];
+                line[line.rfind('\n').map_or(0, |i| i + 1)..]
+                    .chars()
+                    .count()
+            }
+            Input::Bytes(input) => {
+                let line = &input[..pos];
+                pos - line.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+            }
+            Input::Stream(stream) => {
+                let line = &stream.window[..pos - stream.offset];
+                match line.rfind('\n') {
+                    Some(i) => line[i + 1..].chars().count(),
+                    None => stream.offset_column + line.chars().count(),
+                }
+            }
+        }
+    }
+
+    /// The bytes in `start..end`, which should not be discarded yet.
+    /// Text is UTF-8, but binary input doesn't have to be, so this gives the raw bytes.
+    pub fn slice(&self, start: usize, end: usize) -> &[u8] {
+        match self {
+            Input::Str(input) => &input.as_bytes()[start..end],
+            Input::Bytes(input) => &input[start..end],
+            Input::Stream(stream) => {
+                &stream.window.as_bytes()[start - stream.offset..end - stream.offset]
+            }
+        }
+    }
+
+    /// Lets go of the input before `pos`, which the parser won't look at anymore
+    pub fn discard_before(&mut self, pos: usize) {
+        if let Input::Stream(stream) = self {
+            //Moving the rest of the window is only worth it once it is at least as large as what is discarded
+            let len = pos.saturating_sub(stream.offset);
+            if len >= READ_SIZE && len >= stream.window.len() / 2 {
+                let discarded = &stream.window[..len];
+                stream.offset_column = match discarded.rfind('\n') {
+                    Some(i) => discarded[i + 1..].chars().count(),
+                    None => stream.offset_column + discarded.chars().count(),
+                };
+                stream.window.drain(..len);
+                stream.offset = pos;
+            }
+        }
+    }
+
+    /// The largest number of bytes of input that were in memory at once
+    pub fn peak_in_memory(&self) -> usize {
+        match self {
+            Input::Str(input) => input.len(),
+            Input::Bytes(input) => input.len(),
+            Input::Stream(stream) => stream.peak,
+        }
+    }
+
+    /// The error that ended the stream early, if any
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        match self {
+            Input::Str(_) | Input::Bytes(_) => None,
+            Input::Stream(stream) => stream.error.take(),
+        }
+    }
+}
+
+impl Stream<'_> {
+    /// Reads more of the stream into the window, returning whether there was more to read
+    fn read(&mut self) -> bool {
+        if self.eof {
+            return false;
+        }
+        let start = self.pending.len();
+        self.pending.resize(start + READ_SIZE, 0);
+        let read = loop {
+            match self.reader.read(&mut self.pending[start..]) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                read => break read,
+            }
+        };
+        match read {
+            Ok(0) => {
+                self.pending.truncate(start);
+                self.eof = true;
+                if !self.pending.is_empty() {
+                    self.fail(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "stream ends in the middle of a character",
+                    ));
+                }
+                false
+            }
+            Ok(n) => {
+                self.pending.truncate(start + n);
+                let valid = match std::str::from_utf8(&self.pending) {
+                    Ok(text) => text.len(),
+                    Err(err) if err.error_len().is_none() => err.valid_up_to(),
+                    Err(err) => {
+                        self.fail(io::Error::new(io::ErrorKind::InvalidData, err));
+                        err.valid_up_to()
+                    }
+                };
+                self.window
+                    .push_str(std::str::from_utf8(&self.pending[..valid]).unwrap());
+                self.pending.drain(..valid);
+                self.peak = self.peak.max(self.window.len());
+                true
+            }
+            Err(err) => {
+                self.pending.truncate(start);
+                self.fail(err);
+                false
+            }
+        }
+    }
+
+    /// Ends the stream because of `error`
+    fn fail(&mut self, error: io::Error) {
+        self.eof = true;
+        self.error = Some(error);
+    }
+}
@@ -120,6 +120,34 @@ impl<'grm, O: Clone> ParseResult<'grm, O> {
     }
 }
 
+/// Values that contain positions in the input, which move when the input is edited.
+pub trait Relocate {
+    /// Move all positions in this value by `delta`
+    fn relocate(&mut self, delta: isize);
+}
+
+impl<'grm, O: Clone + Relocate> Relocate for ParseResult<'grm, O> {
+    fn relocate(&mut self, delta: isize) {
+        match &mut self.inner {
+            Ok(ok) => {
+                ok.result.relocate(delta);
+                ok.pos = (ok.pos as isize + delta) as usize;
+                if let Some(err) = &mut ok.best_error {
+                    err.relocate(delta);
+                }
+            }
+            Err(err) => err.relocate(delta),
+        }
+    }
+}
+
+impl Relocate for ParseError<'_> {
+    fn relocate(&mut self, delta: isize) {
+        self.pos = (self.pos as isize + delta) as usize;
+        self.start = self.start.map(|start| (start as isize + delta) as usize);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ParseOk<'grm, O: Clone> {
     pub result: O,
@@ -170,6 +198,12 @@ pub enum ParseErrorLabel<'grm> {
     /// No attempt was even made
     RemainingInputNotParsed,
     Error(&'grm str),
+    /// A token of this kind, see `RuleKind::Token`
+    Token(&'grm str),
+    /// Input at the column of the innermost block, see `ParserState::parse_aligned`
+    Aligned(usize),
+    /// Input after the column of the innermost block, see `ParserState::parse_indented`
+    Indented(usize),
 }
 
 impl Display for ParseErrorLabel<'_> {
@@ -181,23 +215,33 @@ impl Display for ParseErrorLabel<'_> {
                         '\t' => "\\t".to_string(),
                         '\n' => "\\n".to_string(),
                         ' ' => "' '".to_string(),
+                        c if c.is_control() => format!("\\x{:02x}", c as u32),
                         c => c.to_string(),
                     }
                 }
+                fn show_range((s, e): &(char, char)) -> String {
+                    if *s == *e {
+                        show_char(*s)
+                    } else {
+                        format!("{}-{}", show_char(*s), show_char(*e))
+                    }
+                }
+                if cc.negated {
+                    write!(f, "^")?;
+                }
                 write!(
                     f,
                     "{}",
                     cc.ranges
                         .iter()
-                        .map(|(s, e)| {
-                            if *s == *e {
-                                format!("{}", show_char(*s))
-                            } else {
-                                format!("{}-{}", show_char(*s), show_char(*e))
-                            }
-                        })
+                        .map(show_range)
+                        .chain(cc.properties.iter().map(|p| format!("\\p{{{}}}", p.name())))
                         .format(" ")
-                )
+                )?;
+                if !cc.excluded.is_empty() {
+                    write!(f, " -- {}", cc.excluded.iter().map(show_range).format(" "))?;
+                }
+                Ok(())
             }
             ParseErrorLabel::RemainingInputNotParsed => {
                 write!(f, "No Parse Attempt")
@@ -205,12 +249,26 @@ impl Display for ParseErrorLabel<'_> {
             ParseErrorLabel::Error(err) => {
                 write!(f, "{}", err)
             }
+            ParseErrorLabel::Token(kind) => {
+                write!(f, "{}", kind)
+            }
+            ParseErrorLabel::Aligned(column) => {
+                write!(f, "column {}", column + 1)
+            }
+            ParseErrorLabel::Indented(column) => {
+                write!(f, "a column after {}", column + 1)
+            }
         }
     }
 }
 
 impl<'grm> ParseError<'grm> {
     pub fn display(&self, src: &str) {
+        print!("{}", self.render(src));
+    }
+
+    /// Shows the line of `src` where the error occurred, with the error underlined and explained below it
+    pub fn render(&self, src: &str) -> String {
         let start = self.start.unwrap_or(self.pos);
         let mut start_nl = start;
         while let Some(c) = src[..start_nl].chars().rev().next() {
@@ -234,14 +292,36 @@ impl<'grm> ParseError<'grm> {
             (self.pos + 1, self.pos)
         };
 
-        println!("{}", &src[start_nl..end_excl_nl]);
-        print!("{: <1$}", "", start - start_nl);
-        println!("{:^<1$}", "", end_excl - start);
+        let mut out = format!(
+            "{}\n{: <3$}{:^<4$}\n",
+            &src[start_nl..end_excl_nl],
+            "",
+            "",
+            start - start_nl,
+            end_excl - start
+        );
+        if let Some(message) = self.message() {
+            out.push_str(&message);
+            out.push('\n');
+        }
+        out
+    }
 
+    /// The explanation of this error, as shown below the source by `display`
+    pub fn message(&self) -> Option<String> {
         if self.labels.len() > 0 {
-            println!("Expected: {}", self.labels.iter().format(", "))
+            Some(format!(
+                "Expected: {}",
+                self.labels
+                    .iter()
+                    .map(|l| l.to_string())
+                    .unique()
+                    .format(", ")
+            ))
         } else if self.left_recursion_warning {
-            println!("Warning: Left recursion failed here.")
+            Some("Warning: Left recursion failed here.".to_string())
+        } else {
+            None
         }
     }
 }
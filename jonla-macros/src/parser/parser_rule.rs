@@ -1,17 +1,27 @@
-use crate::grammar::{CharClass, RuleAction, RuleBody};
+use crate::grammar::{AstType, Memo, Rule, RuleAction, RuleBody, RuleKind, Skip};
 use crate::parser::parser_core::ParserState;
-use crate::parser::parser_result::{ParseErrorLabel, ParseResult};
+use crate::parser::parser_cst::{push_green, CstKind, GreenElement, GreenNode, GreenToken};
+use crate::parser::parser_result::{ParseErrorLabel, ParseResult, Relocate};
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-pub type PR<'grm> = (HashMap<&'grm str, ActionResult<'grm>>, ActionResult<'grm>);
+/// The bound names, the value, and (if enabled) the concrete syntax tree elements of a parse
+pub type PR<'grm> = (
+    HashMap<&'grm str, ActionResult<'grm>>,
+    ActionResult<'grm>,
+    Vec<GreenElement<'grm>>,
+);
 
+/// The value of a parse. Cached values are cloned every time they are reused, so the children are shared.
 #[derive(Clone)]
 pub enum ActionResult<'grm> {
     Value((usize, usize)),
+    /// The text of a `Value` of a stream, which is discarded while parsing, see `ParserState::from_reader`
+    Text(Rc<str>),
     Literal(&'grm str),
-    Construct(&'grm str, Vec<ActionResult<'grm>>),
-    List(Vec<ActionResult<'grm>>),
+    Construct(&'grm str, Rc<Vec<ActionResult<'grm>>>),
+    List(Rc<Vec<ActionResult<'grm>>>),
     Error,
 }
 
@@ -19,6 +29,7 @@ impl<'grm> ActionResult<'grm> {
     pub fn to_string<'src>(&self, src: &'src str) -> String {
         match self {
             ActionResult::Value((s, e)) => format!("\'{}\'", &src[*s..*e]),
+            ActionResult::Text(text) => format!("\'{}\'", text),
             ActionResult::Literal(lit) => format!("\'{}\'", lit.to_string()),
             ActionResult::Construct(c, es) => format!(
                 "{}({})",
@@ -33,56 +44,390 @@ impl<'grm> ActionResult<'grm> {
     }
 }
 
+impl Relocate for ActionResult<'_> {
+    fn relocate(&mut self, delta: isize) {
+        match self {
+            ActionResult::Value((s, e)) => {
+                *s = (*s as isize + delta) as usize;
+                *e = (*e as isize + delta) as usize;
+            }
+            ActionResult::Construct(_, es) | ActionResult::List(es) => {
+                Rc::make_mut(es).iter_mut().for_each(|e| e.relocate(delta))
+            }
+            ActionResult::Text(_) | ActionResult::Literal(_) | ActionResult::Error => {}
+        }
+    }
+}
+
+/// The concrete syntax tree only stores lengths, so only the action results need to move
+impl Relocate for PR<'_> {
+    fn relocate(&mut self, delta: isize) {
+        self.0.values_mut().for_each(|v| v.relocate(delta));
+        self.1.relocate(delta);
+    }
+}
+
+/// The rules of a grammar, where every rule has an id so rules refer to each other without looking up their names
+pub struct Rules<'grm> {
+    names: Vec<&'grm str>,
+    ids: HashMap<&'grm str, usize>,
+    bodies: Vec<RuleBody<'grm>>,
+    /// Whether the results of every rule are cached, see `Memo`
+    memo: Vec<bool>,
+    kinds: Vec<RuleKind>,
+    /// The ids of the token and skip rules of the lexer, in the order they were defined
+    tokens: Vec<usize>,
+    skips: Vec<usize>,
+}
+
+impl<'grm> Rules<'grm> {
+    /// Gives every rule an id, and resolves the rules that the bodies refer to by name to their id.
+    /// Which rules are cached is decided automatically, see `Memo::Auto`.
+    pub fn new(rules: impl IntoIterator<Item = (&'grm str, RuleBody<'grm>)>) -> Self {
+        Self::build(rules.into_iter().map(|(name, body)| Rule {
+            name,
+            rtrn: AstType::Input,
+            body,
+            memo: Memo::Auto,
+            kind: RuleKind::Rule,
+            skip: Skip::Auto,
+        }))
+    }
+
+    /// Like `new`, for the rules of a grammar file, which can say whether they are cached, define a lexer and have a skip rule
+    pub fn from_grammar(rules: &[Rule<'grm>]) -> Self {
+        Self::build(rules.iter().cloned())
+    }
+
+    fn build(rules: impl IntoIterator<Item = Rule<'grm>>) -> Self {
+        let rules = rules.into_iter().collect_vec();
+        let lexer = |kind| {
+            rules
+                .iter()
+                .filter(|r| r.kind == kind)
+                .map(|r| r.name)
+                .collect_vec()
+        };
+        let (tokens, skips) = (lexer(RuleKind::Token), lexer(RuleKind::Skip));
+        let rules = rules.into_iter().sorted_by_key(|r| r.name).collect_vec();
+        let ids: HashMap<&'grm str, usize> =
+            rules.iter().enumerate().map(|(i, r)| (r.name, i)).collect();
+        let skip_rules = rules.iter().filter(|r| r.skip == Skip::Rule).collect_vec();
+        if skip_rules.len() > 1 {
+            panic!(
+                "There can only be one skip rule, but there are {}",
+                skip_rules.iter().map(|r| r.name).format(", ")
+            );
+        }
+        let skip_rule = skip_rules.first().map(|r| ids[r.name]);
+        let mut names = vec![];
+        let mut bodies = vec![];
+        let mut memo = vec![];
+        let mut kinds = vec![];
+        for rule in rules {
+            //Whether a rule is lexical is about the rule as it is written, without the skip rule
+            let lexical = is_lexical(&rule.body);
+            let skip = match rule.skip {
+                Skip::Auto => skip_rule,
+                Skip::Rule | Skip::Never => None,
+            };
+            names.push(rule.name);
+            memo.push(match rule.memo {
+                Memo::Auto => !lexical,
+                Memo::Always => true,
+                Memo::Never => false,
+            });
+            bodies.push(resolve(rule.body, &ids, skip));
+            kinds.push(rule.kind);
+        }
+        Rules {
+            tokens: tokens.into_iter().map(|name| ids[name]).collect(),
+            skips: skips.into_iter().map(|name| ids[name]).collect(),
+            names,
+            ids,
+            bodies,
+            memo,
+            kinds,
+        }
+    }
+
+    /// Whether the grammar has a lexer, see `RuleKind`.
+    /// Rules then match tokens: a token rule matches a token of its kind, and a literal matches a token with its text.
+    /// What the lexer matches itself, and the character classes in rules, are matched on characters.
+    pub fn has_lexer(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// The id under which the lexer caches the token at a position, see `ParserState::parse_token`
+    fn lexer_id(&self) -> usize {
+        self.len()
+    }
+
+    /// The id of the rule named `name`
+    pub fn id(&self, name: &str) -> usize {
+        *self
+            .ids
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown rule `{}`", name))
+    }
+
+    pub fn name(&self, id: usize) -> &'grm str {
+        self.names[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl<'grm> FromIterator<(&'grm str, RuleBody<'grm>)> for Rules<'grm> {
+    fn from_iter<I: IntoIterator<Item = (&'grm str, RuleBody<'grm>)>>(rules: I) -> Self {
+        Rules::new(rules)
+    }
+}
+
+/// Resolves the rules that `body` refers to by name to their id, and puts the `skip` rule between the elements of its sequences, see `Skip`
+fn resolve<'grm>(
+    body: RuleBody<'grm>,
+    ids: &HashMap<&'grm str, usize>,
+    skip: Option<usize>,
+) -> RuleBody<'grm> {
+    let sub = |body: Box<RuleBody<'grm>>| Box::new(resolve(*body, ids, skip));
+    let is_trivia = |b: &RuleBody| matches!(b, RuleBody::Rule(name) if name.starts_with('_'));
+    match body {
+        RuleBody::Rule(name) => RuleBody::RuleId(
+            *ids.get(name)
+                .unwrap_or_else(|| panic!("Unknown rule `{}`", name)),
+        ),
+        RuleBody::Repeat {
+            expr,
+            min,
+            max,
+            delim,
+        } => {
+            let delim = match skip {
+                //Repeated characters, such as the digits of a number, stay together
+                Some(skip) if !is_lexical(&expr) && !is_trivia(&expr) => {
+                    match resolve(*delim, ids, Some(skip)) {
+                        RuleBody::Sequence(seq) if seq.is_empty() => RuleBody::RuleId(skip),
+                        delim => RuleBody::Sequence(vec![
+                            RuleBody::RuleId(skip),
+                            delim,
+                            RuleBody::RuleId(skip),
+                        ]),
+                    }
+                }
+                _ => resolve(*delim, ids, skip),
+            };
+            RuleBody::Repeat {
+                expr: sub(expr),
+                min,
+                max,
+                delim: Box::new(delim),
+            }
+        }
+        RuleBody::Sequence(subs) => {
+            let mut seq = vec![];
+            let mut prev_trivia = None;
+            for b in subs {
+                //A cut doesn't match anything, so the skip rule goes where it would go without it
+                if let RuleBody::Cut = b {
+                    seq.push(b);
+                    continue;
+                }
+                let trivia = is_trivia(&b);
+                if let Some(skip) = skip {
+                    if prev_trivia == Some(false) && !trivia {
+                        seq.push(RuleBody::RuleId(skip));
+                    }
+                }
+                //A column is checked where the next element starts, so nothing is skipped after it
+                prev_trivia = Some(trivia || matches!(b, RuleBody::Aligned | RuleBody::Indented));
+                seq.push(resolve(b, ids, skip));
+            }
+            RuleBody::Sequence(seq)
+        }
+        RuleBody::Choice(subs) => {
+            RuleBody::Choice(subs.into_iter().map(|b| resolve(b, ids, skip)).collect())
+        }
+        RuleBody::NameBind(name, body) => RuleBody::NameBind(name, sub(body)),
+        RuleBody::Action(body, action) => RuleBody::Action(sub(body), action),
+        RuleBody::SliceInput(body) => RuleBody::SliceInput(sub(body)),
+        RuleBody::Error(body, label) => RuleBody::Error(sub(body), label),
+        RuleBody::Block(body) => RuleBody::Block(sub(body)),
+        body @ (RuleBody::RuleId(_)
+        | RuleBody::CharClass(_)
+        | RuleBody::Literal(_)
+        | RuleBody::LiteralSet(_)
+        | RuleBody::Cut
+        | RuleBody::Aligned
+        | RuleBody::Indented) => body,
+    }
+}
+
+/// Whether `body` doesn't refer to any rule
+fn is_lexical(body: &RuleBody) -> bool {
+    match body {
+        RuleBody::Rule(_) | RuleBody::RuleId(_) => false,
+        RuleBody::CharClass(_)
+        | RuleBody::Literal(_)
+        | RuleBody::LiteralSet(_)
+        | RuleBody::Cut
+        | RuleBody::Aligned
+        | RuleBody::Indented => true,
+        RuleBody::Repeat { expr, delim, .. } => is_lexical(expr) && is_lexical(delim),
+        RuleBody::Sequence(subs) | RuleBody::Choice(subs) => subs.iter().all(is_lexical),
+        RuleBody::NameBind(_, body)
+        | RuleBody::Action(body, _)
+        | RuleBody::SliceInput(body)
+        | RuleBody::Error(body, _)
+        | RuleBody::Block(body) => is_lexical(body),
+    }
+}
+
 impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
+    /// Parses `rule` at `pos`. With a lexer, what is skipped after the last token is parsed as well.
     pub fn parse_rule(
         &mut self,
         pos: usize,
-        rules: &HashMap<&'grm str, RuleBody<'grm>>,
-        rule: &'grm str,
+        rules: &Rules<'grm>,
+        rule: &str,
     ) -> ParseResult<'grm, PR<'grm>> {
-        self.parse_cache_recurse(
-            pos,
-            |s, p| s.parse_expr(p, rules, &rules.get(rule).unwrap()),
-            rule,
-        )
+        let result = self.parse_rule_id(pos, rules, rules.id(rule));
+        if !rules.has_lexer() {
+            return result;
+        }
+        match result.inner {
+            Ok(mut ok) => {
+                self.lexing = true;
+                let (end, trivia) = self.skip(ok.pos, rules);
+                self.lexing = false;
+                push_green(&mut ok.result.2, trivia);
+                ok.pos = end;
+                ParseResult::from_ok(ok)
+            }
+            Err(err) => ParseResult::from_err(err),
+        }
+    }
+
+    fn parse_rule_id(
+        &mut self,
+        pos: usize,
+        rules: &Rules<'grm>,
+        rule: usize,
+    ) -> ParseResult<'grm, PR<'grm>> {
+        //The names bound in a rule are not visible outside of it, so they don't have to be cached.
+        //Neither are its cuts, the choices around the rule can still try their other alternatives.
+        let sub = |s: &mut Self, p| {
+            s.parse_rule_scope(|s| s.parse_expr(p, rules, &rules.bodies[rule]))
+                .map(|(_, v, green)| (HashMap::new(), v, green))
+        };
+        //The rules that the lexer uses may also be used on tokens, so they are not cached while lexing
+        if rules.memo[rule] && !self.lexing {
+            self.parse_cache_recurse(pos, sub, rule)
+        } else {
+            sub(self, pos)
+        }
     }
 
     pub fn parse_expr(
         &mut self,
         pos: usize,
-        rules: &HashMap<&'grm str, RuleBody<'grm>>,
+        rules: &Rules<'grm>,
         expr: &RuleBody<'grm>,
     ) -> ParseResult<'grm, PR<'grm>> {
         match expr {
-            RuleBody::Rule(rule) => self
-                .parse_rule(pos, rules, rule)
-                .map(|(_, v)| (HashMap::new(), v)),
+            RuleBody::Rule(rule) => self.parse_expr(pos, rules, &RuleBody::RuleId(rules.id(rule))),
+            &RuleBody::RuleId(id) if rules.kinds[id] == RuleKind::Token && !self.lexing => {
+                let kind = rules.name(id);
+                self.parse_token_if(pos, rules, vec![ParseErrorLabel::Token(kind)], |_, k, _| {
+                    k == kind
+                })
+            }
+            RuleBody::Literal(literal) if rules.has_lexer() && !self.lexing => {
+                let labels = vec![ParseErrorLabel::Error(literal)];
+                self.parse_token_if(pos, rules, labels, |s, _, span| {
+                    s.input_is_literal(span.0, span.1, literal)
+                })
+            }
+            RuleBody::LiteralSet(set) if rules.has_lexer() && !self.lexing => {
+                let labels = set
+                    .literals
+                    .iter()
+                    .map(|l| ParseErrorLabel::Error(l))
+                    .collect();
+                self.parse_token_if(pos, rules, labels, |s, _, span| {
+                    s.input_is_literal_set(span.0, span.1, set)
+                })
+            }
+            &RuleBody::RuleId(id) => {
+                let cst = self.cst;
+                let rule = rules.name(id);
+                self.parse_rule_id(pos, rules, id)
+                    .map_with_pos(|(_, v, children), new_pos| {
+                        let green = if !cst {
+                            vec![]
+                        } else if rule.starts_with('_') {
+                            vec![GreenElement::Token(GreenToken {
+                                kind: CstKind::Trivia(rule),
+                                len: new_pos - pos,
+                            })]
+                        } else {
+                            vec![GreenElement::Node(GreenNode::new(
+                                CstKind::Rule(rule),
+                                children,
+                            ))]
+                        };
+                        (HashMap::new(), v, green)
+                    })
+            }
             RuleBody::CharClass(cc) => {
                 let result = self.parse_charclass(pos, cc);
+                let cst = self.cst;
                 result.map_with_pos(|_, new_pos| {
-                    (HashMap::new(), ActionResult::Value((pos, new_pos)))
+                    (
+                        HashMap::new(),
+                        self.input_value(pos, new_pos),
+                        cst_token(cst, CstKind::Text, new_pos - pos),
+                    )
                 })
             }
             RuleBody::Literal(literal) => {
-                let mut state = ParseResult::new_ok((), pos);
-                for char in literal.chars() {
-                    state = self
-                        .parse_sequence(state, |s, p| {
-                            s.parse_charclass(
-                                p,
-                                &CharClass {
-                                    ranges: vec![(char, char)],
-                                },
-                            )
-                        })
-                        .map(|_| ());
-                }
-                state
+                let cst = self.cst;
+                //The text of a literal doesn't need to be kept from a stream, unless it is written with escapes
+                let literal_value = self.is_stream() && !literal.contains('\\');
+                self.parse_literal(pos, literal)
                     .map_with_pos(|_, new_pos| {
-                        (HashMap::new(), ActionResult::Value((pos, new_pos)))
+                        (
+                            HashMap::new(),
+                            if literal_value {
+                                ActionResult::Literal(literal)
+                            } else {
+                                self.input_value(pos, new_pos)
+                            },
+                            cst_token(cst, CstKind::Literal(literal), new_pos - pos),
+                        )
+                    })
+                    .map_errs(|mut err| {
+                        err.start = Some(pos);
+                        err
+                    })
+            }
+            RuleBody::LiteralSet(set) => {
+                let cst = self.cst;
+                self.parse_literal_set(pos, set)
+                    .map_with_pos(|literal, new_pos| {
+                        (
+                            HashMap::new(),
+                            self.input_value(pos, new_pos),
+                            cst_token(cst, CstKind::Literal(literal), new_pos - pos),
+                        )
                     })
                     .map_errs(|mut err| {
-                        err.labels = vec![ParseErrorLabel::Error(literal)];
                         err.start = Some(pos);
                         err
                     })
@@ -93,7 +438,7 @@ impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
                 max,
                 delim,
             } => {
-                let mut state = ParseResult::new_ok((HashMap::new(), ()), pos);
+                let mut state = ParseResult::new_ok((HashMap::new(), vec![]), pos);
                 let mut results = vec![];
 
                 //Parse minimum amount, this is mandatory so just make it a sequence
@@ -101,20 +446,26 @@ impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
                     //Parse delim
                     if i != 0 {
                         let res = self.parse_sequence(state, |s, p| s.parse_expr(p, rules, delim));
-                        state = res.map(|(l, _)| l)
+                        state = res.map(|(mut l, r)| {
+                            push_green(&mut l.1, r.2);
+                            l
+                        })
                     }
 
                     //Parse expr
                     let res =
                         self.parse_sequence(state.clone(), |s, p| s.parse_expr(p, rules, expr));
-                    state = res.map(|(l, r)| {
+                    state = res.map(|(mut l, r)| {
                         results.push(r.1);
+                        push_green(&mut l.1, r.2);
                         l
                     });
                 }
 
                 if state.is_ok() {
                     for i in *min..max.unwrap_or(u64::MAX) {
+                        //If this repetition fails, the parser goes back to the end of the previous one
+                        self.backtrack_push(state.pos(), false);
                         let mut state_new = state.clone();
 
                         //Parse delim
@@ -122,7 +473,10 @@ impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
                             let res = self.parse_sequence(state_new.clone(), |s, p| {
                                 s.parse_expr(p, rules, delim)
                             });
-                            state_new = res.map(|(l, _)| l)
+                            state_new = res.map(|(mut l, r)| {
+                                push_green(&mut l.1, r.2);
+                                l
+                            })
                         }
 
                         //Parse expr
@@ -130,8 +484,9 @@ impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
                             .parse_sequence(state_new.clone(), |s, p| s.parse_expr(p, rules, expr));
 
                         //Update results
-                        let state_new = state_new.map(|(l, r)| {
+                        let state_new = state_new.map(|(mut l, r)| {
                             results.push(r.1);
+                            push_green(&mut l.1, r.2);
                             l
                         });
 
@@ -141,6 +496,12 @@ impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
                             assert_eq!(p, old_pos);
                             state
                         });
+                        self.backtrack_pop();
+
+                        //This repetition failed after a cut, so the whole repetition fails
+                        if !state.is_ok() {
+                            break;
+                        }
 
                         //If no progress was made, stop.
                         //TODO: More complicated notion of progress?
@@ -150,28 +511,31 @@ impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
                     }
                 }
 
-                state.map(|(map, _)| (map, ActionResult::List(results)))
+                state.map(|(map, green)| (map, ActionResult::List(Rc::new(results)), green))
             }
             RuleBody::Sequence(subs) => {
-                let mut state = ParseResult::new_ok((HashMap::new(), ()), pos);
+                let mut state = ParseResult::new_ok((HashMap::new(), vec![]), pos);
                 for sub in subs {
                     let res = self.parse_sequence(state, |s, p| s.parse_expr(p, rules, sub));
                     state = res.map(|(mut l, r)| {
                         for (k, v) in r.0.into_iter() {
                             l.0.insert(k, v);
                         }
+                        push_green(&mut l.1, r.2);
                         l
                     });
                 }
-                state.map(|(map, _)| (map, ActionResult::Error))
+                state.map(|(map, green)| (map, ActionResult::Error, green))
             }
             RuleBody::Choice(subs) => {
                 //TODO should empty choices be allowed? If so, what error should that give?
                 let mut state = ParseResult::new_err(pos, vec![]);
+                self.backtrack_push(pos, true);
                 for sub in subs {
                     state = self.parse_choice(pos, state, |s, p| s.parse_expr(p, rules, sub));
                 }
-                state.map(|(_, v)| (HashMap::new(), v))
+                self.backtrack_pop();
+                state.map(|(_, v, green)| (HashMap::new(), v, green))
             }
             RuleBody::NameBind(name, sub) => {
                 let res = self.parse_expr(pos, rules, sub);
@@ -188,22 +552,143 @@ impl<'grm, 'src> ParserState<'grm, 'src, PR<'grm>> {
                 })
             }
             RuleBody::SliceInput(sub) => {
+                self.keep_push(pos);
                 let res = self.parse_expr(pos, rules, sub);
+                self.keep_pop();
                 let new_pos = res.pos();
-                res.map(|_| (HashMap::new(), ActionResult::Value((pos, new_pos))))
+                res.map(|(_, _, green)| (HashMap::new(), self.input_value(pos, new_pos), green))
             }
             RuleBody::Error(sub, err_label) => {
+                let committed = self.committed();
                 let res = self.parse_expr(pos, rules, sub);
+                //If this expression passed a cut, the error is reported where it went wrong
+                let cut = !committed && self.committed();
                 res.map_errs(|mut err| {
+                    if cut {
+                        return err;
+                    }
                     err.labels = vec![ParseErrorLabel::Error(err_label)];
                     err.start = Some(pos);
                     err
                 })
             }
+            RuleBody::Cut => {
+                self.cut(pos);
+                ParseResult::new_ok((HashMap::new(), ActionResult::Error, vec![]), pos)
+            }
+            RuleBody::Block(sub) => self.parse_block(pos, |s, pos| s.parse_expr(pos, rules, sub)),
+            RuleBody::Aligned => self
+                .parse_aligned(pos)
+                .map(|()| (HashMap::new(), ActionResult::Error, vec![])),
+            RuleBody::Indented => self
+                .parse_indented(pos)
+                .map(|()| (HashMap::new(), ActionResult::Error, vec![])),
+        }
+    }
+
+    /// Lexes the token after `pos`, after what the skip rules match.
+    /// Its value is a constructor named after the kind of the token, with the span of the token,
+    /// and its concrete syntax tree has the skipped trivia and then the token.
+    /// Tokens are cached like rules, so the parser lexes the input once however often it looks at a token.
+    fn parse_token(&mut self, pos: usize, rules: &Rules<'grm>) -> ParseResult<'grm, PR<'grm>> {
+        let lex = |s: &mut Self, pos| {
+            s.lexing = true;
+            let (start, mut green) = s.skip(pos, rules);
+            let mut token: Option<(usize, usize)> = None;
+            for &id in &rules.tokens {
+                if let Ok(ok) = s.parse_rule_id(start, rules, id).inner {
+                    if ok.pos > token.map_or(start, |(_, end)| end) {
+                        token = Some((id, ok.pos));
+                    }
+                }
+            }
+            s.lexing = false;
+            match token {
+                Some((id, end)) => {
+                    let kind = rules.name(id);
+                    green.extend(cst_token(s.cst, CstKind::Token(kind), end - start));
+                    let span = ActionResult::Value((start, end));
+                    let value = ActionResult::Construct(kind, Rc::new(vec![span]));
+                    ParseResult::new_ok((HashMap::new(), value, green), end)
+                }
+                None => ParseResult::new_err(start, vec![]),
+            }
+        };
+        self.parse_cache_recurse(pos, lex, rules.lexer_id())
+    }
+
+    /// Matches the token after `pos` if `accept` accepts its kind and span, failing with `labels` otherwise
+    fn parse_token_if(
+        &mut self,
+        pos: usize,
+        rules: &Rules<'grm>,
+        labels: Vec<ParseErrorLabel<'grm>>,
+        accept: impl FnOnce(&mut Self, &'grm str, (usize, usize)) -> bool,
+    ) -> ParseResult<'grm, PR<'grm>> {
+        //The text of the token is still needed after it is lexed
+        self.keep_push(pos);
+        let result = match self.parse_token(pos, rules).inner {
+            Ok(ok) => {
+                let (_, ActionResult::Construct(kind, span), green) = ok.result else {
+                    unreachable!()
+                };
+                let ActionResult::Value(span) = span[0] else {
+                    unreachable!()
+                };
+                if accept(self, kind, span) {
+                    let value = self.input_value(span.0, span.1);
+                    ParseResult::new_ok((HashMap::new(), value, green), ok.pos)
+                } else {
+                    ParseResult::new_err(span.0, labels)
+                }
+            }
+            Err(err) => ParseResult::new_err(err.pos, labels),
+        };
+        self.keep_pop();
+        result
+    }
+
+    /// Skips what the skip rules of the lexer match from `pos` on, returning where that ends and the skipped trivia
+    fn skip(&mut self, mut pos: usize, rules: &Rules<'grm>) -> (usize, Vec<GreenElement<'grm>>) {
+        let mut trivia = vec![];
+        while let Some((id, end)) =
+            rules
+                .skips
+                .iter()
+                .find_map(|&id| match self.parse_rule_id(pos, rules, id).inner {
+                    Ok(ok) if ok.pos > pos => Some((id, ok.pos)),
+                    _ => None,
+                })
+        {
+            trivia.extend(cst_token(
+                self.cst,
+                CstKind::Trivia(rules.name(id)),
+                end - pos,
+            ));
+            pos = end;
+        }
+        (pos, trivia)
+    }
+
+    /// The value of the input in `start..end`
+    fn input_value(&self, start: usize, end: usize) -> ActionResult<'grm> {
+        if self.is_stream() {
+            //A stream is text, so this doesn't replace anything
+            ActionResult::Text(String::from_utf8_lossy(self.input_slice(start, end)).into())
+        } else {
+            ActionResult::Value((start, end))
         }
     }
 }
 
+fn cst_token(cst: bool, kind: CstKind, len: usize) -> Vec<GreenElement> {
+    if cst {
+        vec![GreenElement::Token(GreenToken { kind, len })]
+    } else {
+        vec![]
+    }
+}
+
 fn apply_action<'grm>(
     rule: &RuleAction<'grm>,
     map: &HashMap<&str, ActionResult<'grm>>,
@@ -219,7 +704,7 @@ fn apply_action<'grm>(
         RuleAction::InputLiteral(lit) => ActionResult::Literal(lit),
         RuleAction::Construct(name, args) => {
             let args_vals = args.iter().map(|a| apply_action(a, map)).collect_vec();
-            ActionResult::Construct(name, args_vals)
+            ActionResult::Construct(name, Rc::new(args_vals))
         }
     }
 }
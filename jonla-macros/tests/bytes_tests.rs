@@ -0,0 +1,92 @@
+use itertools::Itertools;
+use jonla_macros::grammar;
+use jonla_macros::grammar::GrammarFile;
+use jonla_macros::parser::parser_core::ParserState;
+use jonla_macros::parser::parser_rule::{ActionResult, Rules, PR};
+
+/// A binary format, with a header and then records that start with a tag byte
+const SYNTAX: &str = r#"
+    ast File {
+        File(records: [Record])
+    }
+
+    ast Record {
+        Name(name: Input)
+        Size(size: Input)
+        Text(text: Input)
+    }
+
+    rule byte -> Input = ['\x00'-'\xff']
+
+    rule start -> File {
+        "\x89JNL\r\n" rs:record* { File(rs) }
+    }
+
+    rule record -> Record {
+        "\x01" n:$(['\x01'-'\xff']*) "\x00" { Name(n) } /
+        "\x02" s:$(byte byte byte byte) { Size(s) } /
+        "\x03" t:$(['\x20'-'\x7e' | '\n']*) "\x00" { Text(t) }
+    }
+    "#;
+
+/// Shows a result, with the values as escaped bytes of `input`
+fn show(result: &ActionResult, input: &[u8]) -> String {
+    match result {
+        ActionResult::Value((s, e)) => format!("'{}'", input[*s..*e].escape_ascii()),
+        ActionResult::Construct(c, es) => {
+            format!("{}({})", c, es.iter().map(|e| show(e, input)).format(", "))
+        }
+        ActionResult::List(es) => format!("[{}]", es.iter().map(|e| show(e, input)).format(", ")),
+        _ => unreachable!(),
+    }
+}
+
+fn parse(input: &[u8]) -> Result<String, (usize, String)> {
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(SYNTAX).unwrap();
+    let rules = Rules::from_grammar(&grammar.rules);
+    let mut state: ParserState<PR> = ParserState::new_bytes(input);
+    match state
+        .parse_full_input(|s, p| s.parse_rule(p, &rules, "start"))
+        .inner
+    {
+        Ok(ok) => Ok(show(&ok.result.1, input)),
+        Err(err) => Err((err.pos, err.labels.iter().join(", "))),
+    }
+}
+
+#[test]
+fn bytes() {
+    assert_eq!(parse(b"\x89JNL\r\n"), Ok("File([])".to_string()));
+    assert_eq!(
+        parse(b"\x89JNL\r\n\x01caf\xe9\x00\x02\x00\xff\n\x00\x03a b\x00"),
+        Ok("File([Name('caf\\xe9'), Size('\\x00\\xff\\n\\x00'), Text('a b')])".to_string())
+    );
+    //Bytes that aren't UTF-8 or ASCII are matched like any other byte
+    assert_eq!(
+        parse(b"\x89JNL\r\n\x01\xff\xfe\x00"),
+        Ok("File([Name('\\xff\\xfe')])".to_string())
+    );
+}
+
+#[test]
+fn byte_errors() {
+    //The header is matched byte by byte, without decoding UTF-8
+    assert_eq!(parse(b"\x89JNL\n"), Err((4, "\\x89JNL\\r\\n".to_string())));
+    assert_eq!(
+        parse(b"\x89JNL\r\n\x03\x01"),
+        Err((7, "' '-~ \\n, \\x00".to_string()))
+    );
+    assert_eq!(
+        parse(b"\x89JNL\r\n\x02\x00\x00"),
+        Err((9, "\\x00-\u{ff}".to_string()))
+    );
+}
+
+#[test]
+fn byte_slices() {
+    //The input of a parser for bytes is sliced as raw bytes, which don't have to be UTF-8
+    let input = b"\x89JNL\r\n\x01\xff\xfe\x00";
+    let state: ParserState<PR> = ParserState::new_bytes(input);
+    assert_eq!(state.input_slice(7, 9), b"\xff\xfe");
+    assert_eq!(state.input_slice(0, 4), b"\x89JNL");
+}
@@ -0,0 +1,123 @@
+use jonla_macros::grammar;
+use jonla_macros::grammar::GrammarFile;
+use jonla_macros::parser::parser_core::ParserState;
+use jonla_macros::parser::parser_rule::{Rules, PR};
+
+const SYNTAX: &str = r#"
+    ast Stmt {
+        Let(name: Input, value: Expr)
+    }
+
+    ast Expr {
+        Add(l: Expr, r: Expr)
+        Num(n: Input)
+        Var(name: Input)
+    }
+
+    rule _ -> Input = [' ']*
+
+    rule identifier -> Input {
+        $(['a'-'z']+)
+    }
+
+    rule start -> [Stmt] {
+        stmt*
+    }
+
+    rule stmt -> Stmt {
+        "let" _ n:identifier _ "=" _ v:sum _ ['\n'] { Let(n, v) }
+    }
+
+    rule sum -> Expr {
+        l:sum _ "+" _ r:atom { Add(l, r) } /
+        a:atom { a }
+    }
+
+    rule atom -> Expr {
+        n:$(['0'-'9']+) { Num(n) } /
+        n:identifier { Var(n) } /
+        "(" _ e:sum _ ")" { e }
+    }
+    "#;
+
+/// A let expression contains the rest of the input, so the parser can go back to its start until the end,
+/// unless it passes the cut after `let`
+const LET_SYNTAX: &str = r#"
+    ast Expr {
+        Let(name: Input, value: Expr, body: Expr)
+        Add(l: Expr, r: Expr)
+        Num(n: Input)
+        Var(name: Input)
+    }
+
+    rule _ -> Input = [' ']*
+
+    rule identifier -> Input {
+        $(['a'-'z']+)
+    }
+
+    rule start -> Expr {
+        expr
+    }
+
+    rule expr -> Expr {
+        "let" ~ _ n:identifier _ "=" _ v:sum _ ['\n'] b:expr { Let(n, v, b) } /
+        s:sum { s }
+    }
+
+    rule sum -> Expr {
+        l:sum _ "+" _ r:atom { Add(l, r) } /
+        a:atom { a }
+    }
+
+    rule atom -> Expr {
+        n:$(['0'-'9']+) { Num(n) } /
+        n:identifier { Var(n) } /
+        "(" _ e:sum _ ")" { e }
+    }
+    "#;
+
+/// Parses `input`, returning the number of cached results afterwards and the largest number of them during the parse
+fn cache_sizes(syntax: &str, input: &str, evict: bool) -> (usize, usize) {
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(syntax).unwrap();
+    let rules = Rules::from_grammar(&grammar.rules);
+    let mut state: ParserState<PR> = ParserState::new(input);
+    if evict {
+        state = state.with_eviction();
+    }
+    let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    assert!(result.is_ok());
+    (state.cache_entries(), state.cache_peak())
+}
+
+/// `n` statements
+fn stmts(n: usize) -> String {
+    "let a = (1 + b) + 3\n".repeat(n)
+}
+
+/// `n` nested let expressions
+fn lets(n: usize) -> String {
+    format!("{}a", stmts(n))
+}
+
+#[test]
+fn lexical_rules_are_not_cached() {
+    //`_` and `identifier` don't refer to other rules, so only `start`, `stmt`, `sum` and `atom` are cached:
+    //`start` and `stmt` at 0, `stmt` at the end, `sum` and `atom` at both parentheses, and `atom` at `b` and `3`
+    assert_eq!(cache_sizes(SYNTAX, &stmts(1), false).0, 9);
+}
+
+#[test]
+fn eviction_bounds_the_cache() {
+    let peak = |n: usize, evict: bool| cache_sizes(SYNTAX, &stmts(n), evict).1;
+    assert!(peak(100, false) > 9 * peak(10, false));
+    assert_eq!(peak(100, true), peak(10, true));
+}
+
+#[test]
+fn cuts_allow_eviction() {
+    let without_cut = LET_SYNTAX.replace('~', "");
+    let peak = |syntax: &str, n: usize| cache_sizes(syntax, &lets(n), true).1;
+    assert!(peak(&without_cut, 30) > 2 * peak(&without_cut, 10));
+    assert_eq!(peak(LET_SYNTAX, 30), peak(LET_SYNTAX, 10));
+}
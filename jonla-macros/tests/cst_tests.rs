@@ -0,0 +1,108 @@
+use jonla_macros::grammar;
+use jonla_macros::grammar::GrammarFile;
+use jonla_macros::parser::parser_core::ParserState;
+use jonla_macros::parser::parser_cst::{tokens_to_string, CstKind, GreenNode, SyntaxNode};
+use jonla_macros::parser::parser_result::ParseResult;
+use jonla_macros::parser::parser_rule::{Rules, PR};
+
+fn parse_cst(syntax: &'static str, input: &'static str) -> SyntaxNode<'static> {
+    let grammar: GrammarFile = match grammar::grammar_def::toplevel(syntax) {
+        Ok(ok) => ok,
+        Err(err) => {
+            panic!("{}", err);
+        }
+    };
+    let rules: Rules<'static> = grammar
+        .rules
+        .iter()
+        .map(|r| (r.name, r.body.clone()))
+        .collect();
+
+    let mut state: ParserState<'static, 'static, PR<'static>> = ParserState::new_with_cst(input);
+    let result: ParseResult<'static, PR<'static>> =
+        state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    match result.inner {
+        Ok(ok) => SyntaxNode::new_root(GreenNode::new(CstKind::Rule("start"), ok.result.2)),
+        Err(err) => {
+            err.display(input);
+            panic!();
+        }
+    }
+}
+
+const ARITH: &str = r##"
+    ast Expr {
+        Add(l: Expr, r: Expr)
+        Num(n: Input)
+    }
+
+    rule _ -> Input = ([' ' | '\n'] / "#" ['a'-'z' | ' ']* ['\n'])*
+
+    rule num -> Input {
+        $(['0'-'9']+)
+    }
+
+    rule start -> Expr {
+        _ e:expr _ {e}
+    }
+
+    rule expr -> Expr {
+        l:num _ "+" _ r:expr { Add(Num(l), r) } /
+        n:num { Num(n) }
+    }
+    "##;
+
+#[test]
+fn cst_lossless() {
+    for input in ["1+2", "  12 +  3 ", "# one\n1 + # two\n 2\n", "7"] {
+        let tree = parse_cst(ARITH, input);
+        assert_eq!(tree.text_range(), (0, input.len()));
+        assert_eq!(tokens_to_string(&tree.tokens(), input), input);
+    }
+}
+
+#[test]
+fn cst_trivia() {
+    let input = "1 # comment\n+2";
+    let tree = parse_cst(ARITH, input);
+    let trivia = tree
+        .tokens()
+        .into_iter()
+        .filter(|t| t.is_trivia())
+        .map(|t| t.text(input).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(trivia, vec![" # comment\n"]);
+}
+
+#[test]
+fn cst_dump() {
+    let input = "1 + 23";
+    let tree = parse_cst(ARITH, input);
+    assert_eq!(
+        tree.debug_dump(input),
+        r#"start@0..6
+  expr@0..6
+    num@0..1
+      Text@0..1 "1"
+    Trivia(_)@1..2 " "
+    "+"@2..3 "+"
+    Trivia(_)@3..4 " "
+    expr@4..6
+      num@4..6
+        Text@4..6 "23"
+"#
+    );
+}
+
+#[test]
+fn cst_not_built_by_default() {
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(ARITH).unwrap();
+    let rules: Rules<'static> = grammar
+        .rules
+        .iter()
+        .map(|r| (r.name, r.body.clone()))
+        .collect();
+    let mut state: ParserState<'static, 'static, PR<'static>> = ParserState::new("1 + 2");
+    let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    assert!(result.inner.unwrap().result.2.is_empty());
+}
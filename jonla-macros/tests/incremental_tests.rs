@@ -0,0 +1,210 @@
+use jonla_macros::grammar;
+use jonla_macros::grammar::GrammarFile;
+use jonla_macros::parser::parser_core::{ParserState, TextEdit};
+use jonla_macros::parser::parser_result::ParseResult;
+use jonla_macros::parser::parser_rule::{Rules, PR};
+
+const SYNTAX: &str = r#"
+    ast Expr {
+        Add(l: Expr, r: Expr)
+        Mul(l: Expr, r: Expr)
+        Num(n: Input)
+        Let(name: Input, value: Expr, body: Expr)
+        Var(name: Input)
+    }
+
+    rule _ -> Input = [' ']*
+
+    rule num -> Input {
+        $(['0'-'9']+)
+    }
+
+    rule identifier -> Input {
+        $(['a'-'z']+)
+    }
+
+    rule start -> Expr {
+        _ e:expr _ {e}
+    }
+
+    rule expr -> Expr {
+        "let" _ n:identifier _ "=" _ v:sum _ ['\n'] _ b:expr { Let(n, v, b) } /
+        s:sum { s }
+    }
+
+    rule sum -> Expr {
+        l:sum _ "+" _ r:product { Add(l, r) } /
+        p:product { p }
+    }
+
+    rule product -> Expr {
+        l:product _ "*" _ r:atom { Mul(l, r) } /
+        a:atom { a }
+    }
+
+    rule atom -> Expr {
+        n:num { Num(n) } /
+        n:identifier { Var(n) } /
+        "(" _ e:sum _ ")" { e }
+    }
+    "#;
+
+/// Items of which the bodies are nested blocks, see `parse_test! layout` in the parser tests
+const LAYOUT_SYNTAX: &str = r#"
+    ast Expr {
+        Items(first: Expr, rest: Expr)
+        Item(name: Input, body: Expr)
+        Var(name: Input)
+    }
+
+    rule _ -> Input = [' ']*
+    rule __ -> Input = [' ' | '\n']*
+
+    rule identifier -> Input {
+        $(['a'-'z']+)
+    }
+
+    rule start -> Expr {
+        __ b:@block(items) __ { b }
+    }
+
+    rule items -> Expr {
+        i:item __ @aligned r:items { Items(i, r) } /
+        i:item { i }
+    }
+
+    rule item -> Expr {
+        n:identifier _ ":" _ b:body { Item(n, b) }
+    }
+
+    rule body -> Expr {
+        __ @indented b:@block(items) { b } /
+        n:identifier { Var(n) }
+    }
+    "#;
+
+fn rules(syntax: &'static str) -> Rules<'static> {
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(syntax).unwrap();
+    grammar
+        .rules
+        .iter()
+        .map(|r| (r.name, r.body.clone()))
+        .collect()
+}
+
+fn show(result: ParseResult<'static, PR<'static>>, input: &str) -> Result<String, usize> {
+    match result.inner {
+        Ok(ok) => Ok(ok.result.1.to_string(input)),
+        Err(err) => Err(err.pos),
+    }
+}
+
+/// Applies `edits` one after another, checking that the incremental parse matches a parse from scratch,
+/// and that it parses no more rules than the parse from scratch, and fewer over all edits
+fn check_edits(initial: &'static str, edits: &[(usize, usize, &'static str)]) {
+    check_syntax_edits(SYNTAX, initial, edits)
+}
+
+fn check_syntax_edits(
+    syntax: &'static str,
+    initial: &'static str,
+    edits: &[(usize, usize, &'static str)],
+) {
+    let rules = rules(syntax);
+    let mut input = initial.to_string();
+    let mut state: ParserState<'static, 'static, PR<'static>> = ParserState::new(initial);
+    let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    assert!(result.is_ok());
+
+    let (mut reparsed_total, mut fresh_total) = (0, 0);
+    for &(start, old_end, replacement) in edits {
+        input.replace_range(start..old_end, replacement);
+        let leaked: &'static str = Box::leak(input.clone().into_boxed_str());
+
+        state.apply_edit(
+            leaked,
+            TextEdit {
+                start,
+                old_end,
+                new_len: replacement.len(),
+            },
+        );
+        let parsed_before = state.rules_parsed();
+        let incremental = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+        let reparsed = state.rules_parsed() - parsed_before;
+
+        let mut fresh_state: ParserState<'static, 'static, PR<'static>> = ParserState::new(leaked);
+        let fresh = fresh_state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+
+        assert_eq!(show(incremental, leaked), show(fresh, leaked));
+        assert!(
+            reparsed <= fresh_state.rules_parsed(),
+            "{:?}: parsed {} rules again, {} from scratch",
+            leaked,
+            reparsed,
+            fresh_state.rules_parsed()
+        );
+        reparsed_total += reparsed;
+        fresh_total += fresh_state.rules_parsed();
+    }
+    assert!(reparsed_total < fresh_total);
+}
+
+#[test]
+fn edit_number() {
+    check_edits("1 + 2 * 3", &[(4, 5, "25"), (0, 1, "7"), (10, 10, "4")]);
+}
+
+#[test]
+fn edit_insert_and_delete() {
+    check_edits(
+        "1 + 2 * 3 + 4",
+        &[(5, 5, " + 9"), (0, 4, ""), (0, 0, "(8 + 8) * "), (1, 2, "")],
+    );
+}
+
+#[test]
+fn edit_let_chain() {
+    check_edits(
+        "let a = 1\nlet b = a + 2\nlet c = b * b\na + b + c",
+        &[
+            (8, 9, "42"),
+            (11, 14, "let bb"),
+            (0, 0, "let z = 0\n"),
+            (10, 20, ""),
+            (30, 31, "x"),
+        ],
+    );
+}
+
+#[test]
+fn edit_through_error() {
+    check_edits(
+        "1 + 2 + 3",
+        &[(4, 5, ""), (4, 4, "("), (5, 5, "5)"), (0, 9, "x")],
+    );
+}
+
+#[test]
+fn edit_at_end() {
+    check_edits(
+        "1 + 2",
+        &[(5, 5, " "), (6, 6, "*"), (7, 7, "3"), (0, 0, " ")],
+    );
+}
+
+#[test]
+fn edit_layout() {
+    //Edits that move items in and out of blocks, of which the cache entries depend on the columns of the blocks
+    check_syntax_edits(
+        LAYOUT_SYNTAX,
+        "a: x\nb:\n  c: y\n  d: z\ne: w",
+        &[
+            (22, 22, "  "),
+            (15, 17, ""),
+            (0, 1, "aaa"),
+            (9, 9, "  "),
+            (22, 24, "f: v\n"),
+        ],
+    );
+}
@@ -0,0 +1,114 @@
+use itertools::Itertools;
+use jonla_macros::grammar;
+use jonla_macros::grammar::GrammarFile;
+use jonla_macros::parser::parser_core::ParserState;
+use jonla_macros::parser::parser_cst::{tokens_to_string, CstKind, GreenNode, SyntaxNode};
+use jonla_macros::parser::parser_rule::{Rules, PR};
+
+const SYNTAX: &str = r##"
+    ast Expr {
+        Let(name: Input, value: Expr, body: Expr)
+        Add(l: Expr, r: Expr)
+        Num(n: Input)
+        Var(name: Input)
+    }
+
+    lexer {
+        skip _ = [' ' | '\n']+
+        skip _comment = "#" [' '-'~']*
+        token ident = ['a'-'z']+
+        token num = ['0'-'9']+
+        token symbol {
+            "=" / "+" / "(" / ")" / ";"
+        }
+    }
+
+    rule start -> Expr {
+        "let" n:ident "=" v:start ";" b:start { Let(n, v, b) } /
+        s:sum { s }
+    }
+
+    rule sum -> Expr {
+        l:sum "+" r:atom { Add(l, r) } /
+        a:atom { a }
+    }
+
+    rule atom -> Expr {
+        n:num { Num(n) } /
+        n:ident { Var(n) } /
+        "(" e:start ")" { e }
+    }
+    "##;
+
+fn rules() -> Rules<'static> {
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(SYNTAX).unwrap();
+    Rules::from_grammar(&grammar.rules)
+}
+
+fn parse(input: &str) -> Result<String, (usize, String)> {
+    let rules = rules();
+    let mut state: ParserState<PR> = ParserState::new(input);
+    match state
+        .parse_full_input(|s, p| s.parse_rule(p, &rules, "start"))
+        .inner
+    {
+        Ok(ok) => Ok(ok.result.1.to_string(input)),
+        Err(err) => Err((err.pos, err.labels.iter().join(", "))),
+    }
+}
+
+#[test]
+fn tokens() {
+    assert_eq!(
+        parse("let x = 1 + 2; x + (y)"),
+        Ok("Let('x', Add(Num('1'), Num('2')), Add(Var('x'), Var('y')))".to_string())
+    );
+    //The lexer skips whitespace and comments between the tokens, and after the last one
+    assert_eq!(
+        parse("  let x=1 # one\n;\n( x+x ) # done"),
+        Ok("Let('x', Num('1'), Add(Var('x'), Var('x')))".to_string())
+    );
+    //A literal matches a whole token, so a keyword isn't matched at the start of an identifier
+    assert_eq!(
+        parse("letx + 1"),
+        Ok("Add(Var('letx'), Num('1'))".to_string())
+    );
+}
+
+#[test]
+fn token_errors() {
+    //Errors are at the start of a token, and expect kinds of tokens
+    assert_eq!(parse("let x  1"), Err((7, "=".to_string())));
+    assert_eq!(
+        parse("let x = ;"),
+        Err((8, "let, num, ident, (".to_string()))
+    );
+    assert_eq!(parse("(1 + 2"), Err((6, ")".to_string())));
+    //Input that isn't a token
+    assert_eq!(parse("(% 1)"), Err((1, "let, num, ident, (".to_string())));
+}
+
+#[test]
+fn token_cst() {
+    let rules = rules();
+    let input = "let x = 1; # one\n x + 2 ";
+    let mut state: ParserState<PR> = ParserState::new_with_cst(input);
+    let ok = state
+        .parse_full_input(|s, p| s.parse_rule(p, &rules, "start"))
+        .inner
+        .unwrap();
+    let tree = SyntaxNode::new_root(GreenNode::new(CstKind::Rule("start"), ok.result.2));
+    assert_eq!(tokens_to_string(&tree.tokens(), input), input);
+    let kinds = tree
+        .tokens()
+        .iter()
+        .filter_map(|t| match t.kind() {
+            CstKind::Token(kind) => Some(format!("{}:{}", t.text(input), kind)),
+            _ => None,
+        })
+        .join(" ");
+    assert_eq!(
+        kinds,
+        "let:ident x:ident =:symbol 1:num ;:symbol x:ident +:symbol 2:num"
+    );
+}
@@ -1,9 +1,8 @@
 use jonla_macros::grammar;
-use jonla_macros::grammar::{GrammarFile, RuleBody};
+use jonla_macros::grammar::GrammarFile;
 use jonla_macros::parser::parser_core::ParserState;
 use jonla_macros::parser::parser_result::ParseResult;
-use jonla_macros::parser::parser_rule::PR;
-use std::collections::HashMap;
+use jonla_macros::parser::parser_rule::{Rules, PR};
 
 macro_rules! parse_test {
     (name: $name:ident syntax: $syntax:literal passing tests: $($input_pass:literal => $expected:literal)* failing tests: $($input_fail:literal)*) => {
@@ -16,8 +15,7 @@ macro_rules! parse_test {
                     panic!("{}", err);
                 }
             };
-            let rules: HashMap<&'static str, RuleBody<'static>> =
-                grammar.rules.iter().map(|r| (r.name, r.body.clone())).collect();
+            let rules: Rules<'static> = Rules::from_grammar(&grammar.rules);
 
             $(
             let input: &'static str = $input_pass;
@@ -35,6 +33,11 @@ macro_rules! parse_test {
                     panic!();
                 }
             }
+            //Removing cache entries that can't be used anymore doesn't change the result
+            let mut state: ParserState<'static, 'static, PR<'static>> = ParserState::new(input).with_eviction();
+            let result: ParseResult<'static, PR<'static>> =
+                state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+            assert_eq!($expected, result.inner.unwrap().result.1.to_string(input));
             )*
 
             $(
@@ -121,6 +124,190 @@ failing tests:
     "8w"
 }
 
+parse_test! {
+name: charclass_unicode
+syntax: r#"
+    rule start -> Input {
+        $([ \p{XID_Start} | '_' ][ \p{XID_Continue} ]* [ \p{Nd} | \p{P} ]?)
+    }
+    "#
+passing tests:
+    "x" => "'x'"
+    "_1" => "'_1'"
+    "αβ" => "'αβ'"
+    "naïve" => "'naïve'"
+    "x٣" => "'x٣'"
+    "x!" => "'x!'"
+    "日本" => "'日本'"
+
+failing tests:
+    "1x"
+    "→"
+    "x→"
+    "x y"
+    ""
+}
+
+parse_test! {
+name: charclass_negated
+syntax: r#"
+    rule start -> Input {
+        "\"" s:$([^ '\"' | '\\' | \p{Cc}]*) "\"" { s }
+    }
+    "#
+passing tests:
+    "\"\"" => "''"
+    "\"a b\"" => "'a b'"
+    "\"λ → ∀\"" => "'λ → ∀'"
+
+failing tests:
+    "\"a\"b\""
+    "\"a\\\""
+    "\"a\nb\""
+}
+
+parse_test! {
+name: charclass_excluded
+syntax: r#"
+    rule start -> Input {
+        $([ \p{XID_Start} | '_' -- 'λ' | 'a'-'c' ][ \p{XID_Continue} ]*)
+    }
+    "#
+passing tests:
+    "x" => "'x'"
+    "_a" => "'_a'"
+    "dλ" => "'dλ'"
+    "αβ" => "'αβ'"
+
+failing tests:
+    "λx"
+    "bx"
+    "1"
+}
+
+parse_test! {
+name: literal_escapes
+syntax: r#"
+    rule start -> Input {
+        "\u{3bb}\\\t" / "\u{1F600}" / [ '\u{2190}'-'\u{2193}' ]
+    }
+    "#
+passing tests:
+    "λ\\\t" => "'λ\\\t'"
+    "😀" => "'😀'"
+    "→" => "'→'"
+
+failing tests:
+    "λ\\"
+    "λ\\t"
+    "\\u{3bb}"
+    "⇒"
+}
+
+parse_test! {
+name: literal_set
+syntax: r#"
+    rule start -> Input {
+        $(["-" | "->" | "→" | "=" | "=="] ["let" | "in"]i ["a" | "ab"] "c")
+    }
+    "#
+passing tests:
+    "->letac" => "'->letac'"
+    "-LETac" => "'-LETac'"
+    "==Inac" => "'==Inac'"
+    //The longest literal of a set is matched, not the first
+    "→lEtabc" => "'→lEtabc'"
+
+failing tests:
+    "-"
+    "=>letac"
+    "->leac"
+    "--letac"
+    "->letc"
+    ""
+}
+
+parse_test! {
+name: literal_case_insensitive
+syntax: r#"
+    ast Stmt {
+        Let(name: Input)
+    }
+
+    rule start -> Stmt {
+        "let"i " " n:$(['a'-'z' | 'A'-'Z']+) { Let(n) }
+    }
+    "#
+passing tests:
+    "let x" => "Let('x')"
+    "LET x" => "Let('x')"
+    "Let Let" => "Let('Let')"
+
+failing tests:
+    "lett x"
+    "le x"
+    "λet x"
+}
+
+parse_test! {
+name: literal_then_bind
+syntax: r#"
+    rule name -> Input {
+        $(['a'-'z']+)
+    }
+
+    rule start -> Input {
+        "x"i:name { i }
+    }
+    "#
+passing tests:
+    //The `i` is the name of a bind, not a flag of the literal
+    "xab" => "'ab'"
+
+failing tests:
+    "Xab"
+    "x"
+}
+
+parse_test! {
+name: lexer_literal_set
+syntax: r#"
+    ast Expr {
+        Let(name: Input, value: Expr)
+        Op(l: Expr, op: Input, r: Expr)
+        Var(name: Input)
+    }
+
+    lexer {
+        skip _ = [' ']+
+        token word = ['a'-'z' | 'A'-'Z']+
+        token symbol = ["=" | "->" | "-" | "=>"]
+    }
+
+    rule start -> Expr {
+        ["let" | "val"]i n:word "=" v:start { Let(n, v) } /
+        l:var o:["->" | "=>"] r:start { Op(l, o, r) } /
+        v:var { v }
+    }
+
+    rule var -> Expr {
+        n:word { Var(n) }
+    }
+    "#
+passing tests:
+    "a" => "Var('a')"
+    "a -> b=>c" => "Op(Var('a'), '->', Op(Var('b'), '=>', Var('c')))"
+    "LET x = Val y = a" => "Let('x', Let('y', Var('a')))"
+    //A literal matches a whole token
+    "letx" => "Var('letx')"
+
+failing tests:
+    "a - b"
+    "a -> "
+    "let = a"
+    "lets x = a"
+}
+
 parse_test! {
 name: repeat_star
 syntax: r#"
@@ -371,6 +558,7 @@ syntax: r#"
         Num(n: Input)
     }
 
+    #[skip]
     rule _ -> Input = [' ']*
 
     rule num -> Input {
@@ -382,21 +570,21 @@ syntax: r#"
     }
 
     rule expr -> Expr {
-        l:expr2 _ "+" _ r:expr { Add(l, r) } /
-        l:expr2 _ "-" _ r:expr { Sub(l, r) } /
+        l:expr2 "+" r:expr { Add(l, r) } /
+        l:expr2 "-" r:expr { Sub(l, r) } /
         s:expr2 { s }
     }
     rule expr2 -> Expr {
-        l:expr3 _ "*" _ r:expr2 { Mul(l, r) } /
-        l:expr3 _ "/" _ r:expr2 { Div(l, r) } /
+        l:expr3 "*" r:expr2 { Mul(l, r) } /
+        l:expr3 "/" r:expr2 { Div(l, r) } /
         s:expr3 { s }
     }
     rule expr3 -> Expr {
-        l:expr3 _ "^" _ r:expr4 { Pow(l, r) } /
+        l:expr3 "^" r:expr4 { Pow(l, r) } /
         s:expr4 { s }
     }
     rule expr4 -> Expr {
-        "-" _ e:expr4 { Neg(e) } /
+        "-" e:expr4 { Neg(e) } /
         e:num { Num(e) }
     }
     "#
@@ -416,3 +604,274 @@ failing tests:
     "1+"
     "+1"
 }
+
+/// The position and explanation of the error of parsing `input` with the `start` rule of `syntax`
+fn parse_error(syntax: &'static str, input: &'static str) -> (usize, String) {
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(syntax).unwrap();
+    let rules: Rules<'static> = grammar
+        .rules
+        .iter()
+        .map(|r| (r.name, r.body.clone()))
+        .collect();
+    let mut state: ParserState<'static, 'static, PR<'static>> = ParserState::new(input);
+    let result: ParseResult<'static, PR<'static>> =
+        state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    let err = result.inner.err().unwrap();
+    (err.pos, err.message().unwrap())
+}
+
+#[test]
+fn left_recursion_errors() {
+    let syntax = r#"
+        rule num -> Input {
+            $(['0'-'9']+)
+        }
+
+        rule start -> Input {
+            $(start "+" num) /
+            num
+        }
+        "#;
+    //A left recursive rule that matches nothing reports what its alternatives expected
+    assert_eq!(parse_error(syntax, "x"), (0, "Expected: 0-9".to_string()));
+    assert_eq!(parse_error(syntax, ""), (0, "Expected: 0-9".to_string()));
+}
+
+#[test]
+fn error_labels_are_listed_once() {
+    let syntax = r#"
+        rule start -> Input {
+            "a" "b" /
+            "a" "c" /
+            "d"
+        }
+        "#;
+    assert_eq!(parse_error(syntax, "x"), (0, "Expected: a, d".to_string()));
+    assert_eq!(parse_error(syntax, "ax"), (1, "Expected: b, c".to_string()));
+}
+
+parse_test! {
+name: skip
+syntax: r#"
+    ast Expr {
+        Call(f: Input, args: [Expr])
+        Var(name: Input)
+    }
+
+    #[skip]
+    rule _ -> Input = ([' '] / "--" ['a'-'z' | ' ']* ['\n'])*
+    rule _w -> Input = [' ']+
+
+    #[no_skip]
+    rule identifier -> Input {
+        $(['a'-'z'] ['a'-'z' | '0'-'9']*)
+    }
+
+    rule start -> Expr {
+        _ e:expr _ { e }
+    }
+
+    rule expr -> Expr {
+        f:identifier "(" args:(a:expr ","? { a })* ")" { Call(f, args) } /
+        "do" _w x:identifier { Var(x) } /
+        x:identifier { Var(x) }
+    }
+    "#
+passing tests:
+    "f()" => "Call('f', [])"
+    " f ( x , g(y) ) " => "Call('f', [Var('x'), Call('g', [Var('y')])])"
+    "f(x,y)" => "Call('f', [Var('x'), Var('y')])"
+    "f -- call f\n(x)" => "Call('f', [Var('x')])"
+    "do  x" => "Var('x')"
+    "x1" => "Var('x1')"
+
+failing tests:
+    //Nothing is skipped in an identifier
+    "x 1"
+    //Nor next to a rule that says itself what goes there
+    "do -- x\nx"
+}
+
+parse_test! {
+name: memo_attributes
+syntax: r#"
+    ast Expr {
+        Add(l: Expr, r: Expr)
+        Num(n: Input)
+    }
+
+    #[memo]
+    rule _ -> Input = [' ']*
+
+    #[no_memo]
+    rule num -> Input {
+        $(['0'-'9']+)
+    }
+
+    rule start -> Expr {
+        _ e:sum _ {e}
+    }
+
+    #[memo]
+    rule sum -> Expr {
+        l:sum _ "+" _ r:atom { Add(l, r) } /
+        a:atom { a }
+    }
+
+    #[no_memo]
+    rule atom -> Expr {
+        n:num { Num(n) } /
+        "(" _ s:sum _ ")" { s }
+    }
+    "#
+passing tests:
+    "1" => "Num('1')"
+    "1 + 2 + 3" => "Add(Add(Num('1'), Num('2')), Num('3'))"
+    " 1 + (2 + 3) " => "Add(Num('1'), Add(Num('2'), Num('3')))"
+
+failing tests:
+    ""
+    "1 +"
+    "(1"
+}
+
+parse_test! {
+name: cut
+syntax: r#"
+    ast Expr {
+        Let(name: Input, value: Expr, body: Expr)
+        App(f: Input, a: Input)
+        Var(name: Input)
+        Tuple(es: [Input])
+    }
+
+    rule identifier -> Input {
+        $(['a'-'z']+)
+    }
+
+    rule start -> Expr {
+        "let " ~ n:identifier " = " v:start "; " b:start { Let(n, v, b) } /
+        "(" ~ es:(e:identifier ","? {e})* ")" { Tuple(es) } /
+        ("<" ~ n:identifier) ">" { Var(n) } /
+        "<" n:identifier { Var(n) } /
+        f:identifier " " a:identifier { App(f, a) } /
+        n:identifier { Var(n) }
+    }
+    "#
+passing tests:
+    "let x = y; x" => "Let('x', Var('y'), Var('x'))"
+    "letter" => "Var('letter')"
+    "f x" => "App('f', 'x')"
+    "(a,b)" => "Tuple(['a', 'b'])"
+    "()" => "Tuple([])"
+    "<a>" => "Var('a')"
+
+failing tests:
+    //Without the cut, this would be the application of `let`
+    "let x"
+    "let x = y"
+    "(a,"
+    //The cut is in a part of the alternative that succeeded
+    "<a"
+}
+
+#[test]
+fn cut_errors() {
+    let syntax = r#"
+        rule identifier -> Input {
+            $(['a'-'z']+)
+        }
+
+        rule start -> Input {
+            "let " ~ identifier " = " start "; " start /
+            "(" ~ (identifier ","?)* ")" /
+            identifier " " identifier /
+            identifier
+        }
+        "#;
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(syntax).unwrap();
+    let rules = Rules::from_grammar(&grammar.rules);
+    let error = |input: &str| {
+        let mut state: ParserState<PR> = ParserState::new(input);
+        let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+        let err = result.inner.err().unwrap();
+        (err.pos, err.message().unwrap())
+    };
+    //A committed failure is reported where the committed alternative went wrong
+    assert_eq!(error("let x"), (5, "Expected: a-z,  = ".to_string()));
+    assert_eq!(
+        error("let x = y; (a b"),
+        (13, "Expected: a-z, ,, )".to_string())
+    );
+}
+
+parse_test! {
+name: layout
+syntax: r#"
+    ast Expr {
+        Items(first: Expr, rest: Expr)
+        Item(name: Input, body: Expr)
+        Var(name: Input)
+    }
+
+    rule _ -> Input = [' ']*
+    rule __ -> Input = [' ' | '\n']*
+
+    rule identifier -> Input {
+        $(['a'-'z']+)
+    }
+
+    rule start -> Expr {
+        __ b:@block(items) __ { b }
+    }
+
+    rule items -> Expr {
+        i:item __ @aligned r:items { Items(i, r) } /
+        i:item { i }
+    }
+
+    rule item -> Expr {
+        n:identifier _ ":" _ b:body { Item(n, b) }
+    }
+
+    rule body -> Expr {
+        __ @indented b:@block(items) { b } /
+        n:identifier { Var(n) }
+    }
+    "#
+passing tests:
+    "a: x" => "Item('a', Var('x'))"
+    "a: x\nb: y" => "Items(Item('a', Var('x')), Item('b', Var('y')))"
+    "a:\n  b: x\n  c: y\nd: z" => "Items(Item('a', Items(Item('b', Var('x')), Item('c', Var('y')))), Item('d', Var('z')))"
+    //The column of a block is where its first item starts
+    "  a: b: x\n     c: y\n  d: z" => "Items(Item('a', Items(Item('b', Var('x')), Item('c', Var('y')))), Item('d', Var('z')))"
+
+failing tests:
+    //An item of a block that isn't aligned with it
+    "a: x\n b: y"
+    "a:\n  b: x\n   c: y"
+    "a:\n  b: x\n c: y"
+    //A block in an item is indented further than it
+    "a:\nb: x"
+}
+
+#[test]
+fn literal_set_errors() {
+    let syntax = r#"
+        rule start -> Input {
+            ["let" | "left" | "in" | "->"]i "!"
+        }
+        "#;
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(syntax).unwrap();
+    let rules = Rules::from_grammar(&grammar.rules);
+    let error = |input: &str| {
+        let mut state: ParserState<PR> = ParserState::new(input);
+        let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+        let err = result.inner.err().unwrap();
+        (err.pos, err.message().unwrap())
+    };
+    //The error expects the literals that matched the most input, rather than their next characters
+    assert_eq!(error("x"), (0, "Expected: let, left, in, ->".to_string()));
+    assert_eq!(error("LEx"), (2, "Expected: let, left".to_string()));
+    assert_eq!(error("Left?"), (4, "Expected: !".to_string()));
+}
@@ -0,0 +1,132 @@
+use jonla_macros::grammar;
+use jonla_macros::grammar::GrammarFile;
+use jonla_macros::parser::parser_core::ParserState;
+use jonla_macros::parser::parser_result::ParseResult;
+use jonla_macros::parser::parser_rule::{Rules, PR};
+use std::io;
+use std::io::Read;
+
+const SYNTAX: &str = r#"
+    ast Stmt {
+        Let(name: Input, value: Expr)
+    }
+
+    ast Expr {
+        Add(l: Expr, r: Expr)
+        Num(n: Input)
+        Var(name: Input)
+    }
+
+    rule _ -> Input = [' ']*
+
+    rule identifier -> Input {
+        $(['a'-'z' | 'é' | 'λ']+)
+    }
+
+    rule start -> [Stmt] {
+        stmt*
+    }
+
+    rule stmt -> Stmt {
+        "let" ~ _ n:identifier _ "=" _ v:sum _ ['\n'] { Let(n, v) }
+    }
+
+    rule sum -> Expr {
+        l:sum _ "+" _ r:atom { Add(l, r) } /
+        a:atom { a }
+    }
+
+    rule atom -> Expr {
+        n:$(['0'-'9']+) { Num(n) } /
+        n:identifier { Var(n) } /
+        "(" _ e:sum _ ")" { e }
+    }
+    "#;
+
+fn rules() -> Rules<'static> {
+    let grammar: GrammarFile = grammar::grammar_def::toplevel(SYNTAX).unwrap();
+    Rules::from_grammar(&grammar.rules)
+}
+
+fn stmts(n: usize) -> String {
+    "let é = (1 + bλ) + 3\n".repeat(n)
+}
+
+/// Parses the input of `reader`, returning the result and the largest number of bytes of input that were in memory
+fn parse_stream(reader: impl Read) -> (ParseResult<'static, PR<'static>>, usize) {
+    let rules = rules();
+    let mut state: ParserState<PR> = ParserState::from_reader(reader);
+    let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    (result, state.input_peak())
+}
+
+/// Reads one byte at a time, so characters are split over reads
+struct Bytes<R>(R);
+
+impl<R: Read> Read for Bytes<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(&mut buf[..1])
+    }
+}
+
+/// Fails after the input of the reader
+struct Failing<R>(R);
+
+impl<R: Read> Read for Failing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf)? {
+            0 => Err(io::Error::other("disconnected")),
+            n => Ok(n),
+        }
+    }
+}
+
+#[test]
+fn streams_parse_like_strings() {
+    let input = stmts(10);
+    let rules = rules();
+    let mut state: ParserState<PR> = ParserState::new(&input);
+    let expected = state
+        .parse_full_input(|s, p| s.parse_rule(p, &rules, "start"))
+        .inner
+        .unwrap()
+        .result
+        .1
+        .to_string(&input);
+
+    //The values of a stream hold their text, so they don't need the input
+    let (result, _) = parse_stream(input.as_bytes());
+    assert_eq!(result.inner.unwrap().result.1.to_string(""), expected);
+    let (result, _) = parse_stream(Bytes(input.as_bytes()));
+    assert_eq!(result.inner.unwrap().result.1.to_string(""), expected);
+}
+
+#[test]
+fn streams_keep_a_window() {
+    let input = stmts(5000);
+    let (result, peak) = parse_stream(input.as_bytes());
+    assert!(result.is_ok());
+    assert!(peak * 5 < input.len(), "{} of {}", peak, input.len());
+    assert_eq!(parse_stream(stmts(20000).as_bytes()).1, peak);
+}
+
+#[test]
+fn stream_errors() {
+    let rules = rules();
+    let input = stmts(3);
+    let mut invalid = input.clone().into_bytes();
+    invalid[30] = 0xff;
+    let mut state: ParserState<PR> = ParserState::from_reader(&invalid[..]);
+    let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    assert_eq!(result.inner.err().unwrap().pos, 30);
+    assert_eq!(
+        state.read_error().unwrap().kind(),
+        io::ErrorKind::InvalidData
+    );
+
+    let mut state: ParserState<PR> = ParserState::from_reader(Failing(input.as_bytes()));
+    let result = state.parse_full_input(|s, p| s.parse_rule(p, &rules, "start"));
+    //The input ended early, but it is all there
+    assert!(result.is_ok());
+    assert_eq!(state.read_error().unwrap().to_string(), "disconnected");
+}